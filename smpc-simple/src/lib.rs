@@ -0,0 +1,108 @@
+//! Reference implementation of the product protocol, run in one process that holds every party's input.
+//!
+//! The same ciphertexts are computed as in the distributed protocol, party after party, but nothing is sent
+//! anywhere and nothing is hidden from anyone. Its only use is as an oracle to check the websocket
+//! implementation against.
+use curv::arithmetic::traits::{Modulo, Samplable};
+use kzen_paillier::{BigInt, Decrypt, EncryptionKey, EncryptWithChosenRandomness, KeyGeneration, Paillier, Randomness, RawCiphertext, RawPlaintext};
+use serde::{Serialize, Serializer};
+
+/// Every value computed in a local run of the product protocol. Serialized, ciphertexts and shares are hex
+/// strings as in the websocket messages, while the signed inputs and product are decimal strings.
+#[derive(Debug, Clone, Serialize)]
+pub struct Transcript {
+    // modulus of the first party's Paillier key, the only key of the protocol
+    pub n: BigInt,
+    // inputs of the parties, in order
+    #[serde(serialize_with = "decimals")]
+    pub inputs: Vec<BigInt>,
+    // E(x_0 * ... * x_i), computed by party i on the way forward
+    pub forward: Vec<BigInt>,
+    // E(x_0 * ... * x_(k-1) - r_(k-1) - ... - r_i), computed by party i on the way back, from the last party to party 1
+    pub backward: Vec<BigInt>,
+    // additive shares of the product mod n, indexed by party: r_i for every party but the first, which decrypts the rest
+    pub shares: Vec<BigInt>,
+    // sum of the shares mod n, as a signed integer
+    #[serde(serialize_with = "decimal")]
+    pub product: BigInt,
+}
+
+/// Run the product protocol over `inputs` under a Paillier key of `bits` bits.
+///
+/// Negative inputs are represented as n - |x|, and the product is decoded back to a signed integer, which is
+/// only right while |product| < n / 2.
+///
+/// # Panics
+///
+/// If `inputs` is empty.
+pub fn run_local_product(inputs: &[BigInt], bits: usize) -> Transcript {
+    assert!(!inputs.is_empty(), "the product of no inputs is not defined");
+    let (ek, dk) = Paillier::keypair_with_modulus_size(bits).keys();
+    let n = ek.n.clone();
+    let plaintexts: Vec<BigInt> = inputs.iter().map(|x| x.modulus(&n)).collect();
+
+    // the first party encrypts its input, and every next one raises the ciphertext to its own
+    let mut forward = vec![encrypt(&ek, &plaintexts[0])];
+    for x in &plaintexts[1..] {
+        let last = &forward[forward.len() - 1];
+        forward.push(BigInt::mod_pow(last, x, &ek.nn));
+    }
+
+    // on the way back every party but the first subtracts a random share of its own from the plaintext
+    let mut shares = vec![BigInt::from(0); inputs.len()];
+    let mut backward: Vec<BigInt> = Vec::new();
+    for i in (1..inputs.len()).rev() {
+        let r = BigInt::sample_below(&n);
+        let minus_r = encrypt(&ek, &BigInt::mod_sub(&BigInt::from(0), &r, &n));
+        let last = backward.last().unwrap_or(&forward[forward.len() - 1]);
+        backward.push(BigInt::mod_mul(last, &minus_r, &ek.nn));
+        shares[i] = r;
+    }
+
+    // the first party decrypts what is left, its own share
+    let last = backward.last().unwrap_or(&forward[forward.len() - 1]);
+    shares[0] = Paillier::decrypt(&dk, RawCiphertext::from(last)).0.into_owned();
+
+    let sum = shares.iter().fold(BigInt::from(0), |acc, share| BigInt::mod_add(&acc, share, &n));
+    let product = if &sum + &sum > n { sum - &n } else { sum };
+    Transcript { n, inputs: inputs.to_vec(), forward, backward, shares, product }
+}
+
+// curv writes a BigInt as the hex of its magnitude, which loses the sign
+fn decimal<S: Serializer>(value: &BigInt, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&value.to_string())
+}
+
+fn decimals<S: Serializer>(values: &[BigInt], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(values.iter().map(|v| v.to_string()))
+}
+
+fn encrypt(ek: &EncryptionKey, m: &BigInt) -> BigInt {
+    let r = Randomness(BigInt::sample_below(&ek.n));
+    let ct: RawCiphertext = Paillier::encrypt_with_chosen_randomness(ek, RawPlaintext::from(m), &r);
+    ct.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ints(values: &[i32]) -> Vec<BigInt> {
+        values.iter().map(|&x| BigInt::from(x)).collect()
+    }
+
+    #[test]
+    fn test_product() {
+        let transcript = run_local_product(&ints(&[1, 2, 3, 4, 1000]), 512);
+        assert_eq!(transcript.product, BigInt::from(24000));
+        assert_eq!(transcript.forward.len(), 5);
+        assert_eq!(transcript.backward.len(), 4);
+        assert_eq!(transcript.shares.len(), 5);
+    }
+
+    #[test]
+    fn test_signed_product() {
+        assert_eq!(run_local_product(&ints(&[-3, 7, -2, -5]), 512).product, BigInt::from(-210));
+        assert_eq!(run_local_product(&ints(&[-42]), 512).product, BigInt::from(-42));
+    }
+}
@@ -0,0 +1,38 @@
+//! Write JSON test vectors for a seed and a scheme to a file. The simulated session logs to stdout as usual.
+//!
+//!     cargo run -p shared --features seeded-rng --bin test-vectors -- <out.json> [seed] [Paillier|DamgardJurik|EcElGamal]
+use std::env;
+use std::fs;
+use std::process;
+
+use shared::homomorphic::SchemeKind;
+use shared::test_vectors;
+
+fn main() {
+    // runs the same parties as a client does, see shared::secret
+    shared::secret::wipe_freed_memory();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(path) = args.first() else {
+        exit("usage: test-vectors <out.json> [seed] [scheme]")
+    };
+    let seed = match args.get(1).map(|s| s.parse::<u64>()) {
+        None => 1,
+        Some(Ok(seed)) => seed,
+        Some(Err(e)) => exit(&format!("invalid seed: {}", e)),
+    };
+    let scheme = match args.get(2) {
+        None => SchemeKind::Paillier,
+        Some(name) => serde_json::from_value(name.as_str().into()).unwrap_or_else(|_| exit(&format!("unknown scheme '{}'", name))),
+    };
+    let vector = test_vectors::generate(seed, scheme);
+    let json = serde_json::to_string_pretty(&vector).unwrap_or_else(|e| exit(&e.to_string()));
+    if let Err(e) = fs::write(path, json) {
+        exit(&format!("failed to write {}: {}", path, e));
+    }
+    println!("Wrote the test vectors for seed {} under {} to {}", seed, scheme, path);
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
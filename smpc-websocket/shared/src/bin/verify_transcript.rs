@@ -0,0 +1,37 @@
+//! Check the transcript the server recorded for a session, and optionally the clients' transcripts against it:
+//!
+//!     cargo run -p shared --bin verify-transcript -- <server.jsonl> [client.jsonl...]
+//!
+//! Exits with status 1 if anything is inconsistent.
+use std::env;
+use std::process;
+
+use shared::transcript::{self, TranscriptEntry};
+
+fn main() {
+    let paths: Vec<String> = env::args().skip(1).collect();
+    let Some((server_path, client_paths)) = paths.split_first() else {
+        eprintln!("usage: verify-transcript <server.jsonl> [client.jsonl...]");
+        process::exit(1)
+    };
+    let server = read(server_path);
+    let mut violations = transcript::verify(&server);
+    for path in client_paths {
+        violations.extend(transcript::cross_check(&server, &read(path)));
+    }
+    println!("{} entries checked", server.len());
+    if violations.is_empty() {
+        return println!("The transcript is consistent");
+    }
+    for violation in &violations {
+        println!("{}", violation);
+    }
+    process::exit(1)
+}
+
+fn read(path: &str) -> Vec<TranscriptEntry> {
+    transcript::read(path).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", path, e);
+        process::exit(1)
+    })
+}
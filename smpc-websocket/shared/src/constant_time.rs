@@ -0,0 +1,61 @@
+//! Modular exponentiation with secret exponents: a party's private input in `scalar_mul`, and the decryption
+//! exponent of a secret key.
+//!
+//! curv's `mod_pow` is GMP's `mpz_powm`, a sliding window whose running time depends on the bit length and the
+//! bit pattern of the exponent, so the time a party takes to answer a round could tell its input apart. GMP's
+//! `mpz_powm_sec` takes the same time and touches memory the same way for any two exponents with the same number
+//! of limbs, and `pow_blinded` gives every exponent the same size before calling it.
+use curv::arithmetic::traits::{BitManipulation, Converter, Modulo};
+use gmp::mpz::Mpz;
+use kzen_paillier::BigInt;
+use zeroize::Zeroizing;
+
+use crate::rng;
+
+/// Bits of randomness masking a blinded exponent.
+pub const BLINDING_BITS: usize = 64;
+
+/// base^exponent mod modulus, in time that only depends on the number of limbs of the exponent. The modulus must
+/// be odd and the exponent non-negative.
+pub fn pow_fixed(base: &BigInt, exponent: &BigInt, modulus: &BigInt) -> BigInt {
+    assert!(modulus.test_bit(0), "constant time exponentiation needs an odd modulus");
+    assert!(*exponent >= BigInt::from(0), "constant time exponentiation needs a non-negative exponent");
+    // mpz_powm_sec requires a positive exponent
+    if *exponent == BigInt::from(0) {
+        return BigInt::from(1) % modulus;
+    }
+    // the exponent goes through plain bytes on the way, wiped once converted (see crate::secret)
+    let to_mpz = |x: &BigInt| Mpz::from(Zeroizing::new(x.to_bytes()).as_slice());
+    let result = to_mpz(base).powm_sec(&to_mpz(exponent), &to_mpz(modulus));
+    BigInt::from_bytes(&Zeroizing::new(Vec::<u8>::from(&result)))
+}
+
+/// base^(exponent + r * period) mod modulus for a random r of BLINDING_BITS bits with its top bit set, where
+/// `period` is a public value that does not change what the result stands for when added to the exponent: for
+/// a Paillier or Damgård–Jurik ciphertext, c^period is an encryption of 0, so any such power of c encrypts the
+/// same plaintext. Whatever the secret exponent, the blinded one has bitlen(period) + BLINDING_BITS bits, or one
+/// more.
+pub fn pow_blinded(base: &BigInt, exponent: &BigInt, modulus: &BigInt, period: &BigInt) -> BigInt {
+    let mask = rng::sample(BLINDING_BITS - 1) + (BigInt::from(1) << (BLINDING_BITS - 1));
+    let exponent = BigInt::modulus(exponent, period) + period * mask;
+    pow_fixed(base, &exponent, modulus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_mod_pow() {
+        let modulus = BigInt::from(1_000_003) * BigInt::from(999_983);
+        let base = BigInt::from(123_456_789);
+        for exponent in [0u64, 1, 2, 65_537, u64::MAX] {
+            let exponent = BigInt::from(exponent);
+            assert_eq!(pow_fixed(&base, &exponent, &modulus), BigInt::mod_pow(&base, &exponent, &modulus));
+        }
+        // base^period = 1 here, so blinding does not change the result
+        let (p, period) = (BigInt::from(1_000_003), BigInt::from(1_000_002));
+        let exponent = BigInt::from(4242);
+        assert_eq!(pow_blinded(&base, &exponent, &p, &period), BigInt::mod_pow(&base, &exponent, &p));
+    }
+}
@@ -0,0 +1,551 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{Display, Formatter};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::homomorphic::SchemeKind;
+use crate::profile::SecurityProfile;
+#[cfg(feature = "insecure-mock")]
+use crate::protocol::beaver;
+use crate::protocol::Registry;
+use crate::relay_log::{Checkpoint, RelayLog, SignedCheckpoint, SigningKey};
+use crate::replay::{self, ReplayGuard};
+use crate::resume;
+use crate::types::{ClientMessage, CompletionReport, Computation, Dropout, InitializeProtocol, RelayedMessage, ResumeRequest, WebsocketMessage};
+
+/// Public parameters the server announces for every computation of a session.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SessionConfig {
+    // security level, i.e. the size of the first client's key
+    pub profile: SecurityProfile,
+    // public upper bound on the bit length of each party's private input
+    pub max_input_bits: usize,
+    // decimal digits kept after the point in every private input
+    pub scale: u32,
+    // number of elements in every party's private input vector
+    pub vector_len: usize,
+    // additively homomorphic scheme the clients encrypt with
+    pub scheme: SchemeKind,
+}
+
+/// How a session ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEnd {
+    // every computation of the plan is done
+    Completed,
+    // a client aborted, a computation timed out or the plan was rejected. The reason is sent along with the
+    // close frame, and blame lists the clients held responsible, if any.
+    Aborted { reason: String, blame: Vec<usize> },
+}
+
+impl SessionEnd {
+    fn aborted(reason: impl Into<String>) -> Self {
+        SessionEnd::Aborted { reason: reason.into(), blame: vec![] }
+    }
+}
+
+impl Display for SessionEnd {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionEnd::Completed => write!(f, "completed"),
+            SessionEnd::Aborted { reason, blame } if blame.is_empty() => write!(f, "aborted: {}", reason),
+            SessionEnd::Aborted { reason, blame } => write!(f, "aborted: {} (blamed clients {:?})", reason, blame),
+        }
+    }
+}
+
+// Most messages kept for a client to send again: a client acknowledges them when it resumes and when it goes on
+// with the next computation, so only a computation that long without either fills it up.
+const MAX_UNACKNOWLEDGED: usize = 4096;
+
+// The messages sent to a client that it may still ask for again when it resumes, oldest first.
+#[derive(Default)]
+struct Outbox {
+    messages: VecDeque<Value>,
+    // number of messages sent to the client before the first one kept
+    base: usize,
+    // number of messages sent to the client up to the InitializeProtocol of the computation in progress
+    started: usize,
+}
+
+impl Outbox {
+    /// Number of messages sent to the client in the session.
+    fn len(&self) -> usize {
+        self.base + self.messages.len()
+    }
+
+    /// Keep a message, dropping the oldest one past MAX_UNACKNOWLEDGED.
+    fn push(&mut self, message: Value) {
+        self.messages.push_back(message);
+        if self.messages.len() > MAX_UNACKNOWLEDGED {
+            self.acknowledge(self.base + 1);
+        }
+    }
+
+    /// Forget the first `count` messages sent to the client, which it received.
+    fn acknowledge(&mut self, count: usize) {
+        let count = count.min(self.len()).saturating_sub(self.base);
+        self.messages.drain(..count);
+        self.base += count;
+    }
+}
+
+/// What the server does in response to a message.
+#[derive(Debug)]
+pub enum ServerAction {
+    // send a ClientMessage, as JSON, to the client with this sid
+    Send { to: usize, message: Value },
+    // close the connection to every client
+    Close(SessionEnd),
+}
+
+/// Everything the relaying server does in a session, whatever carries its messages.
+///
+/// The coordinator relays rounds between the clients, runs the computations of the plan one after the other
+/// and ends the session when they are all done or a client aborts. Messages of another session, or copies of
+/// messages already received, are dropped. Every message it sends goes into its relay log, and every finished
+/// computation ends with a signed checkpoint of the log. It keeps the messages it sent each client until the
+/// client acknowledges them, to send them again when a dropped connection is resumed (see shared::resume). A client whose connection is lost for
+/// good drops out of the session if the computation in progress tolerates it (see `Protocol::threshold`), and
+/// the session is aborted otherwise. The websocket ServerActor and the in-process simulator only move its
+/// messages around, and tell it when a computation took too long or a connection dropped.
+pub struct Coordinator {
+    config: SessionConfig,
+    // protocols the clients are expected to run, used to validate the plan before sending it
+    registry: Registry,
+    plan: Vec<Computation>,
+    num_clients: usize,
+    // index in the plan of the computation in progress
+    current: usize,
+    // parties that reported they are done with the current computation
+    completed: HashSet<usize>,
+    // number of clients that must finish the current computation if others drop out, None if all of them must
+    threshold: Option<usize>,
+    // clients dropped from the session for good. Nothing is relayed from or to them any more.
+    dropped: HashSet<usize>,
+    // bumped every time a computation starts, so that a timeout set for an earlier one is ignored
+    epoch: u64,
+    // signs the checkpoints of the relay log
+    signing_key: SigningKey,
+    // chain over every message sent in the session, and over the messages sent to each client
+    log: RelayLog,
+    client_logs: Vec<RelayLog>,
+    // id of the session in progress and the last counter accepted from every client
+    guard: ReplayGuard,
+    // resume token of every client, and the messages sent to it that it did not acknowledge, checkpoints included
+    tokens: Vec<String>,
+    outbox: Vec<Outbox>,
+    // clients whose connection dropped and that did not resume yet. Nothing is sent to them meanwhile.
+    disconnected: HashSet<usize>,
+}
+
+impl Coordinator {
+    pub fn new(config: SessionConfig, plan: Vec<Computation>) -> Self {
+        Coordinator {
+            config,
+            registry: Registry::default(),
+            plan,
+            num_clients: 0,
+            current: 0,
+            completed: HashSet::new(),
+            threshold: None,
+            dropped: HashSet::new(),
+            epoch: 0,
+            signing_key: SigningKey::generate(),
+            log: RelayLog::default(),
+            client_logs: Vec::new(),
+            guard: ReplayGuard::default(),
+            tokens: Vec::new(),
+            outbox: Vec::new(),
+            disconnected: HashSet::new(),
+        }
+    }
+
+    /// Sign checkpoints with this key rather than a fresh one.
+    pub fn with_signing_key(self, signing_key: SigningKey) -> Self {
+        Coordinator { signing_key, ..self }
+    }
+
+    pub fn signing_key(&self) -> &SigningKey {
+        &self.signing_key
+    }
+
+    /// Start the first computation of the plan with every client connected.
+    pub fn start(&mut self, num_clients: usize) -> Vec<ServerAction> {
+        self.reset();
+        self.num_clients = num_clients;
+        self.client_logs = vec![RelayLog::default(); num_clients];
+        self.guard = ReplayGuard::new(replay::session_id());
+        self.tokens = (0..num_clients).map(|_| resume::token()).collect();
+        self.outbox = (0..num_clients).map(|_| Outbox::default()).collect();
+        self.start_computation()
+    }
+
+    // reset the coordinator for potentially another round of SMPC
+    pub fn reset(&mut self) {
+        self.num_clients = 0;
+        self.current = 0;
+        self.completed = HashSet::new();
+        self.threshold = None;
+        self.dropped = HashSet::new();
+        self.log = RelayLog::default();
+        self.client_logs = Vec::new();
+        self.guard = ReplayGuard::default();
+        self.tokens = Vec::new();
+        self.outbox = Vec::new();
+        self.disconnected = HashSet::new();
+    }
+
+    /// Id of the session in progress, None if no session is running.
+    pub fn session_id(&self) -> Option<&str> {
+        (self.num_clients > 0).then(|| self.guard.session_id())
+    }
+
+    /// Epoch of the computation in progress, None if no session is running.
+    pub fn epoch(&self) -> Option<u64> {
+        (self.num_clients > 0 && self.current < self.plan.len()).then_some(self.epoch)
+    }
+
+    /// The computation started at `epoch` took too long: abort the session and blame the clients that did not
+    /// report completion. Nothing happens if that computation is already over.
+    pub fn timeout(&mut self, epoch: u64) -> Vec<ServerAction> {
+        if self.epoch() != Some(epoch) {
+            return vec![];
+        }
+        let blame: Vec<usize> = self.remaining().filter(|sid| !self.completed.contains(sid)).collect();
+        let reason = format!("computation {} timed out waiting for clients {:?}", self.plan[self.current].id, blame);
+        eprintln!("{}", reason);
+        self.close(SessionEnd::Aborted { reason, blame })
+    }
+
+    /// The connection to a client dropped: keep the messages for it until it resumes the session. Returns false
+    /// if the client is not part of the session or its connection was already known to be down.
+    pub fn disconnected(&mut self, sid: usize) -> bool {
+        if sid >= self.num_clients || !self.disconnected.insert(sid) {
+            return false;
+        }
+        println!("Connection to client {} dropped, holding its messages until it resumes", sid);
+        true
+    }
+
+    /// What to send first on a new connection to a client whose connection dropped, None if it has nothing to
+    /// resume.
+    pub fn resume_request(&self, sid: usize) -> Option<Value> {
+        if !self.disconnected.contains(&sid) {
+            return None;
+        }
+        let request = ResumeRequest {
+            session_id: self.guard.session_id().to_string(),
+            sid,
+            token: self.tokens[sid].clone(),
+            last_seq: self.guard.contiguous(sid),
+        };
+        serde_json::to_value(ClientMessage::Resume(request))
+            .map_err(|e| eprintln!("Failed to serialize resume request: {}", e))
+            .ok()
+    }
+
+    /// Whether the server still waits for a client whose connection dropped to resume the session.
+    pub fn awaiting(&self, sid: usize) -> bool {
+        self.disconnected.contains(&sid)
+    }
+
+    /// The grace period of a client whose connection dropped is over, unless it resumed in the meantime. The
+    /// client drops out of the session if enough clients remain to finish the computation in progress without
+    /// it, otherwise the session is aborted and the client blamed.
+    pub fn lost(&mut self, sid: usize) -> Vec<ServerAction> {
+        if !self.disconnected.remove(&sid) {
+            return vec![];
+        }
+        let Some(threshold) = self.threshold.filter(|&t| self.remaining().count() > t) else {
+            let reason = format!("client {} did not reconnect within the grace period", sid);
+            eprintln!("{}", reason);
+            return self.close(SessionEnd::Aborted { reason, blame: vec![sid] });
+        };
+        let computation_id = self.plan[self.current].id;
+        println!("Client {} dropped out of computation {}, {} clients remain for a threshold of {}", sid, computation_id, self.remaining().count() - 1, threshold);
+        self.dropped.insert(sid);
+        self.completed.remove(&sid);
+        let mut actions = Vec::new();
+        match serde_json::to_value(ClientMessage::Dropout(Dropout { computation_id, sid })) {
+            Ok(message) => actions.extend((0..self.num_clients).filter_map(|to| self.send(to, message.clone()))),
+            Err(e) => return self.close(SessionEnd::aborted(e.to_string())),
+        }
+        actions.extend(self.finish_computation());
+        actions
+    }
+
+    /// Handle a message received from the client at `client_index`.
+    pub fn handle_message(&mut self, msg: WebsocketMessage, client_index: usize) -> Vec<ServerAction> {
+        if self.dropped.contains(&client_index) {
+            eprintln!("Dropped a message from client {}, which dropped out of the session", client_index);
+            return vec![];
+        }
+        if let Err(e) = self.guard.check(client_index, msg.header()) {
+            eprintln!("Dropped a message: {}", e);
+            return vec![];
+        }
+        // a message of the computation in progress confirms the checkpoint of the one before, and everything
+        // sent to the client up to the start of this one
+        if self.is_current(msg.header().computation_id) && let Some(outbox) = self.outbox.get_mut(client_index) {
+            outbox.acknowledge(outbox.started);
+        }
+        match msg {
+            WebsocketMessage::Unicast(response) => {
+                // Handle the first round response
+                println!("Received Unicast from client {}: {:?}", client_index, response);
+                if response.to >= self.num_clients {
+                    eprintln!("Invalid client {} (Num client = {})", response.to + 1, self.num_clients);
+                    return vec![];
+                }
+                if !self.is_current(response.header.computation_id) {
+                    eprintln!("Client {} sent a message for computation {}, which is not running", client_index, response.header.computation_id);
+                    return vec![];
+                }
+                let relayed = RelayedMessage::wrap(client_index, response.header, response.data);
+                self.send(response.to, relayed).into_iter().collect()
+            }
+            WebsocketMessage::Broadcast(response) => {
+                println!("Received Broadcast from client {}: {:?}", client_index, response);
+                if !self.is_current(response.header.computation_id) {
+                    eprintln!("Client {} sent a message for computation {}, which is not running", client_index, response.header.computation_id);
+                    return vec![];
+                }
+                // send the response to all clients except the one broadcasting it.
+                let relayed = RelayedMessage::wrap(client_index, response.header, response.data);
+                (0..self.num_clients)
+                    .filter(|&index| index != client_index)
+                    .filter_map(|to| self.send(to, relayed.clone()))
+                    .collect()
+            }
+            WebsocketMessage::Relayer(response) => {
+                // not to forward but to indicate that this party is done with a computation
+                println!("Client {} completed a computation: {}", response.from, response.data);
+                let report = match serde_json::from_value::<CompletionReport>(response.data) {
+                    Ok(report) => report,
+                    Err(e) => {
+                        eprintln!("Failed to parse completion report: {}", e);
+                        return vec![];
+                    }
+                };
+                if !self.is_current(report.computation_id) || report.computation_id != response.header.computation_id {
+                    eprintln!("Client {} reported unexpected computation {}", response.from, report.computation_id);
+                    return vec![];
+                }
+                self.completed.insert(client_index);
+                self.finish_computation()
+            }
+            WebsocketMessage::Abort(abort) => {
+                // the connection the abort came in on says who sent it, whatever the message claims
+                eprintln!("Client {} aborted the protocol: {}", client_index, abort.reason);
+                let reason = format!("Client {} aborted: {}", client_index, abort.reason);
+                let blame = abort.blame.filter(|&sid| sid < self.num_clients).into_iter().collect();
+                self.close(SessionEnd::Aborted { reason, blame })
+            }
+            WebsocketMessage::Resumed(resumed) => {
+                let received = resumed.received as usize;
+                let outbox = &mut self.outbox[client_index];
+                if !self.disconnected.contains(&client_index) || received > outbox.len() {
+                    eprintln!("Client {} resumed unexpectedly after {} messages", client_index, received);
+                    return vec![];
+                }
+                if received < outbox.base {
+                    eprintln!("Client {} resumed after {} messages, but message {} is no longer kept to send again", client_index, received, received + 1);
+                    return vec![];
+                }
+                println!("Client {} resumed the session, sending it the messages it missed", client_index);
+                self.disconnected.remove(&client_index);
+                outbox.acknowledge(received);
+                // already in the relay log, from when they were first sent
+                outbox
+                    .messages
+                    .iter()
+                    .map(|message| ServerAction::Send { to: client_index, message: message.clone() })
+                    .collect()
+            }
+        }
+    }
+
+    /// Clients still in the session, i.e. that did not drop out of it.
+    fn remaining(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.num_clients).filter(|sid| !self.dropped.contains(sid))
+    }
+
+    /// Move on to the next computation, or end the session, once every remaining client completed the current one.
+    fn finish_computation(&mut self) -> Vec<ServerAction> {
+        if self.remaining().any(|sid| !self.completed.contains(&sid)) {
+            return vec![];
+        }
+        let computation_id = self.plan[self.current].id;
+        self.completed = HashSet::new();
+        let mut actions = self.checkpoint(computation_id);
+        self.current += 1;
+        if self.current < self.plan.len() {
+            actions.extend(self.start_computation());
+            return actions;
+        }
+        println!("All clients completed the protocol. Closing the session.");
+        actions.extend(self.close(SessionEnd::Completed));
+        actions
+    }
+
+    fn is_current(&self, computation_id: usize) -> bool {
+        self.plan.get(self.current).map(|c| c.id) == Some(computation_id)
+    }
+
+    /// Send a message to a client, appending it to the relay log. It is only kept for later if the client's
+    /// connection is down, and dropped if the client dropped out of the session.
+    fn send(&mut self, to: usize, message: Value) -> Option<ServerAction> {
+        if self.dropped.contains(&to) {
+            return None;
+        }
+        // the websocket actor and the simulator send a message as this text
+        let frame = message.to_string();
+        self.log.append(to, &frame);
+        if let Some(log) = self.client_logs.get_mut(to) {
+            log.append(to, &frame);
+        }
+        self.hold(to, message)
+    }
+
+    /// Keep a message sent to a client, and send it unless the client's connection is down.
+    fn hold(&mut self, to: usize, message: Value) -> Option<ServerAction> {
+        if self.dropped.contains(&to) {
+            return None;
+        }
+        if let Some(outbox) = self.outbox.get_mut(to) {
+            outbox.push(message.clone());
+        }
+        (!self.disconnected.contains(&to)).then_some(ServerAction::Send { to, message })
+    }
+
+    /// Send every client the signed heads of the relay log once a computation is over. Checkpoints are not
+    /// part of the log themselves.
+    fn checkpoint(&mut self, computation_id: usize) -> Vec<ServerAction> {
+        let checkpoint = Checkpoint {
+            session_id: self.guard.session_id().to_string(),
+            computation_id,
+            session: self.log.clone(),
+            clients: self.client_logs.clone(),
+        };
+        let signed = SignedCheckpoint::sign(checkpoint, &self.signing_key);
+        match serde_json::to_value(ClientMessage::Checkpoint(signed)) {
+            Ok(message) => (0..self.num_clients).filter_map(|to| self.hold(to, message.clone())).collect(),
+            Err(e) => {
+                eprintln!("Failed to serialize checkpoint of computation {}: {}", computation_id, e);
+                vec![]
+            }
+        }
+    }
+
+    fn close(&mut self, end: SessionEnd) -> Vec<ServerAction> {
+        self.reset();
+        vec![ServerAction::Close(end)]
+    }
+
+    /// Send the parameters of the current computation to every client, each with its own sid.
+    fn start_computation(&mut self) -> Vec<ServerAction> {
+        let Some(computation) = self.plan.get(self.current).cloned() else {
+            return vec![];
+        };
+        let config = self.config;
+        self.epoch += 1;
+        println!(
+            "Starting computation {}: {} {} under {} ({})",
+            computation.id, computation.protocol, computation.params, config.scheme, config.profile
+        );
+        // in test mode the server plays the dealer of Beaver triples, handing every client its own parameters
+        #[cfg(feature = "insecure-mock")]
+        let dealt = beaver::deal(&computation, self.num_clients, config.vector_len, config.profile.modulus_bits());
+        #[cfg(not(feature = "insecure-mock"))]
+        let dealt: Option<Vec<Value>> = None;
+        let mut actions = Vec::new();
+        for sid in 0..self.num_clients {
+            let init = InitializeProtocol {
+                bits_security: config.profile.modulus_bits(),
+                profile: config.profile,
+                num_parties: self.num_clients,
+                sid,
+                computation_id: computation.id,
+                protocol: computation.protocol.clone(),
+                params: dealt.as_ref().map_or_else(|| computation.params.clone(), |params| params[sid].clone()),
+                scheme: config.scheme,
+                max_input_bits: config.max_input_bits,
+                scale: config.scale,
+                vector_len: config.vector_len,
+                reveal: computation.reveal,
+                server_key: Some(self.signing_key.verifying_key().clone()),
+                session_id: self.guard.session_id().to_string(),
+                resume_token: self.tokens[sid].clone(),
+            };
+            // an unknown protocol or bad parameters would only be noticed by the clients, once the session is underway
+            match self.registry.create(&init) {
+                Ok(protocol) => self.threshold = protocol.threshold(),
+                Err(e) => {
+                    eprintln!("Invalid computation {}: {}", computation.id, e);
+                    return self.close(SessionEnd::aborted(e.to_string()));
+                }
+            }
+            let remaining = self.remaining().count();
+            if remaining < self.threshold.unwrap_or(self.num_clients) {
+                let dropped: Vec<usize> = (0..self.num_clients).filter(|sid| self.dropped.contains(sid)).collect();
+                let reason = format!("computation {} cannot run without clients {:?}, which dropped out", computation.id, dropped);
+                eprintln!("{}", reason);
+                return self.close(SessionEnd::aborted(reason));
+            }
+            match serde_json::to_value(ClientMessage::InitializeProtocol(init)) {
+                Ok(message) => actions.extend(self.send(sid, message)),
+                Err(e) => return self.close(SessionEnd::aborted(e.to_string())),
+            }
+            let outbox = &mut self.outbox[sid];
+            outbox.started = outbox.len();
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::sum;
+    use crate::types::{MessageHeader, ResumedMessage, RevealMode};
+
+    fn resumed(coordinator: &Coordinator, from: usize, seq: u64, received: u64) -> WebsocketMessage {
+        let header = MessageHeader { session_id: coordinator.session_id().unwrap().to_string(), computation_id: 0, seq };
+        WebsocketMessage::Resumed(ResumedMessage { from, header, received })
+    }
+
+    #[test]
+    fn test_outbox_is_bounded() {
+        let mut outbox = Outbox::default();
+        (0..MAX_UNACKNOWLEDGED + 2).for_each(|k| outbox.push(Value::from(k)));
+        assert_eq!((outbox.base, outbox.len(), outbox.messages.len()), (2, MAX_UNACKNOWLEDGED + 2, MAX_UNACKNOWLEDGED));
+        assert_eq!(outbox.messages[0], Value::from(2));
+        outbox.acknowledge(10);
+        assert_eq!((outbox.base, outbox.messages[0].clone()), (10, Value::from(10)));
+        // acknowledging less than before or more than was sent changes nothing more
+        outbox.acknowledge(5);
+        outbox.acknowledge(usize::MAX);
+        assert_eq!((outbox.base, outbox.messages.len()), (MAX_UNACKNOWLEDGED + 2, 0));
+    }
+
+    #[test]
+    fn test_resume_acknowledges_messages() {
+        let config = SessionConfig { profile: SecurityProfile::Toy512, max_input_bits: 24, scale: 2, vector_len: 2, scheme: SchemeKind::Paillier };
+        let mut coordinator = Coordinator::new(config, vec![sum::computation(0, 0, RevealMode::Public)]);
+        coordinator.start(3);
+        assert_eq!(coordinator.outbox[2].len(), 1);
+
+        // client 2 got its InitializeProtocol before its connection dropped
+        assert!(coordinator.disconnected(2));
+        let actions = coordinator.handle_message(resumed(&coordinator, 2, 1, 1), 2);
+        assert!(actions.is_empty(), "{:?}", actions);
+        assert_eq!((coordinator.outbox[2].base, coordinator.outbox[2].messages.len()), (1, 0));
+
+        // it cannot ask for messages it already acknowledged
+        assert!(coordinator.disconnected(2));
+        assert!(coordinator.handle_message(resumed(&coordinator, 2, 2, 0), 2).is_empty());
+        assert!(coordinator.awaiting(2));
+    }
+}
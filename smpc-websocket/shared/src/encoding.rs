@@ -0,0 +1,196 @@
+use std::fmt::{Display, Formatter};
+
+use curv::arithmetic::traits::{BasicOps, BitManipulation, Converter};
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{EncodingError, ProtocolError};
+use crate::secret::Wipe;
+
+/// A signed fixed-point number: `mantissa / 10^scale`.
+///
+/// Multiplying two fixed-point numbers multiplies the mantissas and adds the scales, so a product of
+/// k inputs encoded with scale s has scale k*s.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixedPoint {
+    pub mantissa: BigInt,
+    pub scale: u32,
+}
+
+impl Wipe for FixedPoint {
+    fn wipe(&mut self) {
+        self.mantissa.wipe();
+    }
+}
+
+impl FixedPoint {
+    pub fn new(mantissa: BigInt, scale: u32) -> Self {
+        FixedPoint { mantissa, scale }
+    }
+
+    pub fn mul(&self, other: &FixedPoint) -> FixedPoint {
+        FixedPoint::new(&self.mantissa * &other.mantissa, self.scale + other.scale)
+    }
+
+    /// Map the mantissa into Z_n. Negative values are represented as n - |mantissa|.
+    pub fn encode(&self, n: &BigInt) -> Result<BigInt, EncodingError> {
+        encode_signed(&self.mantissa, n)
+    }
+
+    /// Decode a value of Z_n that carries `scale` decimal digits.
+    pub fn decode(value: &BigInt, n: &BigInt, scale: u32) -> FixedPoint {
+        FixedPoint::new(decode_signed(value, n), scale)
+    }
+}
+
+impl Display for FixedPoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.mantissa < BigInt::from(0) { "-" } else { "" };
+        let digits = self.mantissa.abs().to_str_radix(10);
+        if self.scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        // left pad with zeros so that there is always at least one digit before the point
+        let scale = self.scale as usize;
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (int_part, frac_part) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, int_part, frac_part)
+    }
+}
+
+/// Parses decimal strings like "-12.50" into fixed-point numbers with a configured scale.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FixedPointEncoder {
+    // number of decimal digits kept after the point
+    pub scale: u32,
+}
+
+impl FixedPointEncoder {
+    pub fn new(scale: u32) -> Self {
+        FixedPointEncoder { scale }
+    }
+
+    pub fn parse(&self, input: &str) -> Result<FixedPoint, EncodingError> {
+        let invalid = || EncodingError::InvalidNumber(input.to_string());
+        let trimmed = input.trim();
+        let (negative, unsigned) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+        let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let all_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+        if int_part.is_empty() && frac_part.is_empty() || !all_digits(int_part) || !all_digits(frac_part) {
+            return Err(invalid());
+        }
+        let digits = frac_part.len() as u32;
+        if digits > self.scale {
+            return Err(EncodingError::TooManyDecimals { digits, scale: self.scale });
+        }
+        // "12.5" with scale 2 becomes 1250
+        let padded = format!("{}{}{}", int_part, frac_part, "0".repeat((self.scale - digits) as usize));
+        let magnitude = BigInt::from_str_radix(&padded, 10).map_err(|_| invalid())?;
+        let mantissa = if negative { -magnitude } else { magnitude };
+        Ok(FixedPoint::new(mantissa, self.scale))
+    }
+
+    /// Parse a decimal string and map it into Z_n.
+    pub fn encode(&self, input: &str, n: &BigInt) -> Result<BigInt, EncodingError> {
+        self.parse(input)?.encode(n)
+    }
+
+    /// Scale of the product of `num_parties` inputs encoded with this encoder.
+    pub fn product_scale(&self, num_parties: usize) -> u32 {
+        self.scale * num_parties as u32
+    }
+
+    /// Decode the reconstructed product of `num_parties` inputs.
+    pub fn decode_product(&self, value: &BigInt, n: &BigInt, num_parties: usize) -> FixedPoint {
+        FixedPoint::decode(value, n, self.product_scale(num_parties))
+    }
+}
+
+/// Signed values live in (-n/2, n/2). Anything of |value| >= 2^(bit_length(n) - 2) is rejected, since
+/// n/2 is at least that large.
+fn signed_capacity_bits(n: &BigInt) -> usize {
+    n.bit_length().saturating_sub(2)
+}
+
+/// Map a signed integer into Z_n, negative values wrapping around to n - |value|.
+pub fn encode_signed(value: &BigInt, n: &BigInt) -> Result<BigInt, EncodingError> {
+    let value_bits = value.abs().bit_length();
+    let available_bits = signed_capacity_bits(n);
+    if value_bits > available_bits {
+        return Err(EncodingError::Overflow { value_bits, available_bits });
+    }
+    if *value < BigInt::from(0) {
+        Ok(n + value)
+    } else {
+        Ok(value.clone())
+    }
+}
+
+/// Inverse of `encode_signed`: values above n/2 are read as negative.
+pub fn decode_signed(value: &BigInt, n: &BigInt) -> BigInt {
+    let half: BigInt = n >> 1;
+    if *value > half {
+        value - n
+    } else {
+        value.clone()
+    }
+}
+
+/// Refuse a product of `num_parties` signed inputs of `max_input_bits` bits each whose magnitude could reach n/2,
+/// past which its sign could not be recovered.
+pub fn check_signed_product_bound(num_parties: usize, max_input_bits: usize, n: &BigInt) -> Result<(), ProtocolError> {
+    check_signed_result_bound(num_parties * max_input_bits, n)
+}
+
+/// Any result of |value| < 2^required_bits must stay below n/2 so that its sign can be recovered.
+pub fn check_signed_result_bound(required_bits: usize, n: &BigInt) -> Result<(), ProtocolError> {
+    let available_bits = signed_capacity_bits(n);
+    if required_bits > available_bits {
+        return Err(ProtocolError::ProductOutOfRange { required_bits, available_bits });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curv::arithmetic::Modulo;
+
+    #[test]
+    fn test_parse_and_display() {
+        let encoder = FixedPointEncoder::new(2);
+        assert_eq!(encoder.parse("-12.5").unwrap(), FixedPoint::new(BigInt::from(-1250), 2));
+        assert_eq!(encoder.parse("0.07").unwrap().to_string(), "0.07");
+        assert_eq!(encoder.parse("+3").unwrap().to_string(), "3.00");
+        assert_eq!(encoder.parse("1.234"), Err(EncodingError::TooManyDecimals { digits: 3, scale: 2 }));
+        assert!(encoder.parse("1.2.3").is_err());
+        assert!(encoder.parse("-").is_err());
+    }
+
+    #[test]
+    fn test_signed_product_roundtrip() {
+        let n = BigInt::from_str_radix("1000000000000000000000007", 10).unwrap();
+        let encoder = FixedPointEncoder::new(2);
+        let inputs = ["-12.50", "3.01", "-0.5"];
+        let product = inputs.iter().fold(BigInt::from(1), |acc, x| {
+            BigInt::mod_mul(&acc, &encoder.encode(x, &n).unwrap(), &n)
+        });
+        // -12.50 * 3.01 * -0.5 = 18.8125
+        assert_eq!(encoder.decode_product(&product, &n, inputs.len()).to_string(), "18.812500");
+
+        let negative = BigInt::mod_mul(&product, &encoder.encode("-1", &n).unwrap(), &n);
+        assert_eq!(encoder.decode_product(&negative, &n, 4).to_string(), "-18.81250000");
+    }
+
+    #[test]
+    fn test_overflow() {
+        let n = BigInt::from(1u64 << 16) + BigInt::from(1);
+        assert!(encode_signed(&BigInt::from(-(1 << 14) + 1), &n).is_ok());
+        assert!(encode_signed(&BigInt::from(1 << 15), &n).is_err());
+        assert!(check_signed_product_bound(2, 7, &n).is_ok());
+        assert!(check_signed_product_bound(2, 8, &n).is_err());
+    }
+}
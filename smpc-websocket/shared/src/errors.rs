@@ -0,0 +1,316 @@
+use std::fmt::{Display, Formatter};
+
+use crate::profile::SecurityProfile;
+
+/// Errors raised by a party while running the protocol. Any of these means the party refuses to go on
+/// and asks the server to abort the session.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolError {
+    // the private input does not fit in the bound agreed for the session
+    InputOutOfRange { input_bits: usize, max_input_bits: usize },
+    // the product (or sum) of the inputs could exceed the plaintext space and would silently wrap mod n
+    ProductOutOfRange { required_bits: usize, available_bits: usize },
+    // the private input could not be encoded into Z_n
+    Encoding(EncodingError),
+    // parameters, a key or a ciphertext received from the server or another party failed validation
+    Invalid(ValidationError),
+    // the private input vector does not have the length announced for the session
+    VectorLengthMismatch { expected: usize, actual: usize },
+    // the reveal mode names an output party that is not part of the session
+    InvalidOutputParty { party: usize, num_parties: usize },
+    // a computation refers to shares this party does not hold
+    UnknownComputation(usize),
+    // the operands of a computation were not shared under the same key or with the same length
+    IncompatibleShares { lhs: usize, rhs: usize },
+    // only values with the same fixed-point scale can be added
+    ScaleMismatch { lhs: u32, rhs: u32 },
+    // the first client no longer holds the key the operands were shared under
+    MissingKey,
+    // the client has no private input vector at this index
+    MissingInput(usize),
+    // no protocol is registered under this id
+    UnknownProtocol(String),
+    // a protocol received a message for a round it does not have
+    UnexpectedRound { protocol: String, round: u32 },
+    // the parameters or a round payload could not be (de)serialized
+    MalformedMessage(String),
+    // the protocol cannot run on the encryption scheme of the session, with the given parameters
+    UnsupportedScheme { protocol: String, scheme: String },
+    // the scheme can only decrypt small plaintexts and this one was larger
+    UndecryptablePlaintext { max_bits: usize },
+    // the server announced another signing key than at the start of the session
+    ServerKeyChanged,
+    // a relay log checkpoint is not signed with the server's key, belongs to another session or has no head for
+    // this party
+    InvalidCheckpoint,
+    // the server signed a relay log that does not match the messages this party received
+    RelayLogMismatch { received: u64, sent: u64 },
+    // a message bound to another session than this one
+    WrongSession { from: usize, session_id: String },
+    // a message with the counter of one already accepted from its sender: a copy or a replay
+    ReplayedMessage { from: usize, seq: u64 },
+    // a dropout-tolerant computation needs more than half of the parties and at most all of them to finish
+    InvalidThreshold { threshold: usize, num_parties: usize },
+    // fewer parties than the threshold are left to finish the computation
+    NotEnoughSurvivors { remaining: usize, threshold: usize },
+    // a party dropped out of a computation that needs every party
+    PartyDropped(usize),
+    // the protocol cannot hand its result out in this reveal mode
+    UnsupportedReveal { protocol: String },
+    // Beaver triples were to come from the dealer, but none were dealt
+    NoDealer,
+}
+
+impl Display for ProtocolError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::InputOutOfRange { input_bits, max_input_bits } => write!(
+                f,
+                "private input has {} bits but the session only allows {} bits",
+                input_bits, max_input_bits
+            ),
+            ProtocolError::ProductOutOfRange { required_bits, available_bits } => write!(
+                f,
+                "result may need up to {} bits but the plaintext space only holds {} bits",
+                required_bits, available_bits
+            ),
+            ProtocolError::Encoding(e) => write!(f, "failed to encode the private input: {}", e),
+            ProtocolError::Invalid(e) => write!(f, "invalid message: {}", e),
+            ProtocolError::VectorLengthMismatch { expected, actual } => write!(
+                f,
+                "private input has {} elements but the session expects {}",
+                actual, expected
+            ),
+            ProtocolError::InvalidOutputParty { party, num_parties } => write!(
+                f,
+                "output party {} is not one of the {} parties",
+                party, num_parties
+            ),
+            ProtocolError::UnknownComputation(id) => write!(f, "no shares stored for computation {}", id),
+            ProtocolError::IncompatibleShares { lhs, rhs } => write!(
+                f,
+                "shares of computations {} and {} have different moduli or lengths",
+                lhs, rhs
+            ),
+            ProtocolError::ScaleMismatch { lhs, rhs } => write!(
+                f,
+                "cannot add values with scales {} and {}",
+                lhs, rhs
+            ),
+            ProtocolError::MissingKey => write!(f, "the key the shares were computed under is not available"),
+            ProtocolError::MissingInput(index) => write!(f, "no private input vector at index {}", index),
+            ProtocolError::UnknownProtocol(id) => write!(f, "no protocol registered as '{}'", id),
+            ProtocolError::UnexpectedRound { protocol, round } => write!(
+                f,
+                "protocol '{}' has no round {}",
+                protocol, round
+            ),
+            ProtocolError::MalformedMessage(e) => write!(f, "malformed protocol message: {}", e),
+            ProtocolError::UnsupportedScheme { protocol, scheme } => write!(
+                f,
+                "protocol '{}' cannot run on {} with these parameters",
+                protocol, scheme
+            ),
+            ProtocolError::UndecryptablePlaintext { max_bits } => write!(
+                f,
+                "plaintext is not below 2^{} in magnitude and cannot be decrypted",
+                max_bits
+            ),
+            ProtocolError::ServerKeyChanged => write!(f, "the server changed its signing key during the session"),
+            ProtocolError::InvalidCheckpoint => write!(f, "relay log checkpoint is not validly signed by the server"),
+            ProtocolError::RelayLogMismatch { received, sent } => write!(
+                f,
+                "the server signed a relay log of {} messages to this party that differs from the {} it received",
+                sent, received
+            ),
+            ProtocolError::WrongSession { from, session_id } => write!(
+                f,
+                "message from client {} belongs to another session ({})",
+                from, session_id
+            ),
+            ProtocolError::ReplayedMessage { from, seq } => write!(f, "message {} from client {} was already received", seq, from),
+            ProtocolError::InvalidThreshold { threshold, num_parties } => write!(
+                f,
+                "threshold {} is not a majority of the {} parties",
+                threshold, num_parties
+            ),
+            ProtocolError::NotEnoughSurvivors { remaining, threshold } => write!(
+                f,
+                "only {} parties are left but {} must finish",
+                remaining, threshold
+            ),
+            ProtocolError::PartyDropped(sid) => write!(f, "client {} dropped out of a computation that needs every party", sid),
+            ProtocolError::UnsupportedReveal { protocol } => write!(f, "protocol '{}' cannot hand its result out in this reveal mode", protocol),
+            ProtocolError::NoDealer => write!(f, "no triples were dealt for this computation, the server only deals them in test mode"),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl ProtocolError {
+    /// Whether the error comes from this party's own private input rather than from a message it received.
+    pub fn is_local_input(&self) -> bool {
+        matches!(
+            self,
+            ProtocolError::InputOutOfRange { .. }
+                | ProtocolError::Encoding(_)
+                | ProtocolError::VectorLengthMismatch { .. }
+                | ProtocolError::MissingInput(_)
+        )
+    }
+}
+
+/// Errors raised while mapping signed and fixed-point numbers into Z_n and back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodingError {
+    // the input is not a decimal number like "-12.50"
+    InvalidNumber(String),
+    // the input has more fractional digits than the scale allows
+    TooManyDecimals { digits: u32, scale: u32 },
+    // |value| does not fit in the signed half of Z_n
+    Overflow { value_bits: usize, available_bits: usize },
+}
+
+impl Display for EncodingError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodingError::InvalidNumber(input) => write!(f, "'{}' is not a decimal number", input),
+            EncodingError::TooManyDecimals { digits, scale } => write!(
+                f,
+                "input has {} fractional digits but the scale only allows {}",
+                digits, scale
+            ),
+            EncodingError::Overflow { value_bits, available_bits } => write!(
+                f,
+                "value needs {} bits but only {} bits are available for signed values",
+                value_bits, available_bits
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+impl From<EncodingError> for ProtocolError {
+    fn from(error: EncodingError) -> Self {
+        ProtocolError::Encoding(error)
+    }
+}
+
+/// Why parameters, a key or a ciphertext received from the server or another party were refused, see
+/// shared::validation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    // a session needs at least two parties
+    TooFewParties(usize),
+    // the sid is not one of the parties of the session
+    InvalidSid { sid: usize, num_parties: usize },
+    // a round claims to come from this party or from a party that is not part of the session
+    InvalidSender { from: usize },
+    // vectors of the session have no elements
+    EmptyVector,
+    // the session runs at a weaker security profile than this party accepts
+    BelowPolicy { profile: SecurityProfile, min_profile: SecurityProfile },
+    // the key size announced is not the one of the session's profile
+    ProfileMismatch { bits: usize, profile: SecurityProfile },
+    // the key modulus does not have the size announced for the session
+    ModulusSize { bits: usize, expected: usize },
+    // the parts of the public key do not fit together, e.g. n^2 is not the square of n
+    InconsistentKey(String),
+    // the public key is not the one used earlier in the session
+    KeyChanged,
+    // a message does not carry as many ciphertexts as the computation needs
+    WrongLength { expected: usize, actual: usize },
+    // the ciphertext is not in the ciphertext space of the key
+    CiphertextOutOfRange,
+    // the ciphertext is trivial (e.g. 1, or the point at infinity) and encrypts a known value without randomness
+    TrivialCiphertext,
+    // the ciphertext shares a factor with n
+    NotAUnit,
+    // a message encrypted for this party does not authenticate under the key agreed with its sender
+    Unauthenticated,
+    // secret shares received do not reconstruct the secret their owner committed to
+    InconsistentShares { owner: usize },
+    // another party saw a different set of parties finish a round than this one
+    ViewMismatch { from: usize },
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::TooFewParties(num_parties) => write!(f, "a session needs at least 2 parties, not {}", num_parties),
+            ValidationError::InvalidSid { sid, num_parties } => write!(f, "sid {} is not one of the {} parties", sid, num_parties),
+            ValidationError::InvalidSender { from } => write!(f, "client {} cannot have sent this round", from),
+            ValidationError::EmptyVector => write!(f, "input vectors cannot be empty"),
+            ValidationError::BelowPolicy { profile, min_profile } => write!(
+                f,
+                "the session runs at profile {} but at least {} is required",
+                profile, min_profile
+            ),
+            ValidationError::ProfileMismatch { bits, profile } => write!(
+                f,
+                "the session announces {}-bit keys under profile {}",
+                bits, profile
+            ),
+            ValidationError::ModulusSize { bits, expected } => write!(
+                f,
+                "key modulus has {} bits but the session uses {}-bit keys",
+                bits, expected
+            ),
+            ValidationError::InconsistentKey(reason) => write!(f, "inconsistent public key: {}", reason),
+            ValidationError::KeyChanged => write!(f, "the public key differs from the one used earlier in the session"),
+            ValidationError::WrongLength { expected, actual } => write!(
+                f,
+                "expected {} ciphertexts but received {}",
+                expected, actual
+            ),
+            ValidationError::CiphertextOutOfRange => write!(f, "ciphertext is outside the ciphertext space"),
+            ValidationError::TrivialCiphertext => write!(f, "ciphertext is trivial"),
+            ValidationError::NotAUnit => write!(f, "ciphertext is not a unit mod n"),
+            ValidationError::Unauthenticated => write!(f, "encrypted message does not authenticate"),
+            ValidationError::InconsistentShares { owner } => write!(f, "shares of client {}'s secret do not match its public key", owner),
+            ValidationError::ViewMismatch { from } => write!(f, "client {} saw other parties finish the round", from),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<ValidationError> for ProtocolError {
+    fn from(error: ValidationError) -> Self {
+        ProtocolError::Invalid(error)
+    }
+}
+
+/// Errors raised while opening or writing a client's vault, see shared::vault.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VaultError {
+    // the vault cannot be read or written
+    Io(String),
+    // the vault is not a vault file, or its entries do not decrypt to valid ones
+    Corrupted(String),
+    // the passphrase is wrong or the vault was tampered with, which cannot be told apart
+    Unauthenticated,
+    // no entry has this id
+    UnknownEntry(String),
+}
+
+impl Display for VaultError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VaultError::Io(reason) => write!(f, "vault I/O failed: {}", reason),
+            VaultError::Corrupted(reason) => write!(f, "vault is corrupted: {}", reason),
+            VaultError::Unauthenticated => write!(f, "wrong passphrase, or the vault was tampered with"),
+            VaultError::UnknownEntry(id) => write!(f, "no vault entry '{}'", id),
+        }
+    }
+}
+
+impl std::error::Error for VaultError {}
+
+impl From<std::io::Error> for VaultError {
+    fn from(error: std::io::Error) -> Self {
+        VaultError::Io(error.to_string())
+    }
+}
@@ -0,0 +1,165 @@
+use curv::arithmetic::traits::{BasicOps, Modulo, EGCD};
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+
+use crate::constant_time;
+use crate::errors::{ProtocolError, ValidationError};
+use crate::homomorphic::paillier::sample_keypair;
+use crate::homomorphic::{AdditiveHomomorphic, SchemeKind};
+use crate::rng;
+use crate::secret::Wipe;
+use crate::validation::{check_modulus_size, check_unit};
+
+/// Exponent used by `keypair`: plaintexts mod n^2 hold twice as many bits as Paillier's for the same n.
+pub const DEFAULT_S: u32 = 2;
+/// Largest exponent accepted in a key received from another party, which sets the size of every ciphertext.
+pub const MAX_S: u32 = 4;
+
+/// Damgård–Jurik, the generalisation of Paillier to plaintexts in Z_(n^s) and ciphertexts in Z_(n^(s+1)).
+///
+/// E(m) = (1 + n)^m * r^(n^s) mod n^(s+1). With s = 1 this is Paillier with g = n + 1.
+pub struct DamgardJurikScheme;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DamgardJurikPublicKey {
+    pub n: BigInt,
+    pub s: u32,
+}
+
+impl DamgardJurikPublicKey {
+    /// n^s
+    pub fn plaintext_modulus(&self) -> BigInt {
+        self.n.pow(self.s)
+    }
+
+    /// n^(s+1)
+    pub fn ciphertext_modulus(&self) -> BigInt {
+        self.n.pow(self.s + 1)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DamgardJurikSecretKey {
+    // lcm(p - 1, q - 1)
+    lambda: BigInt,
+    // lambda^-1 mod n^s
+    lambda_inv: BigInt,
+}
+
+impl Wipe for DamgardJurikSecretKey {
+    fn wipe(&mut self) {
+        self.lambda.wipe();
+        self.lambda_inv.wipe();
+    }
+}
+
+impl DamgardJurikScheme {
+    pub fn keypair_with_s(modulus_bits: usize, s: u32) -> (DamgardJurikPublicKey, DamgardJurikSecretKey) {
+        let keypair = sample_keypair(modulus_bits);
+        let n = &keypair.p * &keypair.q;
+        let (p1, q1) = (&keypair.p - BigInt::from(1), &keypair.q - BigInt::from(1));
+        let (gcd, _, _) = BigInt::egcd(&p1, &q1);
+        let lambda = &p1 * &q1 / gcd;
+        let pk = DamgardJurikPublicKey { n, s };
+        // gcd(lambda, n) = 1 since p and q have the same length
+        let lambda_inv = BigInt::mod_inv(&lambda, &pk.plaintext_modulus()).expect("lambda is coprime to n");
+        (pk, DamgardJurikSecretKey { lambda, lambda_inv })
+    }
+}
+
+/// Recover i from (1 + n)^i mod n^(s+1), i in Z_(n^s), following the algorithm of the Damgård–Jurik paper.
+/// i is found digit by digit in base n, using the binomial expansion of (1 + n)^i mod n^(j+1) for j = 1..s.
+fn discrete_log_one_plus_n(a: &BigInt, pk: &DamgardJurikPublicKey) -> BigInt {
+    let n = &pk.n;
+    let mut i = BigInt::from(0);
+    for j in 1..=pk.s {
+        let nj = n.pow(j);
+        // L(a mod n^(j+1)) = (a mod n^(j+1) - 1) / n
+        let mut t1 = (a.modulus(&n.pow(j + 1)) - BigInt::from(1)) / n;
+        let mut t2 = i.clone();
+        let mut factorial = BigInt::from(1);
+        for k in 2..=j {
+            i = BigInt::mod_sub(&i, &BigInt::from(1), &nj);
+            t2 = BigInt::mod_mul(&t2, &i, &nj);
+            factorial *= BigInt::from(k);
+            let k_inv = BigInt::mod_inv(&factorial, &nj).expect("k! is coprime to n for k <= s");
+            let term = BigInt::mod_mul(&(&t2 * n.pow(k - 1)), &k_inv, &nj);
+            t1 = BigInt::mod_sub(&t1, &term, &nj);
+        }
+        i = t1.modulus(&nj);
+    }
+    i
+}
+
+impl AdditiveHomomorphic for DamgardJurikScheme {
+    type PublicKey = DamgardJurikPublicKey;
+    type SecretKey = DamgardJurikSecretKey;
+    type Ciphertext = BigInt;
+
+    const KIND: SchemeKind = SchemeKind::DamgardJurik;
+
+    fn keypair(security_bits: usize) -> (DamgardJurikPublicKey, DamgardJurikSecretKey) {
+        Self::keypair_with_s(security_bits, DEFAULT_S)
+    }
+
+    fn plaintext_modulus(pk: &DamgardJurikPublicKey) -> BigInt {
+        pk.plaintext_modulus()
+    }
+
+    // m is usually a party's private input, see crate::constant_time: (1 + n) has order n^s, the plaintext modulus
+    fn encrypt(pk: &DamgardJurikPublicKey, m: &BigInt) -> BigInt {
+        let modulus = pk.ciphertext_modulus();
+        let g = &pk.n + BigInt::from(1);
+        let r = rng::sample_below(&pk.n);
+        let gm = constant_time::pow_blinded(&g, m, &modulus, &pk.plaintext_modulus());
+        let rn = BigInt::mod_pow(&r, &pk.plaintext_modulus(), &modulus);
+        BigInt::mod_mul(&gm, &rn, &modulus)
+    }
+
+    fn add(pk: &DamgardJurikPublicKey, a: &BigInt, b: &BigInt) -> BigInt {
+        BigInt::mod_mul(a, b, &pk.ciphertext_modulus())
+    }
+
+    // k is usually a party's private input, see crate::constant_time
+    fn scalar_mul(pk: &DamgardJurikPublicKey, c: &BigInt, k: &BigInt) -> BigInt {
+        constant_time::pow_blinded(c, k, &pk.ciphertext_modulus(), &pk.plaintext_modulus())
+    }
+
+    fn decrypt(pk: &DamgardJurikPublicKey, sk: &DamgardJurikSecretKey, c: &BigInt) -> Result<BigInt, ProtocolError> {
+        // c^lambda = (1 + n)^(m * lambda) since r^(n^s * lambda) = 1 mod n^(s+1)
+        let a = constant_time::pow_fixed(c, &sk.lambda, &pk.ciphertext_modulus());
+        let m_lambda = discrete_log_one_plus_n(&a, pk);
+        Ok(BigInt::mod_mul(&m_lambda, &sk.lambda_inv, &pk.plaintext_modulus()))
+    }
+
+    fn validate_public_key(pk: &DamgardJurikPublicKey, security_bits: usize) -> Result<(), ValidationError> {
+        if pk.s == 0 || pk.s > MAX_S {
+            return Err(ValidationError::InconsistentKey(format!("s = {} is not between 1 and {}", pk.s, MAX_S)));
+        }
+        check_modulus_size(&pk.n, security_bits)
+    }
+
+    fn validate_ciphertext(pk: &DamgardJurikPublicKey, c: &BigInt) -> Result<(), ValidationError> {
+        check_unit(c, &pk.n, &pk.ciphertext_modulus())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homomorphic::tests::check_homomorphic;
+    use curv::arithmetic::traits::BitManipulation;
+
+    #[test]
+    fn test_damgard_jurik() {
+        for s in 1..=3 {
+            let (pk, sk) = DamgardJurikScheme::keypair_with_s(256, s);
+            check_homomorphic::<DamgardJurikScheme>(&pk, &sk);
+            // plaintexts larger than n survive the round trip
+            let m = rng::sample_below(&pk.plaintext_modulus());
+            let c = DamgardJurikScheme::encrypt(&pk, &m);
+            assert_eq!(DamgardJurikScheme::decrypt(&pk, &sk, &c).unwrap(), m);
+            assert!(pk.plaintext_modulus().bit_length() > s as usize * (pk.n.bit_length() - 1));
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use curv::elliptic::curves::{Point, Scalar, Secp256k1};
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{ProtocolError, ValidationError};
+use crate::homomorphic::{AdditiveHomomorphic, SchemeKind};
+use crate::rng;
+use crate::secret::Wipe;
+
+/// Decryption recovers plaintexts of |m| < 2^MAX_PLAINTEXT_BITS.
+pub const MAX_PLAINTEXT_BITS: usize = 32;
+
+// baby steps of the discrete log search, the giant step is 2^BABY_STEP_BITS * G
+const BABY_STEP_BITS: usize = MAX_PLAINTEXT_BITS / 2;
+
+/// Exponential ElGamal on secp256k1: E(m) = (r * G, m * G + r * H) with H = x * G.
+///
+/// Ciphertexts are two compressed points, much smaller than Paillier's, and plaintexts live mod the group
+/// order. Decryption only yields m * G, so m is recovered with a baby-step giant-step search and must be small,
+/// which makes the scheme fit for sums of small values but not for anything masked with uniform shares.
+pub struct ElGamalScheme;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElGamalPublicKey {
+    pub h: Point<Secp256k1>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ElGamalSecretKey {
+    x: Scalar<Secp256k1>,
+}
+
+// curv zeroizes a Scalar when it is dropped
+impl Wipe for ElGamalSecretKey {
+    fn wipe(&mut self) {}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElGamalCiphertext {
+    pub c1: Point<Secp256k1>,
+    pub c2: Point<Secp256k1>,
+}
+
+/// j * G for every j below 2^BABY_STEP_BITS, keyed by the compressed encoding of the point.
+fn baby_steps() -> &'static HashMap<Vec<u8>, u64> {
+    static TABLE: OnceLock<HashMap<Vec<u8>, u64>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let g = Point::<Secp256k1>::generator().to_point();
+        let mut table = HashMap::with_capacity(1 << BABY_STEP_BITS);
+        let mut point = Point::<Secp256k1>::zero();
+        for j in 0..1u64 << BABY_STEP_BITS {
+            table.insert(point.to_bytes(true).to_vec(), j);
+            point = point + &g;
+        }
+        table
+    })
+}
+
+/// Find m with |m| < 2^MAX_PLAINTEXT_BITS and m * G = target, returned mod the group order.
+fn discrete_log(target: &Point<Secp256k1>) -> Option<BigInt> {
+    let table = baby_steps();
+    let step = 1u64 << BABY_STEP_BITS;
+    let giant = Point::<Secp256k1>::generator() * Scalar::<Secp256k1>::from(step);
+    // look for m = i * step + j and m = j - i * step at the same time, so small values are found first
+    let (mut up, mut down) = (target.clone(), target.clone());
+    for i in 0..=step {
+        if let Some(j) = table.get(&up.to_bytes(true).to_vec()) {
+            return Some(BigInt::from(i * step + j));
+        }
+        if let Some(j) = table.get(&down.to_bytes(true).to_vec()) {
+            // m = j - i * step, negative values wrap around the group order
+            let m = BigInt::from(*j) - BigInt::from(i * step);
+            return Some(if m < BigInt::from(0) { Scalar::<Secp256k1>::group_order() + m } else { m });
+        }
+        up = up - &giant;
+        down = down + &giant;
+    }
+    None
+}
+
+impl AdditiveHomomorphic for ElGamalScheme {
+    type PublicKey = ElGamalPublicKey;
+    type SecretKey = ElGamalSecretKey;
+    type Ciphertext = ElGamalCiphertext;
+
+    const KIND: SchemeKind = SchemeKind::EcElGamal;
+
+    /// The curve is fixed, so the security level is ignored.
+    fn keypair(_security_bits: usize) -> (ElGamalPublicKey, ElGamalSecretKey) {
+        let x = rng::sample_scalar();
+        let h = Point::<Secp256k1>::generator() * &x;
+        (ElGamalPublicKey { h }, ElGamalSecretKey { x })
+    }
+
+    fn plaintext_modulus(_pk: &ElGamalPublicKey) -> BigInt {
+        Scalar::<Secp256k1>::group_order().clone()
+    }
+
+    fn max_decryptable_bits(_pk: &ElGamalPublicKey) -> Option<usize> {
+        Some(MAX_PLAINTEXT_BITS)
+    }
+
+    fn encrypt(pk: &ElGamalPublicKey, m: &BigInt) -> ElGamalCiphertext {
+        let r = rng::sample_scalar();
+        let c1 = Point::<Secp256k1>::generator() * &r;
+        let c2 = Point::<Secp256k1>::generator() * Scalar::from_bigint(m) + &pk.h * &r;
+        ElGamalCiphertext { c1, c2 }
+    }
+
+    fn add(_pk: &ElGamalPublicKey, a: &ElGamalCiphertext, b: &ElGamalCiphertext) -> ElGamalCiphertext {
+        ElGamalCiphertext { c1: &a.c1 + &b.c1, c2: &a.c2 + &b.c2 }
+    }
+
+    fn scalar_mul(_pk: &ElGamalPublicKey, c: &ElGamalCiphertext, k: &BigInt) -> ElGamalCiphertext {
+        let k = Scalar::<Secp256k1>::from_bigint(k);
+        ElGamalCiphertext { c1: &c.c1 * &k, c2: &c.c2 * &k }
+    }
+
+    fn negate(_pk: &ElGamalPublicKey, c: &ElGamalCiphertext) -> ElGamalCiphertext {
+        ElGamalCiphertext { c1: -&c.c1, c2: -&c.c2 }
+    }
+
+    fn decrypt(_pk: &ElGamalPublicKey, sk: &ElGamalSecretKey, c: &ElGamalCiphertext) -> Result<BigInt, ProtocolError> {
+        let mg = &c.c2 - &c.c1 * &sk.x;
+        discrete_log(&mg).ok_or(ProtocolError::UndecryptablePlaintext { max_bits: MAX_PLAINTEXT_BITS })
+    }
+
+    /// Points are checked to be on the curve when they are parsed. The curve is fixed, so the security level
+    /// is ignored.
+    fn validate_public_key(pk: &ElGamalPublicKey, _security_bits: usize) -> Result<(), ValidationError> {
+        if pk.h.is_zero() {
+            return Err(ValidationError::InconsistentKey("h is the point at infinity".to_string()));
+        }
+        Ok(())
+    }
+
+    /// c1 = r * G is only the point at infinity for r = 0, in which case c2 reveals m * G.
+    fn validate_ciphertext(_pk: &ElGamalPublicKey, c: &ElGamalCiphertext) -> Result<(), ValidationError> {
+        if c.c1.is_zero() {
+            return Err(ValidationError::TrivialCiphertext);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homomorphic::tests::check_homomorphic;
+
+    #[test]
+    fn test_ec_elgamal() {
+        let (pk, sk) = ElGamalScheme::keypair(0);
+        check_homomorphic::<ElGamalScheme>(&pk, &sk);
+
+        let large = BigInt::from(1u64 << 40);
+        let c = ElGamalScheme::encrypt(&pk, &large);
+        assert!(ElGamalScheme::decrypt(&pk, &sk, &c).is_err());
+        // (1 << 32) - 1 needs the last giant step
+        let edge = BigInt::from((1u64 << 32) - 1);
+        let c = ElGamalScheme::encrypt(&pk, &edge);
+        assert_eq!(ElGamalScheme::decrypt(&pk, &sk, &c).unwrap(), edge);
+    }
+}
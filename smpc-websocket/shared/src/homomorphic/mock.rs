@@ -0,0 +1,83 @@
+use curv::arithmetic::traits::Modulo;
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{ProtocolError, ValidationError};
+use crate::homomorphic::{AdditiveHomomorphic, SchemeKind};
+use crate::rng;
+use crate::validation::check_modulus_size;
+
+#[cfg(not(debug_assertions))]
+compile_error!("the insecure-mock feature provides no encryption at all and cannot be enabled in release builds");
+
+/// INSECURE: no encryption at all, for tests and local simulations only.
+///
+/// A "ciphertext" is the plaintext itself mod a random n of the requested size, so every homomorphic operation
+/// behaves like Paillier's (same plaintext space size, same packing and bounds) without any key generation or
+/// modular exponentiation. Anyone who sees a message learns the values in it.
+pub struct InsecureMockScheme;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MockPublicKey {
+    pub n: BigInt,
+}
+
+impl AdditiveHomomorphic for InsecureMockScheme {
+    type PublicKey = MockPublicKey;
+    type SecretKey = ();
+    type Ciphertext = BigInt;
+
+    const KIND: SchemeKind = SchemeKind::InsecureMock;
+
+    /// n is any number of exactly `security_bits` bits, no primes are generated.
+    fn keypair(security_bits: usize) -> (MockPublicKey, ()) {
+        let top = BigInt::from(1) << (security_bits - 1);
+        (MockPublicKey { n: &top + rng::sample_below(&top) }, ())
+    }
+
+    fn plaintext_modulus(pk: &MockPublicKey) -> BigInt {
+        pk.n.clone()
+    }
+
+    fn encrypt(pk: &MockPublicKey, m: &BigInt) -> BigInt {
+        m.modulus(&pk.n)
+    }
+
+    fn add(pk: &MockPublicKey, a: &BigInt, b: &BigInt) -> BigInt {
+        BigInt::mod_add(a, b, &pk.n)
+    }
+
+    fn scalar_mul(pk: &MockPublicKey, c: &BigInt, k: &BigInt) -> BigInt {
+        BigInt::mod_mul(c, k, &pk.n)
+    }
+
+    fn decrypt(_pk: &MockPublicKey, _sk: &(), c: &BigInt) -> Result<BigInt, ProtocolError> {
+        Ok(c.clone())
+    }
+
+    fn validate_public_key(pk: &MockPublicKey, security_bits: usize) -> Result<(), ValidationError> {
+        check_modulus_size(&pk.n, security_bits)
+    }
+
+    /// Any plaintext is a ciphertext, there is nothing to hide anyway.
+    fn validate_ciphertext(pk: &MockPublicKey, c: &BigInt) -> Result<(), ValidationError> {
+        if *c < BigInt::from(0) || *c >= pk.n {
+            return Err(ValidationError::CiphertextOutOfRange);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homomorphic::tests::check_homomorphic;
+    use curv::arithmetic::traits::BitManipulation;
+
+    #[test]
+    fn test_insecure_mock() {
+        let (pk, sk) = InsecureMockScheme::keypair(2048);
+        assert_eq!(pk.n.bit_length(), 2048);
+        check_homomorphic::<InsecureMockScheme>(&pk, &sk);
+    }
+}
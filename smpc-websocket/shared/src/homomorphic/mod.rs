@@ -0,0 +1,132 @@
+use std::fmt::{Debug, Display, Formatter};
+
+use kzen_paillier::BigInt;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{ProtocolError, ValidationError};
+use crate::rng;
+use crate::secret::Wipe;
+
+pub mod damgard_jurik;
+pub mod ec_elgamal;
+#[cfg(feature = "insecure-mock")]
+pub mod mock;
+pub mod paillier;
+
+pub use damgard_jurik::DamgardJurikScheme;
+pub use ec_elgamal::ElGamalScheme;
+#[cfg(feature = "insecure-mock")]
+pub use mock::InsecureMockScheme;
+pub use paillier::PaillierScheme;
+
+/// An additively homomorphic public key encryption scheme over plaintexts in Z_m.
+///
+/// Protocols only rely on these operations, so they can run on any backend whose plaintext space is large
+/// enough for them. Plaintexts and scalars are always given and returned in [0, m).
+pub trait AdditiveHomomorphic: Send + 'static {
+    type PublicKey: Clone + Debug + PartialEq + Serialize + DeserializeOwned + Send + 'static;
+    type SecretKey: Clone + Serialize + DeserializeOwned + Send + Wipe + 'static;
+    type Ciphertext: Clone + Debug + Serialize + DeserializeOwned + Send;
+
+    /// The kind the server announces for sessions on this scheme.
+    const KIND: SchemeKind;
+
+    fn keypair(security_bits: usize) -> (Self::PublicKey, Self::SecretKey);
+
+    /// m, the modulus of the plaintext space.
+    fn plaintext_modulus(pk: &Self::PublicKey) -> BigInt;
+
+    /// Bit length of the largest |plaintext| decryption can recover, or None if it recovers all of Z_m.
+    fn max_decryptable_bits(_pk: &Self::PublicKey) -> Option<usize> {
+        None
+    }
+
+    fn encrypt(pk: &Self::PublicKey, m: &BigInt) -> Self::Ciphertext;
+
+    /// E(a) + E(b) = E(a + b)
+    fn add(pk: &Self::PublicKey, a: &Self::Ciphertext, b: &Self::Ciphertext) -> Self::Ciphertext;
+
+    /// k * E(a) = E(k * a)
+    fn scalar_mul(pk: &Self::PublicKey, c: &Self::Ciphertext, k: &BigInt) -> Self::Ciphertext;
+
+    /// -E(a) = E(-a), by default (m - 1) * E(a)
+    fn negate(pk: &Self::PublicKey, c: &Self::Ciphertext) -> Self::Ciphertext {
+        Self::scalar_mul(pk, c, &(Self::plaintext_modulus(pk) - BigInt::from(1)))
+    }
+
+    fn decrypt(pk: &Self::PublicKey, sk: &Self::SecretKey, c: &Self::Ciphertext) -> Result<BigInt, ProtocolError>;
+
+    /// Check a public key received from another party: well formed and, if its size depends on the security
+    /// level, of `security_bits`.
+    fn validate_public_key(pk: &Self::PublicKey, security_bits: usize) -> Result<(), ValidationError>;
+
+    /// Check a ciphertext received from another party: in the ciphertext space of `pk` and not trivial.
+    fn validate_ciphertext(pk: &Self::PublicKey, c: &Self::Ciphertext) -> Result<(), ValidationError>;
+}
+
+/// Which backend a session runs on, announced by the server.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SchemeKind {
+    #[default]
+    Paillier,
+    // plaintexts mod n^s, see DamgardJurikScheme
+    DamgardJurik,
+    // exponential ElGamal on secp256k1: small ciphertexts but only small plaintexts can be decrypted
+    EcElGamal,
+    // no encryption at all, see InsecureMockScheme
+    #[cfg(feature = "insecure-mock")]
+    InsecureMock,
+}
+
+impl Display for SchemeKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemeKind::Paillier => write!(f, "Paillier"),
+            SchemeKind::DamgardJurik => write!(f, "Damgård–Jurik"),
+            SchemeKind::EcElGamal => write!(f, "EC exponential ElGamal"),
+            #[cfg(feature = "insecure-mock")]
+            SchemeKind::InsecureMock => write!(f, "INSECURE mock (no encryption)"),
+        }
+    }
+}
+
+/// Sample `len` uniform shares r mod m and return E(-r) for each of them along with the shares.
+pub fn sample_masks<S: AdditiveHomomorphic>(pk: &S::PublicKey, len: usize) -> (Vec<S::Ciphertext>, Vec<BigInt>) {
+    let modulus = S::plaintext_modulus(pk);
+    let shares: Vec<BigInt> = (0..len).map(|_| rng::sample_below(&modulus)).collect();
+    let masks = shares.iter().map(|r| S::negate(pk, &S::encrypt(pk, r))).collect();
+    (masks, shares)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::encoding::encode_signed;
+    use curv::arithmetic::traits::Modulo;
+
+    /// Checks every homomorphic operation of a scheme on small signed values.
+    pub fn check_homomorphic<S: AdditiveHomomorphic>(pk: &S::PublicKey, sk: &S::SecretKey) {
+        let m = S::plaintext_modulus(pk);
+        let signed = |x: i32| encode_signed(&BigInt::from(x), &m).unwrap();
+        let enc = |x: i32| S::encrypt(pk, &signed(x));
+        let dec = |c: &S::Ciphertext| S::decrypt(pk, sk, c).unwrap();
+
+        assert_eq!(dec(&enc(1234)), BigInt::from(1234));
+        assert_eq!(dec(&enc(0)), BigInt::from(0));
+        assert_eq!(dec(&S::add(pk, &enc(1234), &enc(-34))), BigInt::from(1200));
+        assert_eq!(dec(&S::scalar_mul(pk, &enc(-7), &BigInt::from(6))), signed(-42));
+        assert_eq!(dec(&S::scalar_mul(pk, &enc(7), &signed(-6))), signed(-42));
+        assert_eq!(dec(&S::negate(pk, &enc(99))), signed(-99));
+
+        // uniform masks can only be removed again if the whole plaintext space can be decrypted
+        if S::max_decryptable_bits(pk).is_some() {
+            return;
+        }
+        let (masks, shares) = sample_masks::<S>(pk, 2);
+        for (mask, r) in masks.iter().zip(&shares) {
+            let masked = dec(&S::add(pk, &enc(5), mask));
+            assert_eq!(BigInt::mod_add(&masked, r, &m), BigInt::from(5));
+        }
+    }
+}
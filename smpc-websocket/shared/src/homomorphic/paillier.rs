@@ -0,0 +1,91 @@
+use curv::arithmetic::traits::Modulo;
+use kzen_paillier::{BigInt, DecryptionKey, EncryptWithChosenRandomness, EncryptionKey, Keypair, Paillier, Randomness, RawCiphertext, RawPlaintext};
+
+use crate::constant_time;
+use crate::errors::{ProtocolError, ValidationError};
+use crate::homomorphic::{AdditiveHomomorphic, SchemeKind};
+use crate::rng;
+use crate::secret::Wipe;
+use crate::validation::{check_modulus_size, check_unit};
+
+/// Two random primes of half the modulus size each, as kzen_paillier's `keypair_with_modulus_size` does.
+pub fn sample_keypair(modulus_bits: usize) -> Keypair {
+    let p = rng::sample_prime(modulus_bits / 2);
+    let q = rng::sample_prime(modulus_bits / 2);
+    Keypair { p, q }
+}
+
+impl Wipe for DecryptionKey {
+    fn wipe(&mut self) {
+        self.p.wipe();
+        self.q.wipe();
+    }
+}
+
+/// Paillier over Z_n, ciphertexts in Z_n^2. E(a) * E(b) = E(a + b) and E(a)^k = E(k * a).
+pub struct PaillierScheme;
+
+impl AdditiveHomomorphic for PaillierScheme {
+    type PublicKey = EncryptionKey;
+    type SecretKey = DecryptionKey;
+    type Ciphertext = BigInt;
+
+    const KIND: SchemeKind = SchemeKind::Paillier;
+
+    fn keypair(security_bits: usize) -> (EncryptionKey, DecryptionKey) {
+        sample_keypair(security_bits).keys()
+    }
+
+    fn plaintext_modulus(pk: &EncryptionKey) -> BigInt {
+        pk.n.clone()
+    }
+
+    fn encrypt(pk: &EncryptionKey, m: &BigInt) -> BigInt {
+        // r is a unit mod n but with negligible probability
+        let r = Randomness(rng::sample_below(&pk.n));
+        let ct: RawCiphertext = Paillier::encrypt_with_chosen_randomness(pk, RawPlaintext::from(m), &r);
+        ct.into()
+    }
+
+    fn add(pk: &EncryptionKey, a: &BigInt, b: &BigInt) -> BigInt {
+        BigInt::mod_mul(a, b, &pk.nn)
+    }
+
+    // k is usually a party's private input, see crate::constant_time
+    fn scalar_mul(pk: &EncryptionKey, c: &BigInt, k: &BigInt) -> BigInt {
+        constant_time::pow_blinded(c, k, &pk.nn, &pk.n)
+    }
+
+    // c^phi = (1 + n)^(m * phi) = 1 + m * phi * n mod n^2. Unlike kzen_paillier's decryption, the secret exponent
+    // goes through the constant time exponentiation.
+    fn decrypt(pk: &EncryptionKey, sk: &DecryptionKey, c: &BigInt) -> Result<BigInt, ProtocolError> {
+        let phi = (&sk.p - BigInt::from(1)) * (&sk.q - BigInt::from(1));
+        // p and q of the same size do not divide each other's predecessor
+        let phi_inv = BigInt::mod_inv(&phi, &pk.n).expect("phi is a unit mod n");
+        let m_phi = (constant_time::pow_fixed(c, &phi, &pk.nn) - BigInt::from(1)) / &pk.n;
+        Ok(BigInt::mod_mul(&m_phi, &phi_inv, &pk.n))
+    }
+
+    fn validate_public_key(pk: &EncryptionKey, security_bits: usize) -> Result<(), ValidationError> {
+        if pk.nn != &pk.n * &pk.n {
+            return Err(ValidationError::InconsistentKey("nn is not n^2".to_string()));
+        }
+        check_modulus_size(&pk.n, security_bits)
+    }
+
+    fn validate_ciphertext(pk: &EncryptionKey, c: &BigInt) -> Result<(), ValidationError> {
+        check_unit(c, &pk.n, &pk.nn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homomorphic::tests::check_homomorphic;
+
+    #[test]
+    fn test_paillier() {
+        let (pk, sk) = PaillierScheme::keypair(512);
+        check_homomorphic::<PaillierScheme>(&pk, &sk);
+    }
+}
@@ -1,2 +1,25 @@
+pub mod constant_time;
+pub mod coordinator;
+pub mod encoding;
+pub mod errors;
+pub mod homomorphic;
+pub mod packing;
+pub mod party;
+pub mod profile;
+pub mod protocol;
+pub mod relay_log;
+pub mod replay;
+pub mod resume;
+pub mod rng;
+pub mod secret;
+pub mod share_store;
+pub mod sharing;
+pub mod simulator;
+#[cfg(feature = "seeded-rng")]
+pub mod test_vectors;
+pub mod transcript;
+pub mod transport;
 pub mod types;
-pub mod utils;
\ No newline at end of file
+pub mod utils;
+pub mod validation;
+pub mod vault;
\ No newline at end of file
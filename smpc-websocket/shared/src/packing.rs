@@ -0,0 +1,177 @@
+use curv::arithmetic::traits::{BitManipulation, Modulo};
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+
+use crate::homomorphic::AdditiveHomomorphic;
+use crate::rng;
+
+/// Extra bits added to every mask so that a masked slot statistically hides the product it carries.
+pub const STATISTICAL_SECURITY_BITS: usize = 40;
+
+/// How element-wise products are packed into plaintexts in the second round.
+///
+/// Exponentiating a ciphertext multiplies every slot by the same exponent, so the first round cannot be
+/// packed: every element needs its own exponent. Once the last party holds one ciphertext per product it
+/// packs them homomorphically, B^0 * E(p_0) + B^1 * E(p_1) + ... = E(p_0 + p_1 B + ...), with B = 2^slot_bits.
+///
+/// Slots must never borrow from each other, so instead of subtracting a uniform mask mod n (which would
+/// wrap) every party j adds M - r_j to each slot with r_j uniform in [0, M) and keeps r_j - M as its share.
+/// The last party also adds 2^value_bits so that negative products stay positive inside their slot.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PackingLayout {
+    // every product p satisfies |p| < 2^value_bits
+    pub value_bits: usize,
+    // masks are sampled below M = 2^mask_bits
+    pub mask_bits: usize,
+    pub slot_bits: usize,
+    pub slots_per_ciphertext: usize,
+}
+
+impl PackingLayout {
+    /// Layout for the products of `num_parties` inputs of at most `max_input_bits` bits, or None if not
+    /// even a single slot fits in the plaintext space Z_n.
+    pub fn new(num_parties: usize, max_input_bits: usize, n: &BigInt) -> Option<Self> {
+        Self::with_value_bits(num_parties * max_input_bits, num_parties, n)
+    }
+
+    /// Layout for values of |p| < 2^value_bits masked by `num_parties - 1` parties.
+    pub fn with_value_bits(value_bits: usize, num_parties: usize, n: &BigInt) -> Option<Self> {
+        let mask_bits = value_bits + STATISTICAL_SECURITY_BITS;
+        // a slot holds p + 2^value_bits + (num_parties - 1) * M, which is below 2^(mask_bits + bits(num_parties) + 1)
+        let slot_bits = mask_bits + BigInt::from(num_parties as u64).bit_length() + 1;
+        let slots_per_ciphertext = n.bit_length().saturating_sub(1) / slot_bits;
+        if slots_per_ciphertext == 0 {
+            return None;
+        }
+        Some(PackingLayout { value_bits, mask_bits, slot_bits, slots_per_ciphertext })
+    }
+
+    pub fn num_ciphertexts(&self, len: usize) -> usize {
+        len.div_ceil(self.slots_per_ciphertext)
+    }
+
+    /// 2^value_bits, added to every slot by the last party.
+    pub fn value_offset(&self) -> BigInt {
+        BigInt::from(1) << self.value_bits
+    }
+
+    /// M = 2^mask_bits
+    pub fn mask_bound(&self) -> BigInt {
+        BigInt::from(1) << self.mask_bits
+    }
+
+    /// Pack plaintext slot values into sum(v_k * B^k), one integer per chunk of slots.
+    pub fn pack(&self, values: &[BigInt]) -> Vec<BigInt> {
+        values
+            .chunks(self.slots_per_ciphertext)
+            .map(|chunk| {
+                chunk.iter().rev().fold(BigInt::from(0), |acc, v| (acc << self.slot_bits) + v)
+            })
+            .collect()
+    }
+
+    /// Split packed integers back into `len` slot values.
+    pub fn unpack(&self, packed: &[BigInt], len: usize) -> Vec<BigInt> {
+        let slot_mask = (BigInt::from(1) << self.slot_bits) - BigInt::from(1);
+        let mut values = Vec::with_capacity(len);
+        for p in packed {
+            let mut rest = p.clone();
+            for _ in 0..self.slots_per_ciphertext {
+                if values.len() == len {
+                    break;
+                }
+                values.push(&rest & &slot_mask);
+                rest >>= self.slot_bits;
+            }
+        }
+        values
+    }
+
+    /// Homomorphically pack one ciphertext per element into packed ciphertexts and add the value offset
+    /// to every slot.
+    pub fn pack_ciphertexts<S: AdditiveHomomorphic>(&self, pk: &S::PublicKey, ciphertexts: &[S::Ciphertext]) -> Vec<S::Ciphertext> {
+        let offsets = self.pack(&vec![self.value_offset(); ciphertexts.len()]);
+        ciphertexts
+            .chunks(self.slots_per_ciphertext)
+            .zip(offsets)
+            .map(|(chunk, offset)| {
+                chunk.iter().enumerate().fold(S::encrypt(pk, &offset), |acc, (k, ct)| {
+                    let weight = BigInt::from(1) << (k * self.slot_bits);
+                    S::add(pk, &acc, &S::scalar_mul(pk, ct, &weight))
+                })
+            })
+            .collect()
+    }
+
+    /// Sample one mask r in [0, M) per slot, add E(M - r) to every slot of the packed ciphertexts and
+    /// return the masked ciphertexts along with this party's shares r - M (mod n).
+    pub fn mask_ciphertexts<S: AdditiveHomomorphic>(&self, pk: &S::PublicKey, packed: &[S::Ciphertext], len: usize) -> (Vec<S::Ciphertext>, Vec<BigInt>) {
+        let bound = self.mask_bound();
+        let n = S::plaintext_modulus(pk);
+        let masks: Vec<BigInt> = (0..len).map(|_| rng::sample_below(&bound)).collect();
+        let slot_values: Vec<BigInt> = masks.iter().map(|r| &bound - r).collect();
+        let masked = packed
+            .iter()
+            .zip(self.pack(&slot_values))
+            .map(|(ct, plain)| S::add(pk, ct, &S::encrypt(pk, &plain)))
+            .collect();
+        let shares = masks.iter().map(|r| BigInt::mod_sub(r, &bound, &n)).collect();
+        (masked, shares)
+    }
+
+    /// Shares of the party that decrypts the packed ciphertexts: every slot minus the value offset (mod n).
+    pub fn unpack_shares(&self, decrypted: &[BigInt], len: usize, n: &BigInt) -> Vec<BigInt> {
+        let offset = self.value_offset();
+        self.unpack(decrypted, len)
+            .iter()
+            .map(|slot| BigInt::mod_sub(slot, &offset, n))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::{decode_signed, encode_signed};
+    use crate::homomorphic::PaillierScheme;
+
+    #[test]
+    fn test_pack_unpack() {
+        let n = BigInt::from(1) << 1023;
+        let layout = PackingLayout::new(3, 16, &n).unwrap();
+        let values: Vec<BigInt> = (0..20u64).map(|v| BigInt::from(v * 1000 + 7)).collect();
+        let packed = layout.pack(&values);
+        assert_eq!(packed.len(), layout.num_ciphertexts(values.len()));
+        assert_eq!(layout.unpack(&packed, values.len()), values);
+    }
+
+    #[test]
+    fn test_packed_shares_sum_to_products() {
+        let (ek, dk) = PaillierScheme::keypair(1024);
+        let num_parties = 3;
+        let layout = PackingLayout::new(num_parties, 16, &ek.n).unwrap();
+        let products: Vec<BigInt> = [-12345i32, 0, 99999, -1, 42].iter().map(|p| BigInt::from(*p)).collect();
+        let cts: Vec<BigInt> = products
+            .iter()
+            .map(|p| PaillierScheme::encrypt(&ek, &encode_signed(p, &ek.n).unwrap()))
+            .collect();
+
+        let mut packed = layout.pack_ciphertexts::<PaillierScheme>(&ek, &cts);
+        let mut all_shares = vec![];
+        for _ in 1..num_parties {
+            let (masked, shares) = layout.mask_ciphertexts::<PaillierScheme>(&ek, &packed, products.len());
+            packed = masked;
+            all_shares.push(shares);
+        }
+        let decrypted: Vec<BigInt> = packed
+            .iter()
+            .map(|ct| PaillierScheme::decrypt(&ek, &dk, ct).unwrap())
+            .collect();
+        all_shares.push(layout.unpack_shares(&decrypted, products.len(), &ek.n));
+
+        for (k, product) in products.iter().enumerate() {
+            let sum = all_shares.iter().fold(BigInt::from(0), |acc, s| BigInt::mod_add(&acc, &s[k], &ek.n));
+            assert_eq!(decode_signed(&sum, &ek.n), *product);
+        }
+    }
+}
@@ -0,0 +1,618 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use curv::arithmetic::Modulo;
+use kzen_paillier::BigInt;
+use serde::Serialize;
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::encoding::FixedPoint;
+use crate::errors::ProtocolError;
+use crate::homomorphic::SchemeKind;
+use crate::protocol::{Action, InputSource, PartyContext, PartyKeys, PrivateInputs, Protocol, Registry};
+use crate::relay_log::{RelayLog, SignedCheckpoint, VerifyingKey};
+use crate::replay::ReplayGuard;
+use crate::resume;
+use crate::share_store::{ShareStore, SharedValue};
+use crate::types::{AbortMessage, BroadcastMessage, ClientMessage, CompletionReport, Dropout, InitializeProtocol, MessageHeader, RelayedMessage, RelayerMessage, ResumeRequest, ResumedMessage, RevealMode, RevealShare, RoundMessage, UnicastMessage, WebsocketMessage};
+use crate::validation::{self, Policy};
+
+/// Where a client keeps secrets from one session to the next, e.g. its vault.
+pub trait SecretStore: Send {
+    /// A key pair of `scheme` with `bits`-bit keys kept from an earlier session, as JSON.
+    fn keys(&self, scheme: SchemeKind, bits: usize) -> Option<Value>;
+
+    fn put_keys(&mut self, scheme: SchemeKind, bits: usize, keys: Value);
+
+    /// This party's shares of the result of a computation of a session.
+    fn put_share(&mut self, session_id: &str, computation_id: usize, value: &SharedValue);
+
+    /// Forget the shares of a computation, which a later one used up.
+    fn delete_share(&mut self, session_id: &str, computation_id: usize);
+}
+
+/// Everything a client does in a session, whatever carries its messages.
+///
+/// Every message the server relays to the client goes through `handle_message`, which returns the messages
+/// the client sends back to the server in reply. The websocket ClientActor and the in-process simulator only
+/// move these messages around. A party outlives a dropped connection: the server resumes the session on a new
+/// one with `ClientMessage::Resume` (see shared::resume).
+pub struct Party {
+    // protocols this client knows how to run, keyed by the id the server sends in InitializeProtocol
+    registry: Registry,
+    // if this is the first client, it will generate and store the keys which will be used to decrypt and obtain the final result.
+    keys: PartyKeys,
+    // every computation of the session, as announced by the server
+    sessions: HashMap<usize, Session>,
+    // this party's additive shares of the result of every finished computation
+    shares: ShareStore,
+    // shares received from the other parties in the reveal phase, keyed by computation id and then by their sid
+    received_shares: HashMap<usize, HashMap<usize, Vec<BigInt>>>,
+    // results this party reconstructed in the reveal phase, keyed by computation id
+    results: HashMap<usize, Vec<String>>,
+    inputs: Box<dyn InputSource>,
+    // index of this party in the session, known once the first computation is announced
+    sid: Option<usize>,
+    // parties the server reported dropped out of the session, see Protocol::dropped
+    dropped: HashSet<usize>,
+    // key the server announced with the first computation, which must sign every checkpoint
+    server_key: Option<VerifyingKey>,
+    // chain over every message received from the server but checkpoints, to compare with the server's
+    relay_log: RelayLog,
+    // outcome of the last checkpoint: the number of messages it confirmed, or why it was refused
+    relay_check: Option<Result<u64, ProtocolError>>,
+    // session announced with the first computation, and the last counter accepted from every other client
+    guard: Option<ReplayGuard>,
+    // counter of the last message this party sent
+    seq: u64,
+    // token the server resumes the session with, announced with the first computation
+    resume_token: Option<String>,
+    // number of messages received from the server in the session, resume requests aside
+    received: u64,
+    // messages this party sent that the server may still ask for again when the session resumes, oldest first
+    sent: VecDeque<SentMessage>,
+    // counter of the last message dropped from `sent` before the server acknowledged it
+    forgotten: u64,
+    // what this party accepts from the server and the other parties
+    policy: Policy,
+    // keeps key pairs and output shares beyond the session, if any
+    store: Option<Box<dyn SecretStore>>,
+}
+
+// Most messages kept to send again: the server acknowledges them on every resume and every checkpoint, so only a
+// session stuck that long without either fills it up.
+const MAX_UNACKNOWLEDGED: usize = 4096;
+
+// A message kept to send again, serialized. It may carry shares in the clear, so it is wiped once dropped.
+struct SentMessage {
+    seq: u64,
+    computation_id: usize,
+    json: Zeroizing<String>,
+}
+
+// Parameters of a computation and the state of the protocol running it.
+struct Session {
+    init: InitializeProtocol,
+    // dropped, with every secret it holds, once the computation has its output
+    protocol: Option<Box<dyn Protocol>>,
+}
+
+impl Party {
+    pub fn new(inputs: Box<dyn InputSource>) -> Self {
+        Party {
+            registry: Registry::default(),
+            keys: PartyKeys::default(),
+            sessions: HashMap::new(),
+            shares: ShareStore::default(),
+            received_shares: HashMap::new(),
+            results: HashMap::new(),
+            inputs,
+            sid: None,
+            dropped: HashSet::new(),
+            server_key: None,
+            relay_log: RelayLog::default(),
+            relay_check: None,
+            guard: None,
+            seq: 0,
+            resume_token: None,
+            received: 0,
+            sent: VecDeque::new(),
+            forgotten: 0,
+            policy: Policy::default(),
+            store: None,
+        }
+    }
+
+    /// Accept sessions and keys according to this policy rather than the default one.
+    pub fn with_policy(self, policy: Policy) -> Self {
+        Party { policy, ..self }
+    }
+
+    /// Reuse the key pairs kept in this store and keep every key pair generated and every output share in it.
+    pub fn with_store(mut self, store: Box<dyn SecretStore>) -> Self {
+        self.keys.keep_generated();
+        Party { store: Some(store), ..self }
+    }
+
+    /// Drop every secret this party holds: its keys, private inputs, shares and the state of the computations
+    /// still running, which cannot go on. Results already reconstructed are kept.
+    pub fn wipe(&mut self) {
+        self.keys = PartyKeys::default();
+        if self.store.is_some() {
+            self.keys.keep_generated();
+        }
+        self.inputs = Box::new(PrivateInputs::default());
+        self.shares = ShareStore::default();
+        self.received_shares.clear();
+        self.sent.clear();
+        for session in self.sessions.values_mut() {
+            session.protocol = None;
+        }
+    }
+
+    /// Id of the session this party takes part in, once the server announced the first computation.
+    pub fn session_id(&self) -> Option<&str> {
+        self.guard.as_ref().map(|guard| guard.session_id())
+    }
+
+    /// Whether a resume request is for this party's session and carries its token.
+    pub fn can_resume(&self, request: &ResumeRequest) -> bool {
+        self.session_id() == Some(request.session_id.as_str())
+            && self.sid == Some(request.sid)
+            && self.resume_token.as_ref().is_some_and(|token| resume::tokens_match(&request.token, token))
+    }
+
+    /// Index of this party in the session, once the server announced the first computation.
+    pub fn sid(&self) -> Option<usize> {
+        self.sid
+    }
+
+    /// This party's shares of every finished computation.
+    pub fn shares(&self) -> &ShareStore {
+        &self.shares
+    }
+
+    /// Every key pair this party generated, secret keys included.
+    #[cfg(feature = "seeded-rng")]
+    pub fn exported_keys(&self) -> &[Value] {
+        self.keys.exported()
+    }
+
+    /// Outcome of the last relay log checkpoint: the number of messages it confirmed, or why it was refused.
+    pub fn relay_check(&self) -> Option<&Result<u64, ProtocolError>> {
+        self.relay_check.as_ref()
+    }
+
+    /// Number of messages this party keeps to send again if the session resumes.
+    pub fn unacknowledged(&self) -> usize {
+        self.sent.len()
+    }
+
+    /// The decoded result of a computation, if this party reconstructed it.
+    pub fn result(&self, computation_id: usize) -> Option<&[String]> {
+        self.results.get(&computation_id).map(|r| r.as_slice())
+    }
+
+    /// Handle a message relayed by the server and return the messages to send back to it.
+    pub fn handle_message(&mut self, msg: ClientMessage) -> Vec<WebsocketMessage> {
+        let mut out = Vec::new();
+        match msg {
+            ClientMessage::InitializeProtocol(init) => self.start_protocol(init, &mut out),
+            ClientMessage::Relayed(msg) => self.relayed_message(msg, &mut out),
+            ClientMessage::Round(_) | ClientMessage::RevealShare(_) => {
+                eprintln!("Dropped a message of another client that was not relayed with its header")
+            }
+            ClientMessage::Checkpoint(msg) => self.check_relay_log(msg, &mut out),
+            ClientMessage::Dropout(msg) => self.dropout(msg, &mut out),
+            ClientMessage::Resume(request) => return self.resume(request),
+        }
+        for msg in &out {
+            self.keep_sent(msg);
+        }
+        out
+    }
+
+    /// Parse a JSON text frame from the server and handle it.
+    pub fn handle_text(&mut self, text: &str) -> Vec<WebsocketMessage> {
+        match serde_json::from_str::<ClientMessage>(text) {
+            Ok(msg) => {
+                // the sid of the first message is only known from the message itself
+                let to = match &msg {
+                    ClientMessage::InitializeProtocol(init) => Some(init.sid),
+                    _ => self.sid,
+                };
+                if let Some(to) = to && !matches!(msg, ClientMessage::Checkpoint(_) | ClientMessage::Resume(_)) {
+                    self.relay_log.append(to, text);
+                }
+                if !matches!(msg, ClientMessage::Resume(_)) {
+                    self.received += 1;
+                }
+                self.handle_message(msg)
+            }
+            Err(e) => {
+                println!("Failed to parse message: {}", e);
+                self.received += 1;
+                // the frame was damaged by the relay or the sender, there is no telling which
+                let mut out = Vec::new();
+                if let Some(sid) = self.sid {
+                    let computation_id = self.sessions.keys().max().copied().unwrap_or_default();
+                    self.send_abort(sid, computation_id, ProtocolError::MalformedMessage(e.to_string()), None, &mut out);
+                }
+                out
+            }
+        }
+    }
+
+    /// Bind the next message this party sends to the session and a computation.
+    fn header(&mut self, computation_id: usize) -> MessageHeader {
+        self.seq += 1;
+        let session_id = self.guard.as_ref().map(|g| g.session_id().to_string()).unwrap_or_default();
+        MessageHeader { session_id, computation_id, seq: self.seq }
+    }
+
+    fn send_unicast<T: Serialize>(&mut self, from: usize, to: usize, computation_id: usize, data: T, out: &mut Vec<WebsocketMessage>) {
+        let data_value = serde_json::to_value(data).unwrap_or_else(|e| {
+            eprintln!("Failed to convert UnicastMessage to value: {}", e);
+            Value::Null
+        });
+        let header = self.header(computation_id);
+        out.push(WebsocketMessage::Unicast(UnicastMessage::new(from, to, header, data_value)));
+    }
+
+    fn send_broadcast<T: Serialize>(&mut self, from: usize, computation_id: usize, data: T, out: &mut Vec<WebsocketMessage>) {
+        let data_value = serde_json::to_value(data).unwrap_or_else(|e| {
+            eprintln!("Failed to convert BroadcastMessage to value: {}", e);
+            Value::Null
+        });
+        let header = self.header(computation_id);
+        out.push(WebsocketMessage::Broadcast(BroadcastMessage::new(from, header, data_value)));
+    }
+
+    /// Refuse to go on with the protocol and ask the server to abort the session, naming the party at fault
+    /// if there is one.
+    fn send_abort(&mut self, from: usize, computation_id: usize, error: ProtocolError, blame: Option<usize>, out: &mut Vec<WebsocketMessage>) {
+        eprintln!("Aborting the protocol: {}", error);
+        self.wipe();
+        let header = self.header(computation_id);
+        out.push(WebsocketMessage::Abort(AbortMessage::new(from, header, error.to_string()).blaming(blame)));
+    }
+
+    /// Parameters the server sent for a computation.
+    fn computation(&self, computation_id: usize) -> Result<&InitializeProtocol, ProtocolError> {
+        self.sessions.get(&computation_id).map(|s| &s.init).ok_or(ProtocolError::UnknownComputation(computation_id))
+    }
+
+    fn start_protocol(&mut self, init: InitializeProtocol, out: &mut Vec<WebsocketMessage>) {
+        // an announcement of another session, or of a computation already started, is a replay
+        match &self.guard {
+            None => {
+                self.guard = Some(ReplayGuard::new(init.session_id.clone()));
+                self.resume_token = Some(init.resume_token.clone());
+            }
+            Some(guard) if guard.session_id() != init.session_id => {
+                return eprintln!("Dropped the announcement of computation {} of another session", init.computation_id);
+            }
+            _ => {}
+        }
+        if self.sessions.contains_key(&init.computation_id) {
+            return eprintln!("Dropped a repeated announcement of computation {}", init.computation_id);
+        }
+        let computation_id = init.computation_id;
+        if let Err(e) = validation::check_init(&init, &self.policy) {
+            // without a valid sid there is no telling who this party is
+            if init.sid >= init.num_parties {
+                return eprintln!("Refused computation {}: {}", computation_id, e);
+            }
+            self.sid = Some(init.sid);
+            return self.send_abort(init.sid, computation_id, e.into(), None, out);
+        }
+        self.sid = Some(init.sid);
+        match (&self.server_key, &init.server_key) {
+            (None, key) => self.server_key = key.clone(),
+            (Some(known), key) if Some(known) != key.as_ref() => {
+                return self.send_abort(init.sid, computation_id, ProtocolError::ServerKeyChanged, None, out);
+            }
+            _ => {}
+        }
+        if let RevealMode::OutputParty(party) = init.reveal && party >= init.num_parties {
+            let error = ProtocolError::InvalidOutputParty { party, num_parties: init.num_parties };
+            return self.send_abort(init.sid, computation_id, error, None, out);
+        }
+        let protocol = match self.registry.create(&init) {
+            Ok(protocol) => protocol,
+            Err(e) => return self.send_abort(init.sid, computation_id, e, None, out),
+        };
+        if let Some(keys) = self.store.as_ref().and_then(|store| store.keys(init.scheme, init.bits_security)) {
+            self.keys.offer(init.scheme, keys);
+        }
+        self.sessions.insert(computation_id, Session { init, protocol: Some(protocol) });
+        self.run_step(computation_id, None, |protocol, pctx| protocol.start(pctx), out);
+    }
+
+    /// Check the header of a message relayed from another client and handle it if it is neither a copy of a
+    /// message already handled nor a message of another session.
+    fn relayed_message(&mut self, msg: RelayedMessage, out: &mut Vec<WebsocketMessage>) {
+        let RelayedMessage { from, header, data } = msg;
+        let Some(guard) = self.guard.as_mut() else {
+            return eprintln!("Received a message from client {} before any computation", from);
+        };
+        if let Err(e) = guard.check(from, &header) {
+            return eprintln!("Dropped a message: {}", e);
+        }
+        match serde_json::from_value::<ClientMessage>(data) {
+            Ok(ClientMessage::Round(msg)) if msg.computation_id == header.computation_id && msg.from == from => {
+                self.round_message(msg, out)
+            }
+            Ok(ClientMessage::RevealShare(msg)) if msg.computation_id == header.computation_id && msg.sid == from => {
+                self.reveal_share(msg, out)
+            }
+            Ok(_) => eprintln!("Dropped a message from client {} that does not match its header", from),
+            Err(e) => {
+                if let Some(sid) = self.sid {
+                    self.send_abort(sid, header.computation_id, ProtocolError::MalformedMessage(e.to_string()), None, out);
+                }
+            }
+        }
+    }
+
+    fn round_message(&mut self, msg: RoundMessage, out: &mut Vec<WebsocketMessage>) {
+        println!("Received round {} of computation {} from client {}", msg.round, msg.computation_id, msg.from);
+        let RoundMessage { computation_id, from, round, payload } = msg;
+        if let Ok(init) = self.computation(computation_id) && let Err(e) = validation::check_sender(init, from) {
+            let sid = init.sid;
+            return self.send_abort(sid, computation_id, e.into(), None, out);
+        }
+        self.run_step(computation_id, Some(from), |protocol, pctx| protocol.handle_round(pctx, from, round, payload), out);
+    }
+
+    /// Run one step of the protocol of a computation and perform the actions it asks for. If the step fails on
+    /// a message of `sender` rather than on this party's own input, the abort blames the sender.
+    fn run_step<F>(&mut self, computation_id: usize, sender: Option<usize>, step: F, out: &mut Vec<WebsocketMessage>)
+    where
+        F: FnOnce(&mut dyn Protocol, &mut PartyContext) -> Result<Vec<Action>, ProtocolError>,
+    {
+        let Some(session) = self.sessions.get_mut(&computation_id) else {
+            return eprintln!("Received a message for unknown computation {}", computation_id);
+        };
+        let sid = session.init.sid;
+        let Some(protocol) = session.protocol.as_mut() else {
+            return eprintln!("Received a message for computation {}, which is over", computation_id);
+        };
+        let mut pctx = PartyContext {
+            init: &session.init,
+            shares: &self.shares,
+            keys: &mut self.keys,
+            inputs: self.inputs.as_ref(),
+            dropped: &self.dropped,
+        };
+        let result = step(protocol.as_mut(), &mut pctx);
+        if let Some(store) = &mut self.store {
+            for (scheme, bits, keys) in self.keys.take_generated() {
+                store.put_keys(scheme, bits, keys);
+            }
+        }
+        match result {
+            Ok(actions) => {
+                for action in actions {
+                    self.perform(sid, computation_id, action, out);
+                }
+            }
+            Err(e) => {
+                let blame = if e.is_local_input() { Some(sid) } else { sender };
+                self.send_abort(sid, computation_id, e, blame, out)
+            }
+        }
+    }
+
+    fn perform(&mut self, sid: usize, computation_id: usize, action: Action, out: &mut Vec<WebsocketMessage>) {
+        match action {
+            Action::Send { to, round, payload } => {
+                let msg = ClientMessage::Round(RoundMessage { computation_id, from: sid, round, payload });
+                self.send_unicast(sid, to, computation_id, msg, out);
+            }
+            Action::Broadcast { round, payload } => {
+                let msg = ClientMessage::Round(RoundMessage { computation_id, from: sid, round, payload });
+                self.send_broadcast(sid, computation_id, msg, out);
+            }
+            Action::Output(value) => {
+                if let Some(session) = self.sessions.get_mut(&computation_id) {
+                    session.protocol = None;
+                }
+                if let (Some(store), Some(guard)) = (&mut self.store, &self.guard) {
+                    store.put_share(guard.session_id(), computation_id, &value);
+                }
+                self.shares.insert(computation_id, value);
+                self.start_reveal(computation_id, out);
+            }
+            Action::Public(value) => {
+                if let Some(session) = self.sessions.get_mut(&computation_id) {
+                    session.protocol = None;
+                }
+                let result: Vec<String> = value
+                    .shares
+                    .iter()
+                    .map(|x| FixedPoint::decode(x, &value.n, value.scale).to_string())
+                    .collect();
+                println!("Computed result of computation {}: {:?}", computation_id, result);
+                self.results.insert(computation_id, result.clone());
+                self.report_completion(sid, computation_id, Some(result), out);
+            }
+            Action::Consume(id) => {
+                if let (Some(store), Some(guard)) = (&mut self.store, &self.guard) {
+                    store.delete_share(guard.session_id(), id);
+                }
+                self.shares.remove(id);
+            }
+        }
+    }
+
+    /// A party dropped out of the session: protocols that tolerate it go on without it, the others fail.
+    fn dropout(&mut self, msg: Dropout, out: &mut Vec<WebsocketMessage>) {
+        println!("Client {} dropped out during computation {}", msg.sid, msg.computation_id);
+        self.dropped.insert(msg.sid);
+        self.run_step(msg.computation_id, None, |protocol, pctx| protocol.dropped(pctx, msg.sid), out);
+    }
+
+    /// Called once this party holds its shares of the result of a computation.
+    fn start_reveal(&mut self, computation_id: usize, out: &mut Vec<WebsocketMessage>) {
+        let (Ok(init), Ok(value)) = (self.computation(computation_id), self.shares.get(computation_id)) else {
+            return eprintln!("Nothing to reveal for computation {}", computation_id);
+        };
+        let sid = init.sid;
+        let reveal_msg = ClientMessage::RevealShare(RevealShare { computation_id, sid, shares: value.shares.clone() });
+        match init.reveal {
+            RevealMode::Private => self.report_completion(sid, computation_id, None, out),
+            RevealMode::Public => {
+                self.send_broadcast(sid, computation_id, reveal_msg, out);
+                self.try_reconstruct(computation_id, out);
+            }
+            RevealMode::OutputParty(party) if party == sid => self.try_reconstruct(computation_id, out),
+            RevealMode::OutputParty(party) => {
+                self.send_unicast(sid, party, computation_id, reveal_msg, out);
+                self.report_completion(sid, computation_id, None, out);
+            }
+        }
+    }
+
+    fn reveal_share(&mut self, share: RevealShare, out: &mut Vec<WebsocketMessage>) {
+        println!("Received reveal shares of computation {} from client {}", share.computation_id, share.sid);
+        let RevealShare { computation_id, sid: from, shares } = share;
+        let Ok(init) = self.computation(computation_id) else {
+            return eprintln!("Dropped reveal shares of unknown computation {}", computation_id);
+        };
+        let sid = init.sid;
+        if let Err(e) = validation::check_sender(init, from) {
+            return self.send_abort(sid, computation_id, e.into(), None, out);
+        }
+        let recipient = match init.reveal {
+            RevealMode::Public => true,
+            RevealMode::OutputParty(party) => party == sid,
+            RevealMode::Private => false,
+        };
+        if !recipient {
+            return eprintln!("Dropped reveal shares of computation {} from client {}, which is not revealed to this party", computation_id, from);
+        }
+        // shares may arrive before this party is done with its own part of the computation, so keep them until then
+        // and check them in try_reconstruct
+        if let Ok(value) = self.shares.get(computation_id) && let Err(e) = validation::check_shares(&shares, value.shares.len(), &value.n) {
+            return self.send_abort(sid, computation_id, e.into(), Some(from), out);
+        }
+        self.received_shares.entry(computation_id).or_default().insert(from, shares);
+        self.try_reconstruct(computation_id, out);
+    }
+
+    /// Reconstruct and decode a result once this party holds its own shares and every other party's.
+    fn try_reconstruct(&mut self, computation_id: usize, out: &mut Vec<WebsocketMessage>) {
+        let (Ok(init), Ok(value)) = (self.computation(computation_id), self.shares.get(computation_id)) else {
+            return;
+        };
+        let Some(received) = self.received_shares.get(&computation_id) else {
+            return;
+        };
+        if received.len() < init.num_parties - 1 {
+            return;
+        }
+        let invalid = received.iter().find_map(|(&from, shares)| validation::check_shares(shares, value.shares.len(), &value.n).err().map(|e| (from, e)));
+        if let Some((from, e)) = invalid {
+            let sid = init.sid;
+            return self.send_abort(sid, computation_id, e.into(), Some(from), out);
+        }
+        let mut sums = value.shares.clone();
+        for shares in received.values() {
+            for (sum, share) in sums.iter_mut().zip(shares) {
+                *sum = BigInt::mod_add(sum, share, &value.n);
+            }
+        }
+        let result: Vec<String> = sums
+            .iter()
+            .map(|sum| FixedPoint::decode(sum, &value.n, value.scale).to_string())
+            .collect();
+        println!("Reconstructed result of computation {}: {:?}", computation_id, result);
+        // in OutputParty mode the result is kept to this party
+        let report = if init.reveal == RevealMode::Public { Some(result.clone()) } else { None };
+        let sid = init.sid;
+        self.received_shares.remove(&computation_id);
+        self.results.insert(computation_id, result);
+        self.report_completion(sid, computation_id, report, out);
+    }
+
+    /// Compare the server's signed relay log with the messages this party received. A mismatch means the relay
+    /// dropped, reordered or injected messages, which no client is to blame for.
+    fn check_relay_log(&mut self, signed: SignedCheckpoint, out: &mut Vec<WebsocketMessage>) {
+        let Some(sid) = self.sid else {
+            return eprintln!("Received a checkpoint before any computation");
+        };
+        let result = self.verify_checkpoint(&signed, sid);
+        if result.is_ok() {
+            // the server is done with the computation and will not ask for its messages again
+            self.sent.retain(|msg| msg.computation_id != signed.checkpoint.computation_id);
+        }
+        match &result {
+            Ok(len) => println!("Relay log checked after computation {}: {} messages", signed.checkpoint.computation_id, len),
+            Err(e) => self.send_abort(sid, signed.checkpoint.computation_id, e.clone(), None, out),
+        }
+        self.relay_check = Some(result);
+    }
+
+    fn verify_checkpoint(&self, signed: &SignedCheckpoint, sid: usize) -> Result<u64, ProtocolError> {
+        let key = self.server_key.as_ref().ok_or(ProtocolError::InvalidCheckpoint)?;
+        let session_id = self.guard.as_ref().map(|g| g.session_id());
+        if !signed.verify(key) || session_id != Some(signed.checkpoint.session_id.as_str()) {
+            return Err(ProtocolError::InvalidCheckpoint);
+        }
+        let sent = signed.checkpoint.clients.get(sid).ok_or(ProtocolError::InvalidCheckpoint)?;
+        if *sent != self.relay_log {
+            return Err(ProtocolError::RelayLogMismatch { received: self.relay_log.len, sent: sent.len });
+        }
+        Ok(sent.len)
+    }
+
+    /// Go on with the session on a new connection: tell the server how many of its messages arrived and send again
+    /// the messages it did not get.
+    fn resume(&mut self, request: ResumeRequest) -> Vec<WebsocketMessage> {
+        if !self.can_resume(&request) {
+            eprintln!("Refused to resume session {} as client {}", request.session_id, request.sid);
+            return vec![];
+        }
+        if request.last_seq < self.forgotten {
+            eprintln!("Cannot resume the session: message {} is no longer kept to send again", request.last_seq + 1);
+            return vec![];
+        }
+        println!("Resuming the session, {} messages received so far", self.received);
+        // the server got everything up to last_seq
+        self.sent.retain(|msg| msg.seq > request.last_seq);
+        let computation_id = self.sessions.keys().max().copied().unwrap_or_default();
+        let header = self.header(computation_id);
+        let mut out = vec![WebsocketMessage::Resumed(ResumedMessage { from: request.sid, header, received: self.received })];
+        for msg in &self.sent {
+            match serde_json::from_str(&msg.json) {
+                Ok(msg) => out.push(msg),
+                Err(e) => eprintln!("Failed to send a message again: {}", e),
+            }
+        }
+        out
+    }
+
+    /// Keep a message to send again if the session resumes, dropping the oldest one past MAX_UNACKNOWLEDGED.
+    fn keep_sent(&mut self, msg: &WebsocketMessage) {
+        let json = match serde_json::to_string(msg) {
+            Ok(json) => Zeroizing::new(json),
+            Err(e) => return eprintln!("Failed to keep a message to send again: {}", e),
+        };
+        let header = msg.header();
+        self.sent.push_back(SentMessage { seq: header.seq, computation_id: header.computation_id, json });
+        if self.sent.len() > MAX_UNACKNOWLEDGED && let Some(oldest) = self.sent.pop_front() {
+            self.forgotten = self.forgotten.max(oldest.seq);
+        }
+    }
+
+    /// Tell the server that this party is done with a computation.
+    fn report_completion(&mut self, sid: usize, computation_id: usize, result: Option<Vec<String>>, out: &mut Vec<WebsocketMessage>) {
+        let report = serde_json::to_value(CompletionReport { computation_id, result }).unwrap_or_else(|e| {
+            println!("Failed to convert value to the json: {}", e);
+            Value::Null
+        });
+        let header = self.header(computation_id);
+        out.push(WebsocketMessage::Relayer(RelayerMessage::new(sid, header, report)));
+    }
+}
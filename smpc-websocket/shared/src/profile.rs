@@ -0,0 +1,80 @@
+//! Named security levels a session can run at. The server picks one for every session and announces it in every
+//! InitializeProtocol, and the first client generates its key with the profile's modulus size. Every client
+//! refuses sessions below the minimum profile of its `validation::Policy`.
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// Size of the first client's key modulus, from the weakest to the strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub enum SecurityProfile {
+    // only for tests and simulations: a 512-bit modulus can be factored
+    #[serde(rename = "toy-512")]
+    Toy512,
+    // fast enough for local runs, not for real inputs
+    #[serde(rename = "test-1024")]
+    Test1024,
+    #[default]
+    #[serde(rename = "standard-2048")]
+    Standard2048,
+    #[serde(rename = "high-3072")]
+    High3072,
+}
+
+impl SecurityProfile {
+    pub const ALL: [SecurityProfile; 4] =
+        [SecurityProfile::Toy512, SecurityProfile::Test1024, SecurityProfile::Standard2048, SecurityProfile::High3072];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SecurityProfile::Toy512 => "toy-512",
+            SecurityProfile::Test1024 => "test-1024",
+            SecurityProfile::Standard2048 => "standard-2048",
+            SecurityProfile::High3072 => "high-3072",
+        }
+    }
+
+    /// Bit length of the key modulus n.
+    pub fn modulus_bits(&self) -> usize {
+        match self {
+            SecurityProfile::Toy512 => 512,
+            SecurityProfile::Test1024 => 1024,
+            SecurityProfile::Standard2048 => 2048,
+            SecurityProfile::High3072 => 3072,
+        }
+    }
+}
+
+impl Display for SecurityProfile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for SecurityProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SecurityProfile::ALL
+            .into_iter()
+            .find(|profile| profile.name() == s)
+            .ok_or_else(|| format!("unknown security profile '{}'", s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_names() {
+        for profile in SecurityProfile::ALL {
+            assert_eq!(profile.name().parse(), Ok(profile));
+            assert_eq!(serde_json::to_string(&profile).unwrap(), format!("\"{}\"", profile.name()));
+            assert!(profile.name().ends_with(&profile.modulus_bits().to_string()));
+        }
+        assert!("standard-1024".parse::<SecurityProfile>().is_err());
+        assert!(SecurityProfile::Test1024 < SecurityProfile::Standard2048);
+    }
+}
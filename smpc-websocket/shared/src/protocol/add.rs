@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::errors::ProtocolError;
+use crate::protocol::{decode_payload, unexpected_round, Action, PartyContext, Protocol};
+use crate::types::{Computation, InitializeProtocol, RevealMode};
+
+/// Sum of the results of two earlier computations. Every party adds its shares locally, without any message.
+pub const ID: &str = "add";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Params {
+    pub lhs: usize,
+    pub rhs: usize,
+}
+
+pub struct Add {
+    params: Params,
+}
+
+pub fn create(init: &InitializeProtocol) -> Result<Box<dyn Protocol>, ProtocolError> {
+    Ok(Box::new(Add { params: decode_payload(init.params.clone())? }))
+}
+
+pub fn computation(id: usize, lhs: usize, rhs: usize, reveal: RevealMode) -> Computation {
+    Computation { id, protocol: ID.to_string(), params: json!({ "lhs": lhs, "rhs": rhs }), reveal }
+}
+
+impl Protocol for Add {
+    fn start(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        Ok(vec![Action::Output(ctx.shares.add(self.params.lhs, self.params.rhs)?)])
+    }
+
+    fn handle_round(&mut self, _ctx: &mut PartyContext, _from: usize, round: u32, _payload: Value) -> Result<Vec<Action>, ProtocolError> {
+        Err(unexpected_round(ID, round))
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+
+use curv::arithmetic::traits::Modulo;
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::encoding::check_signed_result_bound;
+use crate::errors::{ProtocolError, ValidationError};
+use crate::protocol::{decode_payload, multiply, random, unexpected_round, Action, PartyContext, Protocol};
+#[cfg(feature = "insecure-mock")]
+use crate::rng;
+use crate::share_store::SharedValue;
+use crate::types::{Computation, InitializeProtocol, RevealMode};
+
+/// Share-in/share-out multiplication [x] * [y] of the results of two earlier computations in one round, with a
+/// Beaver triple: shares of random a and b and of c = a * b.
+///
+/// Every party broadcasts d_j = x_j - a_j and e_j = y_j - b_j, which open d = x - a and e = y - b without
+/// telling anything about x or y. Its share of the product is then c_j + d b_j + e a_j, plus d e for the first
+/// client: they sum to c + d b + e a + d e = x y. A triple masks a single multiplication, so the party wipes
+/// its shares of the triple once used.
+///
+/// The triples are made beforehand, either under the session's key with `preprocessing`, or dealt by the
+/// server, which then learns every operand: the server only deals them in test mode, with insecure-mock.
+pub const ID: &str = "beaver";
+
+const OPEN: u32 = 0;
+
+// extra bits of the dealt integers, so that they are close to uniform mod any modulus of the session
+#[cfg(feature = "insecure-mock")]
+const STATISTICAL_BITS: usize = 40;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Triple {
+    // ids of the computations that shared a, b and c = a * b mod n, see preprocessing
+    Computed { a: usize, b: usize, c: usize },
+    // the server deals the triple when it starts the computation, in test mode only
+    Dealer,
+    // the integer shares of a, b and c = a * b the server dealt this party, element-wise
+    Dealt { a: Vec<BigInt>, b: Vec<BigInt>, c: Vec<BigInt> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Params {
+    pub lhs: usize,
+    pub rhs: usize,
+    pub triple: Triple,
+}
+
+// this party's share of x - a and of y - b, element-wise
+#[derive(Debug, Serialize, Deserialize)]
+struct Open {
+    d: Vec<BigInt>,
+    e: Vec<BigInt>,
+}
+
+pub struct Beaver {
+    params: Params,
+    // the opening shares of every party, this party's own included
+    opened: BTreeMap<usize, Open>,
+}
+
+pub fn create(init: &InitializeProtocol) -> Result<Box<dyn Protocol>, ProtocolError> {
+    let params: Params = decode_payload(init.params.clone())?;
+    if let Triple::Dealer = params.triple {
+        return Err(ProtocolError::NoDealer);
+    }
+    Ok(Box::new(Beaver { params, opened: BTreeMap::new() }))
+}
+
+pub fn computation(id: usize, lhs: usize, rhs: usize, triple: Triple, reveal: RevealMode) -> Computation {
+    Computation { id, protocol: ID.to_string(), params: json!({ "lhs": lhs, "rhs": rhs, "triple": triple }), reveal }
+}
+
+/// The computations making one triple per element under the first client's key: random shares of a and b
+/// under ids `first` and `first + 1`, multiplied into c mod n under `first + 2` by the Paillier chain of
+/// protocol::multiply. They can run long before the triple is used, in the same session.
+pub fn preprocessing(first: usize) -> (Vec<Computation>, Triple) {
+    let (a, b, c) = (first, first + 1, first + 2);
+    let product = Computation {
+        params: json!({ "lhs": a, "rhs": b, "modular": true }),
+        ..multiply::computation(c, a, b, RevealMode::Private)
+    };
+    (vec![random::computation(a), random::computation(b), product], Triple::Computed { a, b, c })
+}
+
+/// Deal the triples of a beaver computation that takes them from the dealer: the parameters every party gets,
+/// with its shares in place of `Triple::Dealer`. None for any other computation.
+///
+/// The dealer does not know the modulus of the operands, so it deals integers: a and b are sums of uniform
+/// integers far larger than any plaintext modulus of a `modulus_bits` key, whose shares every party reduces mod
+/// the modulus of its operands.
+#[cfg(feature = "insecure-mock")]
+pub fn deal(computation: &Computation, num_parties: usize, vector_len: usize, modulus_bits: usize) -> Option<Vec<Value>> {
+    let mut params: Params = serde_json::from_value(computation.params.clone()).ok()?;
+    if computation.protocol != ID || !matches!(params.triple, Triple::Dealer) {
+        return None;
+    }
+    // Damgård-Jurik plaintexts are mod n^2, twice the key size
+    let bits = 2 * modulus_bits + STATISTICAL_BITS;
+    let mut dealt = vec![(Vec::new(), Vec::new(), Vec::new()); num_parties];
+    let sum = |shares: &[BigInt]| shares.iter().fold(BigInt::from(0), |acc, s| acc + s);
+    for _ in 0..vector_len {
+        // every c_j but the first is uniform and the first one completes the product. Shares are serialized
+        // without their sign, so the triple is drawn again if the first one would be negative, which only
+        // happens when a or b is far below its expected size.
+        let (a, b, c) = loop {
+            let a: Vec<BigInt> = (0..num_parties).map(|_| rng::sample(bits)).collect();
+            let b: Vec<BigInt> = (0..num_parties).map(|_| rng::sample(bits)).collect();
+            let mut c: Vec<BigInt> = (0..num_parties).map(|_| rng::sample(bits)).collect();
+            c[0] = sum(&a) * sum(&b) - sum(&c[1..]);
+            if c[0] >= BigInt::from(0) {
+                break (a, b, c);
+            }
+        };
+        for (party, ((a, b), c)) in dealt.iter_mut().zip(a.into_iter().zip(b).zip(c)) {
+            party.0.push(a);
+            party.1.push(b);
+            party.2.push(c);
+        }
+    }
+    dealt
+        .into_iter()
+        .map(|(a, b, c)| {
+            params.triple = Triple::Dealt { a, b, c };
+            serde_json::to_value(&params).ok()
+        })
+        .collect()
+}
+
+impl Beaver {
+    /// This party's shares of the triple, mod `n` like the operands.
+    fn triple(&self, ctx: &PartyContext, n: &BigInt, len: usize) -> Result<[Vec<BigInt>; 3], ProtocolError> {
+        match &self.params.triple {
+            Triple::Computed { a, b, c } => {
+                let (ta, tb) = ctx.shares.operands(*a, *b)?;
+                let tc = ctx.shares.get(*c)?;
+                if ta.n != *n || tc.n != *n || ta.shares.len() != len || tc.shares.len() != len {
+                    return Err(ProtocolError::IncompatibleShares { lhs: self.params.lhs, rhs: *c });
+                }
+                Ok([ta.shares.clone(), tb.shares.clone(), tc.shares.clone()])
+            }
+            Triple::Dealt { a, b, c } => {
+                if let Some(wrong) = [a, b, c].into_iter().find(|shares| shares.len() != len) {
+                    return Err(ValidationError::WrongLength { expected: len, actual: wrong.len() }.into());
+                }
+                let reduce = |shares: &Vec<BigInt>| shares.iter().map(|s| BigInt::modulus(s, n)).collect();
+                Ok([reduce(a), reduce(b), reduce(c)])
+            }
+            Triple::Dealer => Err(ProtocolError::NoDealer),
+        }
+    }
+
+    /// Once every party opened its shares: this party's shares of the product, after wiping the triple.
+    fn output(&self, ctx: &PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        let (x, y) = ctx.shares.operands(self.params.lhs, self.params.rhs)?;
+        let n = &x.n;
+        let [a, b, c] = self.triple(ctx, n, x.shares.len())?;
+        let open = |pick: fn(&Open) -> &Vec<BigInt>, k: usize| {
+            self.opened.values().fold(BigInt::from(0), |acc, o| BigInt::mod_add(&acc, &pick(o)[k], n))
+        };
+        let shares = (0..x.shares.len())
+            .map(|k| {
+                let (d, e) = (open(|o| &o.d, k), open(|o| &o.e, k));
+                let mut z = BigInt::mod_add(&c[k], &BigInt::mod_mul(&d, &b[k], n), n);
+                z = BigInt::mod_add(&z, &BigInt::mod_mul(&e, &a[k], n), n);
+                if ctx.init.sid == 0 {
+                    z = BigInt::mod_add(&z, &BigInt::mod_mul(&d, &e, n), n);
+                }
+                z
+            })
+            .collect();
+        let mut actions = Vec::new();
+        if let Triple::Computed { a, b, c } = self.params.triple {
+            actions.extend([a, b, c].map(Action::Consume));
+        }
+        actions.push(Action::Output(SharedValue { n: n.clone(), shares, scale: x.scale + y.scale, value_bits: x.value_bits + y.value_bits }));
+        Ok(actions)
+    }
+}
+
+impl Protocol for Beaver {
+    fn start(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        let (x, y) = ctx.shares.operands(self.params.lhs, self.params.rhs)?;
+        let n = &x.n;
+        check_signed_result_bound(x.value_bits + y.value_bits, n)?;
+        let [a, b, _] = self.triple(ctx, n, x.shares.len())?;
+        let d = x.shares.iter().zip(&a).map(|(x, a)| BigInt::mod_sub(x, a, n)).collect();
+        let e = y.shares.iter().zip(&b).map(|(y, b)| BigInt::mod_sub(y, b, n)).collect();
+        let open = Open { d, e };
+        let action = Action::broadcast(OPEN, &open)?;
+        self.opened.insert(ctx.init.sid, open);
+        Ok(vec![action])
+    }
+
+    fn handle_round(&mut self, ctx: &mut PartyContext, from: usize, round: u32, payload: Value) -> Result<Vec<Action>, ProtocolError> {
+        if round != OPEN || self.opened.contains_key(&from) {
+            return Err(unexpected_round(ID, round));
+        }
+        let open: Open = decode_payload(payload)?;
+        let x = ctx.shares.get(self.params.lhs)?;
+        for values in [&open.d, &open.e] {
+            if values.len() != x.shares.len() {
+                return Err(ValidationError::WrongLength { expected: x.shares.len(), actual: values.len() }.into());
+            }
+            if values.iter().any(|v| *v < BigInt::from(0) || *v >= x.n) {
+                return Err(ValidationError::CiphertextOutOfRange.into());
+            }
+        }
+        self.opened.insert(from, open);
+        if self.opened.len() < ctx.init.num_parties {
+            return Ok(vec![]);
+        }
+        self.output(ctx)
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use curv::arithmetic::traits::Modulo;
+use curv::elliptic::curves::{Point, Scalar, Secp256k1};
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::encoding::check_signed_product_bound;
+use crate::errors::{ProtocolError, ValidationError};
+use crate::protocol::channel;
+use crate::protocol::{decode_payload, unexpected_round, Action, PartyContext, Protocol};
+use crate::rng;
+use crate::share_store::SharedValue;
+use crate::sharing::{self, Field};
+use crate::types::{Computation, InitializeProtocol, RevealMode};
+
+/// Element-wise product of one private input vector of every party over Shamir shares, after Ben-Or, Goldwasser
+/// and Wigderson with the degree reduction of Gennaro, Rabin and Rabin. It stays private as long as fewer than
+/// half the parties collude, and needs no homomorphic encryption: only arithmetic mod the secp256k1 group order.
+///
+/// Every party broadcasts a key for the private channels of the computation (see protocol::channel), then shares
+/// its input with polynomials of degree t = (n - 1) / 2 and unicasts every other party its shares. The n shared
+/// factors are multiplied pairwise in a tree: the product of two shares is a share of degree 2t < n, which every
+/// party shares again with degree t, and the sum of the reshares weighted by the Lagrange coefficients at 0 of
+/// all the parties gives each a share of degree t of the product. Once one factor is left, every party weights
+/// its share by its own Lagrange coefficient, which gives additive shares of the product.
+///
+/// The channel keys are not signed, so the server must relay the same KEYS broadcasts to everyone: one that
+/// substitutes them can open every sealed share, and with them every input.
+pub const ID: &str = "bgw";
+
+const KEYS: u32 = 0;
+const INPUT: u32 = 1;
+// round REDUCE + l reshares the products of level l of the tree
+const REDUCE: u32 = 2;
+// after the output, when no round is expected
+const DONE: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Params {
+    // index of the private input vector every party multiplies in
+    pub input: usize,
+}
+
+pub struct Bgw {
+    params: Params,
+    // the round in progress
+    round: u32,
+    sk: Scalar<Secp256k1>,
+    // channel keys by party, this party's own included
+    keys: BTreeMap<usize, Point<Secp256k1>>,
+    // shares received in every round by sender, this party's own included
+    received: BTreeMap<u32, BTreeMap<usize, Vec<BigInt>>>,
+    // this party's shares of the factors left to multiply
+    factors: Vec<Vec<BigInt>>,
+}
+
+/// Honest majority needs at least 3 parties: with 2, either one alone could reconstruct every input.
+pub fn create(init: &InitializeProtocol) -> Result<Box<dyn Protocol>, ProtocolError> {
+    let params = decode_payload(init.params.clone())?;
+    if init.num_parties < 3 {
+        return Err(ProtocolError::InvalidThreshold { threshold: degree(init.num_parties) + 1, num_parties: init.num_parties });
+    }
+    Ok(Box::new(Bgw {
+        params,
+        round: KEYS,
+        sk: rng::sample_scalar(),
+        keys: BTreeMap::new(),
+        received: BTreeMap::new(),
+        factors: Vec::new(),
+    }))
+}
+
+pub fn computation(id: usize, input: usize, reveal: RevealMode) -> Computation {
+    Computation { id, protocol: ID.to_string(), params: json!({ "input": input }), reveal }
+}
+
+/// Degree of the sharing polynomials: the largest t with 2t < num_parties.
+fn degree(num_parties: usize) -> usize {
+    (num_parties - 1) / 2
+}
+
+/// Number of factors left at the start of every level of the tree, down to the last level.
+fn level_sizes(num_parties: usize) -> Vec<usize> {
+    let mut sizes = vec![num_parties];
+    while let Some(&m) = sizes.last().filter(|&&m| m > 2) {
+        sizes.push(m.div_ceil(2));
+    }
+    sizes
+}
+
+/// Number of shares every party sends in `round`, None if the computation has no such round.
+fn round_len(init: &InitializeProtocol, round: u32) -> Option<usize> {
+    match round {
+        INPUT => Some(init.vector_len),
+        _ => {
+            let level = round.checked_sub(REDUCE)? as usize;
+            level_sizes(init.num_parties).get(level).map(|m| m / 2 * init.vector_len)
+        }
+    }
+}
+
+impl Bgw {
+    /// Share every element of `values` among all the parties and seal each its shares, keeping this party's own.
+    fn deal(&mut self, ctx: &PartyContext, round: u32, values: &[BigInt]) -> Result<Vec<Action>, ProtocolError> {
+        let field = Field::secp256k1();
+        let sid = ctx.init.sid;
+        let parties: Vec<usize> = (0..ctx.init.num_parties).collect();
+        let shares: Vec<Vec<sharing::Share>> = values.iter().map(|x| field.share(x, degree(ctx.init.num_parties) + 1, &parties)).collect();
+        let mut actions = Vec::new();
+        for to in parties {
+            let mine: Vec<BigInt> = shares.iter().map(|element| element[to].y.clone()).collect();
+            if to == sid {
+                self.received.entry(round).or_default().insert(sid, mine);
+            } else {
+                let sealed = channel::seal(&self.sk, &self.keys[&to], ctx.init.computation_id, sid, to, &mine)?;
+                actions.push(Action::send(to, round, &sealed)?);
+            }
+        }
+        self.round = round;
+        Ok(actions)
+    }
+
+    /// Multiply the factors pairwise, the last one staying as it is when they are odd in number, and reshare the
+    /// products.
+    fn multiply(&mut self, ctx: &PartyContext, level: u32) -> Result<Vec<Action>, ProtocolError> {
+        let q = Field::secp256k1().modulus().clone();
+        let products: Vec<BigInt> = self
+            .factors
+            .chunks_exact(2)
+            .flat_map(|pair| pair[0].iter().zip(&pair[1]).map(|(a, b)| BigInt::mod_mul(a, b, &q)).collect::<Vec<_>>())
+            .collect();
+        self.deal(ctx, REDUCE + level, &products)
+    }
+
+    /// Combine the reshares of every party into this party's shares of degree t of the products, followed by the
+    /// factor left over.
+    fn reduce(&mut self, ctx: &PartyContext, reshares: BTreeMap<usize, Vec<BigInt>>) -> Vec<Vec<BigInt>> {
+        let field = Field::secp256k1();
+        let q = field.modulus();
+        let points: Vec<BigInt> = reshares.keys().map(|&sid| sharing::point(sid)).collect();
+        let lambdas = field.lagrange_coefficients(&points, &BigInt::from(0)).expect("the parties are at distinct points");
+        let mut products = vec![BigInt::from(0); reshares.values().next().map_or(0, Vec::len)];
+        for (shares, lambda) in reshares.values().zip(&lambdas) {
+            for (p, share) in products.iter_mut().zip(shares) {
+                *p = BigInt::mod_add(p, &BigInt::mod_mul(share, lambda, q), q);
+            }
+        }
+        let mut factors: Vec<Vec<BigInt>> = products.chunks(ctx.init.vector_len).map(<[BigInt]>::to_vec).collect();
+        if self.factors.len() % 2 == 1 {
+            factors.extend(self.factors.pop());
+        }
+        factors
+    }
+
+    /// This party's additive share of the product: its share of degree t weighted by its Lagrange coefficient.
+    fn output(&self, ctx: &PartyContext) -> Action {
+        let field = Field::secp256k1();
+        let q = field.modulus();
+        let points: Vec<BigInt> = (0..ctx.init.num_parties).map(sharing::point).collect();
+        let lambdas = field.lagrange_coefficients(&points, &BigInt::from(0)).expect("the parties are at distinct points");
+        let shares = self.factors[0].iter().map(|share| BigInt::mod_mul(share, &lambdas[ctx.init.sid], q)).collect();
+        // the product of num_parties inputs carries num_parties times the input scale
+        Action::Output(SharedValue {
+            n: q.clone(),
+            shares,
+            scale: ctx.init.scale * ctx.init.num_parties as u32,
+            value_bits: ctx.init.max_input_bits * ctx.init.num_parties,
+        })
+    }
+
+    /// Move on to the next rounds for as long as every party sent the round in progress.
+    fn advance(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        let n = ctx.init.num_parties;
+        let mut actions = Vec::new();
+        loop {
+            if self.round == KEYS {
+                if self.keys.len() < n {
+                    break;
+                }
+                let input = ctx.encoded_input(self.params.input, Field::secp256k1().modulus())?;
+                actions.extend(self.deal(ctx, INPUT, &input)?);
+                continue;
+            }
+            if self.round == DONE || self.received.get(&self.round).is_none_or(|shares| shares.len() < n) {
+                break;
+            }
+            let shares = self.received.remove(&self.round).unwrap_or_default();
+            self.factors = if self.round == INPUT { shares.into_values().collect() } else { self.reduce(ctx, shares) };
+            if self.factors.len() == 1 {
+                actions.push(self.output(ctx));
+                self.round = DONE;
+            } else {
+                let level = if self.round == INPUT { 0 } else { self.round - REDUCE + 1 };
+                actions.extend(self.multiply(ctx, level)?);
+            }
+        }
+        Ok(actions)
+    }
+}
+
+impl Protocol for Bgw {
+    fn start(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        // refuse to start if the signed product of all inputs could wrap around the field
+        check_signed_product_bound(ctx.init.num_parties, ctx.init.max_input_bits, Field::secp256k1().modulus())?;
+        let pk = Point::<Secp256k1>::generator() * &self.sk;
+        let action = Action::broadcast(KEYS, &pk)?;
+        self.keys.insert(ctx.init.sid, pk);
+        let mut actions = vec![action];
+        actions.extend(self.advance(ctx)?);
+        Ok(actions)
+    }
+
+    fn handle_round(&mut self, ctx: &mut PartyContext, from: usize, round: u32, payload: Value) -> Result<Vec<Action>, ProtocolError> {
+        // rounds of other parties may come one ahead of this party's, never after it moved past them
+        let late = round < self.round || self.round == DONE;
+        match round {
+            // trusted as relayed, see the caveat on ID
+            KEYS if !late && !self.keys.contains_key(&from) => {
+                let pk: Point<Secp256k1> = decode_payload(payload)?;
+                channel::check_point(&pk)?;
+                self.keys.insert(from, pk);
+            }
+            _ if round != KEYS && !late && !self.received.get(&round).is_some_and(|shares| shares.contains_key(&from)) => {
+                let expected = round_len(ctx.init, round).ok_or_else(|| unexpected_round(ID, round))?;
+                let pk = self.keys.get(&from).ok_or(ValidationError::InvalidSender { from })?;
+                let shares: Vec<BigInt> = channel::open(&self.sk, pk, ctx.init.computation_id, from, ctx.init.sid, decode_payload(payload)?)?;
+                if shares.len() != expected {
+                    return Err(ValidationError::WrongLength { expected, actual: shares.len() }.into());
+                }
+                let field = Field::secp256k1();
+                if !shares.iter().all(|share| field.contains(share)) {
+                    return Err(ValidationError::CiphertextOutOfRange.into());
+                }
+                self.received.entry(round).or_default().insert(from, shares);
+            }
+            _ => return Err(unexpected_round(ID, round)),
+        }
+        self.advance(ctx)
+    }
+}
@@ -0,0 +1,76 @@
+//! Private channels between the parties of a computation, over a relay that sees every message.
+//!
+//! Every party broadcasts a fresh secp256k1 public key for the computation, and two parties encrypt what they
+//! unicast each other under a key derived from their Diffie-Hellman point: XChaCha20-Poly1305 over a random nonce,
+//! with the computation, the sender and the recipient authenticated along with the payload.
+use chacha20poly1305::aead::{AeadInPlace, KeyInit};
+use chacha20poly1305::{Tag, XChaCha20Poly1305, XNonce};
+use curv::elliptic::curves::{Point, Scalar, Secp256k1};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::errors::{ProtocolError, ValidationError};
+use crate::vault;
+
+// separates the key derived from the agreed point from any other
+const SEAL_KEY_DOMAIN: &[u8] = b"smpc channel seal key";
+const NONCE_BYTES: usize = 24;
+const TAG_BYTES: usize = 16;
+
+/// A payload encrypted for its recipient, binary fields in hex.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sealed {
+    nonce: String,
+    ciphertext: String,
+    tag: String,
+}
+
+/// A key derived from the Diffie-Hellman point of `sk` and `pk`, separated from the others by `domain`.
+pub fn agree(sk: &Scalar<Secp256k1>, pk: &Point<Secp256k1>, domain: &[u8]) -> Zeroizing<[u8; 32]> {
+    let point = pk * sk;
+    let mut hasher = Sha256::new();
+    hasher.update(domain);
+    hasher.update(point.to_bytes(true));
+    Zeroizing::new(hasher.finalize().into())
+}
+
+/// Refuse the point at infinity as a public key, which would agree on a key anyone knows.
+pub fn check_point(point: &Point<Secp256k1>) -> Result<(), ValidationError> {
+    if point.is_zero() {
+        return Err(ValidationError::InconsistentKey("the point at infinity".to_string()));
+    }
+    Ok(())
+}
+
+/// Encrypt `payload` from party `from`, holding `sk`, to party `to`, holding the secret key of `pk`.
+pub fn seal<T: Serialize>(sk: &Scalar<Secp256k1>, pk: &Point<Secp256k1>, computation_id: usize, from: usize, to: usize, payload: &T) -> Result<Sealed, ProtocolError> {
+    let key = agree(sk, pk, SEAL_KEY_DOMAIN);
+    let mut ciphertext = Zeroizing::new(serde_json::to_vec(payload).map_err(|e| ProtocolError::MalformedMessage(e.to_string()))?);
+    let nonce = vault::random_bytes::<NONCE_BYTES>();
+    let tag = XChaCha20Poly1305::new(key.as_ref().into())
+        .encrypt_in_place_detached(XNonce::from_slice(&nonce), &associated_data(computation_id, from, to), ciphertext.as_mut())
+        .map_err(|_| ProtocolError::MalformedMessage("payload too large to seal".to_string()))?;
+    Ok(Sealed { nonce: hex::encode(nonce), ciphertext: hex::encode(&ciphertext), tag: hex::encode(tag) })
+}
+
+/// Decrypt a payload sealed by party `from`, holding the secret key of `pk`, for party `to`, holding `sk`.
+pub fn open<T: DeserializeOwned>(sk: &Scalar<Secp256k1>, pk: &Point<Secp256k1>, computation_id: usize, from: usize, to: usize, sealed: Sealed) -> Result<T, ProtocolError> {
+    let key = agree(sk, pk, SEAL_KEY_DOMAIN);
+    let decode = |field: &str| hex::decode(field).map_err(|e| ProtocolError::MalformedMessage(e.to_string()));
+    let (nonce, ciphertext, tag) = (decode(&sealed.nonce)?, decode(&sealed.ciphertext)?, decode(&sealed.tag)?);
+    if nonce.len() != NONCE_BYTES || tag.len() != TAG_BYTES {
+        return Err(ProtocolError::MalformedMessage("nonce or tag of the wrong size".to_string()));
+    }
+    let mut plaintext = Zeroizing::new(ciphertext);
+    XChaCha20Poly1305::new(key.as_ref().into())
+        .decrypt_in_place_detached(XNonce::from_slice(&nonce), &associated_data(computation_id, from, to), plaintext.as_mut(), Tag::from_slice(&tag))
+        .map_err(|_| ValidationError::Unauthenticated)?;
+    serde_json::from_slice(&plaintext).map_err(|e| ProtocolError::MalformedMessage(e.to_string()))
+}
+
+/// What a sealed message is bound to: its computation, sender and recipient.
+fn associated_data(computation_id: usize, from: usize, to: usize) -> Vec<u8> {
+    [computation_id, from, to].iter().flat_map(|field| (*field as u64).to_be_bytes()).collect()
+}
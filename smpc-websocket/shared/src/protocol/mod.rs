@@ -0,0 +1,532 @@
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use kzen_paillier::BigInt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::encoding::{FixedPoint, FixedPointEncoder};
+use crate::errors::{ProtocolError, ValidationError};
+use crate::homomorphic::{AdditiveHomomorphic, SchemeKind};
+use crate::secret::Secret;
+use crate::share_store::{ShareStore, SharedValue};
+use crate::types::InitializeProtocol;
+use crate::utils::check_input_bound;
+
+pub mod add;
+pub mod beaver;
+pub mod bgw;
+pub mod channel;
+pub mod multiply;
+pub mod product;
+pub mod random;
+pub mod reshare;
+pub mod secagg;
+pub mod sum;
+
+/// A computation run by the clients, one instance per computation id.
+///
+/// The client creates the instance from the registry when the server announces the computation, calls
+/// `start` once and then `handle_round` for every round message of the computation addressed to it, along with
+/// the sid of the party the server received it from. The protocol answers with the actions the client should perform; it never touches the network itself. A party
+/// and its protocols may move between threads, e.g. to be kept while its connection is down.
+pub trait Protocol: Send {
+    fn start(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError>;
+
+    fn handle_round(&mut self, ctx: &mut PartyContext, from: usize, round: u32, payload: Value) -> Result<Vec<Action>, ProtocolError>;
+
+    /// Number of parties that must finish for the computation to have a result when the others drop out, None
+    /// if it needs every party. The server only lets a client drop out while at least this many remain.
+    fn threshold(&self) -> Option<usize> {
+        None
+    }
+
+    /// Party `sid` dropped out of the session and will send nothing more.
+    fn dropped(&mut self, _ctx: &mut PartyContext, sid: usize) -> Result<Vec<Action>, ProtocolError> {
+        Err(ProtocolError::PartyDropped(sid))
+    }
+}
+
+/// What a protocol asks the client to do after a step.
+#[derive(Debug)]
+pub enum Action {
+    // unicast a round message to another party
+    Send { to: usize, round: u32, payload: Value },
+    // send a round message to every other party
+    Broadcast { round: u32, payload: Value },
+    // this party's shares of the result, stored under the computation id and then revealed
+    Output(SharedValue),
+    // the result itself, which every party that finishes the computation learns: there is nothing to reveal,
+    // and no shares to compose with later computations
+    Public(SharedValue),
+    // the shares of an earlier computation were used up and must never be used again, e.g. a Beaver triple:
+    // the party wipes them
+    Consume(usize),
+}
+
+impl Action {
+    pub fn send<T: Serialize>(to: usize, round: u32, payload: &T) -> Result<Action, ProtocolError> {
+        Ok(Action::Send { to, round, payload: encode_payload(payload)? })
+    }
+
+    pub fn broadcast<T: Serialize>(round: u32, payload: &T) -> Result<Action, ProtocolError> {
+        Ok(Action::Broadcast { round, payload: encode_payload(payload)? })
+    }
+}
+
+/// Where a party's private inputs come from.
+pub trait InputSource: Send {
+    /// The private input vector at `index`, parsed with the session scale, wiped once dropped.
+    fn private_input(&self, index: usize, scale: u32) -> Result<Secret<Vec<FixedPoint>>, ProtocolError>;
+}
+
+impl<T: InputSource + Sync + ?Sized> InputSource for &T {
+    fn private_input(&self, index: usize, scale: u32) -> Result<Secret<Vec<FixedPoint>>, ProtocolError> {
+        (**self).private_input(index, scale)
+    }
+}
+
+/// Private input vectors of one party, as decimal strings, zeroized when dropped.
+#[derive(Debug, Clone, Default)]
+pub struct PrivateInputs(pub Vec<Vec<String>>);
+
+impl PrivateInputs {
+    pub fn new(inputs: &[&[&str]]) -> Self {
+        PrivateInputs(inputs.iter().map(|input| input.iter().map(|x| x.to_string()).collect()).collect())
+    }
+
+    /// One input vector per line, its elements separated by commas. Blank lines are skipped.
+    pub fn parse(text: &str) -> Self {
+        let lines = text.lines().filter(|line| !line.trim().is_empty());
+        PrivateInputs(lines.map(|line| line.split(',').map(|x| x.trim().to_string()).collect()).collect())
+    }
+
+    /// Parse a file of input vectors, wiping its text once parsed.
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        let text = Zeroizing::new(fs::read_to_string(path)?);
+        Ok(PrivateInputs::parse(&text))
+    }
+}
+
+impl InputSource for PrivateInputs {
+    fn private_input(&self, index: usize, scale: u32) -> Result<Secret<Vec<FixedPoint>>, ProtocolError> {
+        let input = self.0.get(index).ok_or(ProtocolError::MissingInput(index))?;
+        let encoder = FixedPointEncoder::new(scale);
+        Ok(Secret::new(input.iter().map(|x| encoder.parse(x)).collect::<Result<_, _>>()?))
+    }
+}
+
+impl Drop for PrivateInputs {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Keys of the first client, one pair per scheme. They are generated by the first computation that needs them,
+/// unless a key pair of the right size was kept from an earlier session, and reused by the following ones so
+/// that all the shares of a session live in the same plaintext space. The other parties keep the first public
+/// key they received for each scheme and refuse any other.
+#[derive(Default)]
+pub struct PartyKeys {
+    // key pairs by scheme, their secret keys wiped when dropped
+    keys: HashMap<TypeId, Box<dyn Any + Send>>,
+    // public keys received from the first client, for the parties that do not hold the key pair
+    received: HashMap<TypeId, Box<dyn Any + Send>>,
+    // key pairs kept from earlier sessions, as JSON, to use instead of generating new ones
+    stored: HashMap<SchemeKind, Value>,
+    // key pairs generated in this session, as JSON, if they are to be kept
+    generated: Option<Vec<(SchemeKind, usize, Value)>>,
+    // every key pair as JSON, in the order they were generated, for test vectors
+    #[cfg(feature = "seeded-rng")]
+    exported: Vec<Value>,
+}
+
+impl PartyKeys {
+    fn get<S: AdditiveHomomorphic>(&self) -> Option<&(S::PublicKey, Secret<S::SecretKey>)> {
+        self.keys.get(&TypeId::of::<S>()).and_then(|keys| keys.downcast_ref())
+    }
+
+    pub fn session_keys<S: AdditiveHomomorphic>(&mut self, security_bits: usize) -> (S::PublicKey, Secret<S::SecretKey>) {
+        if let Some(keys) = self.get::<S>() {
+            return keys.clone();
+        }
+        if let Some(keys) = self.stored.remove(&S::KIND).and_then(|keys| Self::restore::<S>(keys, security_bits)) {
+            self.keys.insert(TypeId::of::<S>(), Box::new(keys.clone()));
+            return keys;
+        }
+        let (pk, sk) = S::keypair(security_bits);
+        let keys = (pk, Secret::new(sk));
+        if let Some(generated) = &mut self.generated {
+            generated.push((S::KIND, security_bits, serde_json::json!({ "public": keys.0, "secret": *keys.1 })));
+        }
+        #[cfg(feature = "seeded-rng")]
+        self.exported.push(serde_json::json!({ "public": keys.0, "secret": *keys.1 }));
+        self.keys.insert(TypeId::of::<S>(), Box::new(keys.clone()));
+        keys
+    }
+
+    /// A key pair kept from an earlier session, if it is well formed and of the size the session asks for.
+    fn restore<S: AdditiveHomomorphic>(keys: Value, security_bits: usize) -> Option<(S::PublicKey, Secret<S::SecretKey>)> {
+        let public = serde_json::from_value(keys.get("public")?.clone()).ok()?;
+        let secret = Secret::new(serde_json::from_value(keys.get("secret")?.clone()).ok()?);
+        S::validate_public_key(&public, security_bits).ok()?;
+        Some((public, secret))
+    }
+
+    /// Use this key pair, kept from an earlier session, if the session runs on its scheme.
+    pub fn offer(&mut self, scheme: SchemeKind, keys: Value) {
+        self.stored.insert(scheme, keys);
+    }
+
+    /// Keep every key pair generated from now on, to be taken with `take_generated`.
+    pub fn keep_generated(&mut self) {
+        self.generated.get_or_insert_with(Vec::new);
+    }
+
+    /// Key pairs generated since the last call, with their scheme and key size.
+    pub fn take_generated(&mut self) -> Vec<(SchemeKind, usize, Value)> {
+        self.generated.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Every key pair generated so far, secret keys included.
+    #[cfg(feature = "seeded-rng")]
+    pub fn exported(&self) -> &[Value] {
+        &self.exported
+    }
+
+    /// Check that a public key received in a message is the session's: this party's own if it generated one,
+    /// otherwise the first one it received.
+    pub fn pin_public_key<S: AdditiveHomomorphic>(&mut self, pk: &S::PublicKey) -> Result<(), ValidationError> {
+        let known = match self.get::<S>() {
+            Some((own, _)) => own,
+            None => {
+                let received = self.received.entry(TypeId::of::<S>()).or_insert_with(|| Box::new(pk.clone()));
+                received.downcast_ref::<S::PublicKey>().ok_or(ValidationError::KeyChanged)?
+            }
+        };
+        if known != pk {
+            return Err(ValidationError::KeyChanged);
+        }
+        Ok(())
+    }
+
+    pub fn secret_key<S: AdditiveHomomorphic>(&self) -> Result<&S::SecretKey, ProtocolError> {
+        self.get::<S>().map(|(_, sk)| &**sk).ok_or(ProtocolError::MissingKey)
+    }
+
+    /// The public key, provided shares mod `modulus` were computed under it.
+    pub fn public_key_for<S: AdditiveHomomorphic>(&self, modulus: &BigInt) -> Result<&S::PublicKey, ProtocolError> {
+        self.get::<S>()
+            .map(|(pk, _)| pk)
+            .filter(|pk| S::plaintext_modulus(pk) == *modulus)
+            .ok_or(ProtocolError::MissingKey)
+    }
+}
+
+/// Everything a protocol may read or update on the party running it.
+pub struct PartyContext<'a> {
+    pub init: &'a InitializeProtocol,
+    pub shares: &'a ShareStore,
+    pub keys: &'a mut PartyKeys,
+    pub inputs: &'a dyn InputSource,
+    // parties that dropped out of the session so far
+    pub dropped: &'a HashSet<usize>,
+}
+
+impl PartyContext<'_> {
+    pub fn is_last(&self) -> bool {
+        self.init.sid == self.init.num_parties - 1
+    }
+
+    /// Check the first client's public key as received in a message, before computing on anything under it.
+    pub fn check_public_key<S: AdditiveHomomorphic>(&mut self, pk: &S::PublicKey) -> Result<(), ProtocolError> {
+        S::validate_public_key(pk, self.init.bits_security)?;
+        self.keys.pin_public_key::<S>(pk)?;
+        Ok(())
+    }
+
+    /// Parse the private input vector at `index`, check every element against the session bound and map them
+    /// into Z_n.
+    pub fn encoded_input(&self, index: usize, n: &BigInt) -> Result<Secret<Vec<BigInt>>, ProtocolError> {
+        let values = self.inputs.private_input(index, self.init.scale)?;
+        if values.len() != self.init.vector_len {
+            return Err(ProtocolError::VectorLengthMismatch { expected: self.init.vector_len, actual: values.len() });
+        }
+        println!("Using private input {} ({} elements) in computation {}", index, values.len(), self.init.computation_id);
+        values.iter().map(|x| {
+            check_input_bound(&x.mantissa, self.init.max_input_bits)?;
+            Ok(x.encode(n)?)
+        }).collect::<Result<_, _>>().map(Secret::new)
+    }
+}
+
+/// Builds the protocol instance of a computation, validating its parameters.
+pub type Constructor = fn(&InitializeProtocol) -> Result<Box<dyn Protocol>, ProtocolError>;
+
+/// Protocols keyed by the id used in `InitializeProtocol::protocol`.
+pub struct Registry {
+    constructors: HashMap<&'static str, Constructor>,
+}
+
+impl Registry {
+    pub fn register(&mut self, id: &'static str, constructor: Constructor) {
+        self.constructors.insert(id, constructor);
+    }
+
+    pub fn create(&self, init: &InitializeProtocol) -> Result<Box<dyn Protocol>, ProtocolError> {
+        let constructor = self
+            .constructors
+            .get(init.protocol.as_str())
+            .ok_or_else(|| ProtocolError::UnknownProtocol(init.protocol.clone()))?;
+        constructor(init)
+    }
+}
+
+impl Default for Registry {
+    /// Registry with every protocol of this crate.
+    fn default() -> Self {
+        let mut registry = Registry { constructors: HashMap::new() };
+        registry.register(product::ID, product::create);
+        registry.register(sum::ID, sum::create);
+        registry.register(multiply::ID, multiply::create);
+        registry.register(add::ID, add::create);
+        registry.register(secagg::ID, secagg::create);
+        registry.register(bgw::ID, bgw::create);
+        registry.register(random::ID, random::create);
+        registry.register(beaver::ID, beaver::create);
+        registry
+    }
+}
+
+pub fn encode_payload<T: Serialize>(payload: &T) -> Result<Value, ProtocolError> {
+    serde_json::to_value(payload).map_err(|e| ProtocolError::MalformedMessage(e.to_string()))
+}
+
+pub fn decode_payload<T: DeserializeOwned>(payload: Value) -> Result<T, ProtocolError> {
+    serde_json::from_value(payload).map_err(|e| ProtocolError::MalformedMessage(e.to_string()))
+}
+
+pub fn unexpected_round(protocol: &str, round: u32) -> ProtocolError {
+    ProtocolError::UnexpectedRound { protocol: protocol.to_string(), round }
+}
+
+pub fn unsupported_scheme(protocol: &str, scheme: SchemeKind) -> ProtocolError {
+    ProtocolError::UnsupportedScheme { protocol: protocol.to_string(), scheme: scheme.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoding::FixedPointEncoder;
+    use crate::profile::SecurityProfile;
+    use crate::types::{Computation, RevealMode};
+    use curv::arithmetic::Modulo;
+    use std::collections::VecDeque;
+
+    struct TestInputs(Vec<Vec<&'static str>>);
+
+    impl InputSource for TestInputs {
+        fn private_input(&self, index: usize, scale: u32) -> Result<Secret<Vec<FixedPoint>>, ProtocolError> {
+            let input = self.0.get(index).ok_or(ProtocolError::MissingInput(index))?;
+            let encoder = FixedPointEncoder::new(scale);
+            Ok(Secret::new(input.iter().map(|x| encoder.parse(x)).collect::<Result<_, _>>()?))
+        }
+    }
+
+    #[derive(Default)]
+    struct Party {
+        keys: PartyKeys,
+        shares: ShareStore,
+    }
+
+    fn init(computation: &Computation, scheme: SchemeKind, sid: usize, num_parties: usize) -> InitializeProtocol {
+        InitializeProtocol {
+            bits_security: 512,
+            profile: SecurityProfile::Toy512,
+            num_parties,
+            sid,
+            computation_id: computation.id,
+            protocol: computation.protocol.clone(),
+            params: computation.params.clone(),
+            scheme,
+            max_input_bits: 16,
+            scale: 1,
+            vector_len: 2,
+            reveal: computation.reveal,
+            server_key: None,
+            session_id: String::new(),
+            resume_token: String::new(),
+        }
+    }
+
+    /// Run a computation between the parties, delivering messages in order, and return the decoded result.
+    fn run(registry: &Registry, scheme: SchemeKind, parties: &mut [Party], inputs: &[TestInputs], computation: &Computation) -> Vec<String> {
+        let num_parties = parties.len();
+        let inits: Vec<InitializeProtocol> = (0..num_parties).map(|sid| init(computation, scheme, sid, num_parties)).collect();
+        let mut protocols: Vec<Box<dyn Protocol>> = inits.iter().map(|i| registry.create(i).unwrap()).collect();
+        let mut queue = VecDeque::new();
+        for sid in 0..num_parties {
+            queue.push_back((sid, None));
+        }
+        while let Some((sid, message)) = queue.pop_front() {
+            let party = &mut parties[sid];
+            let dropped = HashSet::new();
+            let mut ctx = PartyContext { init: &inits[sid], shares: &party.shares, keys: &mut party.keys, inputs: &inputs[sid], dropped: &dropped };
+            let actions = match message {
+                None => protocols[sid].start(&mut ctx),
+                Some((from, round, payload)) => protocols[sid].handle_round(&mut ctx, from, round, payload),
+            };
+            for action in actions.unwrap() {
+                match action {
+                    Action::Send { to, round, payload } => queue.push_back((to, Some((sid, round, payload)))),
+                    Action::Broadcast { round, payload } => {
+                        for to in (0..num_parties).filter(|&to| to != sid) {
+                            queue.push_back((to, Some((sid, round, payload.clone()))));
+                        }
+                    }
+                    Action::Public(_) => unreachable!(),
+                    Action::Output(value) => parties[sid].shares.insert(computation.id, value),
+                    Action::Consume(id) => {
+                        parties[sid].shares.remove(id);
+                    }
+                }
+            }
+        }
+        let values: Vec<&SharedValue> = parties.iter().map(|p| p.shares.get(computation.id).unwrap()).collect();
+        let n = &values[0].n;
+        (0..values[0].shares.len())
+            .map(|k| {
+                let sum = values.iter().fold(BigInt::from(0), |acc, v| BigInt::mod_add(&acc, &v.shares[k], n));
+                FixedPoint::decode(&sum, n, values[0].scale).to_string()
+            })
+            .collect()
+    }
+
+    fn test_inputs() -> Vec<TestInputs> {
+        vec![
+            TestInputs(vec![vec!["1.5", "-2"], vec!["10", "0.1"]]),
+            TestInputs(vec![vec!["2", "3"], vec!["-4", "0.2"]]),
+            TestInputs(vec![vec!["-1", "0.5"], vec!["1.5", "0.3"]]),
+        ]
+    }
+
+    #[test]
+    fn test_builtin_protocols() {
+        let registry = Registry::default();
+        let inputs = test_inputs();
+        let private = RevealMode::Private;
+        let schemes = [
+            SchemeKind::Paillier,
+            SchemeKind::DamgardJurik,
+            #[cfg(feature = "insecure-mock")]
+            SchemeKind::InsecureMock,
+        ];
+        for scheme in schemes {
+            let mut parties: Vec<Party> = (0..inputs.len()).map(|_| Party::default()).collect();
+            let mut compute = |computation| run(&registry, scheme, &mut parties, &inputs, &computation);
+
+            assert_eq!(compute(product::computation(0, 0, private)), ["-3.000", "-3.000"]);
+            assert_eq!(compute(sum::computation(1, 1, private)), ["7.5", "0.6"]);
+            assert_eq!(compute(sum::computation(2, 0, private)), ["2.5", "1.5"]);
+            assert_eq!(compute(multiply::computation(3, 1, 2, private)), ["18.75", "0.90"]);
+            assert_eq!(compute(add::computation(4, 1, 2, private)), ["10.0", "2.1"]);
+            assert_eq!(compute(bgw::computation(5, 0, private)), ["-3.000", "-3.000"]);
+            let (preprocessing, triple) = beaver::preprocessing(10);
+            for computation in preprocessing {
+                compute(computation);
+            }
+            assert_eq!(compute(beaver::computation(6, 1, 2, triple, private)), ["18.75", "0.90"]);
+            // the triple was wiped once used
+            assert!(parties.iter().all(|party| party.shares.get(10).is_err()));
+        }
+    }
+
+    #[test]
+    fn test_ec_elgamal_sum() {
+        let registry = Registry::default();
+        let inputs = test_inputs();
+        let mut parties: Vec<Party> = (0..inputs.len()).map(|_| Party::default()).collect();
+        let computation = sum::computation(0, 1, RevealMode::Public);
+        assert_eq!(run(&registry, SchemeKind::EcElGamal, &mut parties, &inputs, &computation), ["7.5", "0.6"]);
+
+        // uniform masks cannot be decrypted
+        let computation = sum::computation(1, 1, RevealMode::Private);
+        assert!(matches!(registry.create(&init(&computation, SchemeKind::EcElGamal, 0, 3)), Err(ProtocolError::UnsupportedScheme { .. })));
+        let computation = product::computation(1, 0, RevealMode::Public);
+        assert!(matches!(registry.create(&init(&computation, SchemeKind::EcElGamal, 0, 3)), Err(ProtocolError::UnsupportedScheme { .. })));
+    }
+
+    #[cfg(feature = "insecure-mock")]
+    #[test]
+    fn test_many_parties_insecure_mock() {
+        let registry = Registry::default();
+        let inputs: Vec<TestInputs> = (0..50).map(|_| TestInputs(vec![vec!["1", "-0.5"]])).collect();
+        let mut parties: Vec<Party> = (0..inputs.len()).map(|_| Party::default()).collect();
+        let mut compute = |computation| run(&registry, SchemeKind::InsecureMock, &mut parties, &inputs, &computation);
+
+        assert_eq!(compute(sum::computation(0, 0, RevealMode::Private)), ["50.0", "-25.0"]);
+        assert_eq!(compute(multiply::computation(1, 0, 0, RevealMode::Private)), ["2500.00", "625.00"]);
+    }
+
+    #[test]
+    fn test_chain_rounds_come_from_a_neighbour_once() {
+        let registry = Registry::default();
+        let inputs = test_inputs();
+        let (mut first_party, mut second_party) = (Party::default(), Party::default());
+        let dropped = HashSet::new();
+        let computation = product::computation(0, 0, RevealMode::Private);
+        let inits: Vec<InitializeProtocol> = (0..3).map(|sid| init(&computation, SchemeKind::Paillier, sid, 3)).collect();
+        let mut first = registry.create(&inits[0]).unwrap();
+        let mut ctx = PartyContext { init: &inits[0], shares: &first_party.shares, keys: &mut first_party.keys, inputs: &inputs[0], dropped: &dropped };
+        let actions = first.start(&mut ctx).unwrap();
+        let [Action::Send { round, payload, .. }] = &actions[..] else {
+            panic!("party 0 did not start the chain");
+        };
+        let (round, payload) = (*round, payload.clone());
+
+        let mut second = registry.create(&inits[1]).unwrap();
+        let mut ctx = PartyContext { init: &inits[1], shares: &second_party.shares, keys: &mut second_party.keys, inputs: &inputs[1], dropped: &dropped };
+        // the forward round only comes from the previous party, the reshare round only from the next one
+        assert_eq!(second.handle_round(&mut ctx, 2, round, payload.clone()).err(), Some(ValidationError::InvalidSender { from: 2 }.into()));
+        assert_eq!(second.handle_round(&mut ctx, 0, 1, Value::Null).err(), Some(ValidationError::InvalidSender { from: 0 }.into()));
+        assert!(second.handle_round(&mut ctx, 0, round, payload.clone()).is_ok());
+        assert_eq!(second.handle_round(&mut ctx, 0, round, payload).err(), Some(unexpected_round(product::ID, round)));
+
+        // the backward round of a multiplication comes back from the next party, not the previous one
+        let computation = multiply::computation(1, 0, 0, RevealMode::Private);
+        let middle_init = init(&computation, SchemeKind::Paillier, 1, 3);
+        let mut middle = registry.create(&middle_init).unwrap();
+        let mut party = Party::default();
+        let mut ctx = PartyContext { init: &middle_init, shares: &party.shares, keys: &mut party.keys, inputs: &inputs[1], dropped: &dropped };
+        assert_eq!(middle.handle_round(&mut ctx, 0, 1, Value::Null).err(), Some(ValidationError::InvalidSender { from: 0 }.into()));
+        assert_eq!(middle.handle_round(&mut ctx, 2, 0, Value::Null).err(), Some(ValidationError::InvalidSender { from: 2 }.into()));
+    }
+
+    #[test]
+    fn test_parse_inputs() {
+        let inputs = PrivateInputs::parse("1.5, -2\n\n 37 \n");
+        assert_eq!(inputs.0, [vec!["1.5", "-2"], vec!["37"]]);
+        assert_eq!(inputs.private_input(2, 0).unwrap_err(), ProtocolError::MissingInput(2));
+    }
+
+    #[test]
+    fn test_unknown_protocol() {
+        let mut computation = product::computation(0, 0, RevealMode::Public);
+        computation.protocol = "division".to_string();
+        let result = Registry::default().create(&init(&computation, SchemeKind::Paillier, 0, 2));
+        assert_eq!(result.err(), Some(ProtocolError::UnknownProtocol("division".to_string())));
+
+        let computation = Computation { params: serde_json::json!({ "lhs": 0 }), ..multiply::computation(0, 0, 1, RevealMode::Public) };
+        assert!(matches!(Registry::default().create(&init(&computation, SchemeKind::Paillier, 0, 2)), Err(ProtocolError::MalformedMessage(_))));
+
+        // two parties cannot have an honest majority
+        let computation = bgw::computation(0, 0, RevealMode::Public);
+        let result = Registry::default().create(&init(&computation, SchemeKind::Paillier, 0, 2));
+        assert_eq!(result.err(), Some(ProtocolError::InvalidThreshold { threshold: 1, num_parties: 2 }));
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+use std::marker::PhantomData;
+
+use curv::arithmetic::traits::{BitManipulation, Modulo};
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::encoding::check_signed_result_bound;
+use crate::errors::{ProtocolError, ValidationError};
+use crate::homomorphic::{sample_masks, AdditiveHomomorphic, DamgardJurikScheme, PaillierScheme, SchemeKind};
+#[cfg(feature = "insecure-mock")]
+use crate::homomorphic::InsecureMockScheme;
+use crate::protocol::{decode_payload, unexpected_round, unsupported_scheme, Action, PartyContext, Protocol};
+use crate::share_store::SharedValue;
+use crate::types::{Computation, InitializeProtocol, RevealMode};
+use crate::validation::check_ciphertexts;
+
+/// Share-in/share-out multiplication [c] = [a] * [b] of the results of two earlier computations.
+///
+/// With shares a_j and b_j, the first client sends E(a_0) and E(b_0) forward and every following client
+/// adds its E(b_j) into E(b). The last client starts the backward chain from E(0): every client j >= 1
+/// adds a_j * E(b) + b_j * E(a_0) - E(r_j) into the running value and keeps r_j as its share. The first
+/// client decrypts and adds a_0 * b_0, so the shares sum to a_0 b_0 + sum over j >= 1 of (a_j b + a_0 b_j) = a b.
+pub const ID: &str = "multiply";
+
+const FORWARD: u32 = 0;
+const BACKWARD: u32 = 1;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Params {
+    pub lhs: usize,
+    pub rhs: usize,
+    // the product is only wanted mod n, e.g. of uniformly random shares, so it may wrap around
+    #[serde(default)]
+    pub modular: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+struct Forward<S: AdditiveHomomorphic> {
+    pk: S::PublicKey,
+    // E(a_0), element-wise
+    lhs_first: Vec<S::Ciphertext>,
+    // E(b_0 + ... + b_sid-1), element-wise
+    rhs_sum: Vec<S::Ciphertext>,
+}
+
+// E(a_0) and E(b) are stripped before the message reaches the first client, which could otherwise decrypt b.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+struct Backward<S: AdditiveHomomorphic> {
+    pk: S::PublicKey,
+    lhs_first: Option<Vec<S::Ciphertext>>,
+    rhs_sum: Option<Vec<S::Ciphertext>>,
+    computed_values: Vec<S::Ciphertext>,
+}
+
+pub struct Multiply<S> {
+    params: Params,
+    // rounds this party already handled, each of which reaches it once
+    handled: HashSet<u32>,
+    scheme: PhantomData<S>,
+}
+
+/// The first client decrypts values masked with uniform shares, so the scheme must decrypt its whole
+/// plaintext space.
+pub fn create(init: &InitializeProtocol) -> Result<Box<dyn Protocol>, ProtocolError> {
+    let params = decode_payload(init.params.clone())?;
+    match init.scheme {
+        SchemeKind::Paillier => Ok(Box::new(Multiply::<PaillierScheme> { params, handled: HashSet::new(), scheme: PhantomData })),
+        SchemeKind::DamgardJurik => Ok(Box::new(Multiply::<DamgardJurikScheme> { params, handled: HashSet::new(), scheme: PhantomData })),
+        SchemeKind::EcElGamal => Err(unsupported_scheme(ID, init.scheme)),
+        #[cfg(feature = "insecure-mock")]
+        SchemeKind::InsecureMock => Ok(Box::new(Multiply::<InsecureMockScheme> { params, handled: HashSet::new(), scheme: PhantomData })),
+    }
+}
+
+pub fn computation(id: usize, lhs: usize, rhs: usize, reveal: RevealMode) -> Computation {
+    Computation { id, protocol: ID.to_string(), params: json!({ "lhs": lhs, "rhs": rhs }), reveal }
+}
+
+impl<S: AdditiveHomomorphic> Multiply<S> {
+    /// Shares of [a] * [b] without the shares themselves. The value is tracked through scale and bit length so
+    /// that the first client can refuse a multiplication that could wrap around n.
+    fn result(&self, ctx: &PartyContext, shares: Vec<BigInt>) -> Result<SharedValue, ProtocolError> {
+        let (a, b) = ctx.shares.operands(self.params.lhs, self.params.rhs)?;
+        Ok(SharedValue {
+            n: a.n.clone(),
+            shares,
+            scale: a.scale + b.scale,
+            value_bits: if self.params.modular { a.n.bit_length() } else { a.value_bits + b.value_bits },
+        })
+    }
+
+    fn forward(&self, ctx: &mut PartyContext, data: Forward<S>) -> Result<Vec<Action>, ProtocolError> {
+        ctx.check_public_key::<S>(&data.pk)?;
+        let (_, b) = ctx.shares.operands(self.params.lhs, self.params.rhs)?;
+        check_ciphertexts::<S>(&data.pk, &data.lhs_first, b.shares.len())?;
+        check_ciphertexts::<S>(&data.pk, &data.rhs_sum, b.shares.len())?;
+        let pk = data.pk;
+        let rhs_sum: Vec<S::Ciphertext> = data.rhs_sum.iter().zip(&b.shares).map(|(ct, b_j)| {
+            S::add(&pk, ct, &S::encrypt(&pk, b_j))
+        }).collect();
+        if ctx.is_last() {
+            // the last client now holds E(b) and starts the backward chain from E(0)
+            let start = (0..rhs_sum.len()).map(|_| S::encrypt(&pk, &BigInt::from(0))).collect();
+            return self.backward_step(ctx, pk, data.lhs_first, rhs_sum, start);
+        }
+        let next = Forward::<S> { pk, lhs_first: data.lhs_first, rhs_sum };
+        Ok(vec![Action::send(ctx.init.sid + 1, FORWARD, &next)?])
+    }
+
+    fn backward(&self, ctx: &mut PartyContext, data: Backward<S>) -> Result<Vec<Action>, ProtocolError> {
+        ctx.check_public_key::<S>(&data.pk)?;
+        let len = ctx.shares.operands(self.params.lhs, self.params.rhs)?.0.shares.len();
+        check_ciphertexts::<S>(&data.pk, &data.computed_values, len)?;
+        if ctx.init.sid == 0 {
+            // share_0 = D(sum over j >= 1 of a_j b + a_0 b_j - r_j) + a_0 b_0
+            let (a, b) = ctx.shares.operands(self.params.lhs, self.params.rhs)?;
+            let sk = ctx.keys.secret_key::<S>()?;
+            let shares = data.computed_values.iter().zip(a.shares.iter().zip(&b.shares)).map(|(ct, (a_0, b_0))| {
+                Ok(BigInt::mod_add(&S::decrypt(&data.pk, sk, ct)?, &BigInt::mod_mul(a_0, b_0, &a.n), &a.n))
+            }).collect::<Result<_, ProtocolError>>()?;
+            return Ok(vec![Action::Output(self.result(ctx, shares)?)]);
+        }
+        let (Some(lhs_first), Some(rhs_sum)) = (data.lhs_first, data.rhs_sum) else {
+            return Err(ProtocolError::MalformedMessage("backward message without E(a_0) or E(b)".to_string()));
+        };
+        check_ciphertexts::<S>(&data.pk, &lhs_first, len)?;
+        check_ciphertexts::<S>(&data.pk, &rhs_sum, len)?;
+        self.backward_step(ctx, data.pk, lhs_first, rhs_sum, data.computed_values)
+    }
+
+    /// Add a_j * E(b) + b_j * E(a_0) - E(r_j) into the running value, keep r_j as this client's share and pass
+    /// the result on to the previous client.
+    fn backward_step(&self, ctx: &PartyContext, pk: S::PublicKey, lhs_first: Vec<S::Ciphertext>, rhs_sum: Vec<S::Ciphertext>, computed_values: Vec<S::Ciphertext>) -> Result<Vec<Action>, ProtocolError> {
+        let (a, b) = ctx.shares.operands(self.params.lhs, self.params.rhs)?;
+        let (masks, shares) = sample_masks::<S>(&pk, computed_values.len());
+        let new_values: Vec<S::Ciphertext> = (0..computed_values.len()).map(|k| {
+            let terms = [
+                S::scalar_mul(&pk, &rhs_sum[k], &a.shares[k]),
+                S::scalar_mul(&pk, &lhs_first[k], &b.shares[k]),
+                masks[k].clone(),
+            ];
+            terms.iter().fold(computed_values[k].clone(), |acc, t| S::add(&pk, &acc, t))
+        }).collect();
+
+        let to_first = ctx.init.sid == 1;
+        let next = Backward::<S> {
+            pk,
+            lhs_first: if to_first { None } else { Some(lhs_first) },
+            rhs_sum: if to_first { None } else { Some(rhs_sum) },
+            computed_values: new_values,
+        };
+        Ok(vec![
+            Action::send(ctx.init.sid - 1, BACKWARD, &next)?,
+            Action::Output(self.result(ctx, shares)?),
+        ])
+    }
+}
+
+impl<S: AdditiveHomomorphic> Protocol for Multiply<S> {
+    fn start(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        if ctx.init.sid != 0 {
+            return Ok(vec![]);
+        }
+        let (a, b) = ctx.shares.operands(self.params.lhs, self.params.rhs)?;
+        // the operands must have been shared under this client's key
+        let pk = ctx.keys.public_key_for::<S>(&a.n)?;
+        if !self.params.modular {
+            check_signed_result_bound(self.result(ctx, vec![])?.value_bits, &a.n)?;
+        }
+        let forward = Forward::<S> {
+            pk: pk.clone(),
+            lhs_first: a.shares.iter().map(|x| S::encrypt(pk, x)).collect(),
+            rhs_sum: b.shares.iter().map(|x| S::encrypt(pk, x)).collect(),
+        };
+        Ok(vec![Action::send(1, FORWARD, &forward)?])
+    }
+    fn handle_round(&mut self, ctx: &mut PartyContext, from: usize, round: u32, payload: Value) -> Result<Vec<Action>, ProtocolError> {
+        // the chain goes forward from the previous party and comes back from the next one
+        let sender = match round {
+            FORWARD => ctx.init.sid.checked_sub(1),
+            BACKWARD => Some(ctx.init.sid + 1),
+            _ => return Err(unexpected_round(ID, round)),
+        };
+        if sender != Some(from) {
+            return Err(ValidationError::InvalidSender { from }.into());
+        }
+        if !self.handled.insert(round) {
+            return Err(unexpected_round(ID, round));
+        }
+        match round {
+            FORWARD => self.forward(ctx, decode_payload(payload)?),
+            _ => self.backward(ctx, decode_payload(payload)?),
+        }
+    }
+}
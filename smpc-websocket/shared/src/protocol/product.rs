@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::marker::PhantomData;
+
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::encoding::check_signed_product_bound;
+use crate::errors::{ProtocolError, ValidationError};
+use crate::homomorphic::{AdditiveHomomorphic, DamgardJurikScheme, PaillierScheme, SchemeKind};
+#[cfg(feature = "insecure-mock")]
+use crate::homomorphic::InsecureMockScheme;
+use crate::packing::PackingLayout;
+use crate::protocol::reshare::{self, Reshare};
+use crate::protocol::{decode_payload, unexpected_round, unsupported_scheme, Action, PartyContext, Protocol};
+use crate::share_store::SharedValue;
+use crate::types::{Computation, InitializeProtocol, RevealMode};
+use crate::validation::check_ciphertexts;
+
+/// Element-wise product of one private input vector of every party.
+///
+/// The first client encrypts its input under its own key and every following party multiplies the ciphertexts
+/// by its input, x_1 * ... * x_k * E(x_0). The last party then starts the reshare round back down the chain.
+pub const ID: &str = "product";
+
+const FORWARD: u32 = 0;
+const RESHARE: u32 = 1;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Params {
+    // index of the private input vector every party multiplies in
+    pub input: usize,
+}
+
+// One ciphertext per element: a scalar multiplication applies the same scalar to every packed slot, so the
+// first round cannot be packed.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+struct Forward<S: AdditiveHomomorphic> {
+    pk: S::PublicKey,
+    computed_values: Vec<S::Ciphertext>,
+}
+
+pub struct Product<S> {
+    params: Params,
+    // rounds this party already handled, each of which reaches it once
+    handled: HashSet<u32>,
+    scheme: PhantomData<S>,
+}
+
+/// The reshare round needs uniform masks, so the scheme must decrypt its whole plaintext space.
+pub fn create(init: &InitializeProtocol) -> Result<Box<dyn Protocol>, ProtocolError> {
+    let params = decode_payload(init.params.clone())?;
+    match init.scheme {
+        SchemeKind::Paillier => Ok(Box::new(Product::<PaillierScheme> { params, handled: HashSet::new(), scheme: PhantomData })),
+        SchemeKind::DamgardJurik => Ok(Box::new(Product::<DamgardJurikScheme> { params, handled: HashSet::new(), scheme: PhantomData })),
+        SchemeKind::EcElGamal => Err(unsupported_scheme(ID, init.scheme)),
+        #[cfg(feature = "insecure-mock")]
+        SchemeKind::InsecureMock => Ok(Box::new(Product::<InsecureMockScheme> { params, handled: HashSet::new(), scheme: PhantomData })),
+    }
+}
+
+pub fn computation(id: usize, input: usize, reveal: RevealMode) -> Computation {
+    Computation { id, protocol: ID.to_string(), params: json!({ "input": input }), reveal }
+}
+
+impl<S: AdditiveHomomorphic> Product<S> {
+    fn forward(&self, ctx: &mut PartyContext, data: Forward<S>) -> Result<Vec<Action>, ProtocolError> {
+        ctx.check_public_key::<S>(&data.pk)?;
+        check_ciphertexts::<S>(&data.pk, &data.computed_values, ctx.init.vector_len)?;
+        let modulus = S::plaintext_modulus(&data.pk);
+        // the first client only checked the product bound assuming every input respects max_input_bits
+        let private_input = ctx.encoded_input(self.params.input, &modulus)?;
+        // negative inputs are encoded as m - |x|, and (m - |x|) * E(y) = E(-|x| * y). Adding E(0) re-randomizes
+        // the result, which would otherwise be the trivial ciphertext for an input of 0.
+        let zero = BigInt::from(0);
+        let new_cts: Vec<S::Ciphertext> = data.computed_values.iter().zip(private_input.iter()).map(|(ct, x)| {
+            S::add(&data.pk, &S::scalar_mul(&data.pk, ct, x), &S::encrypt(&data.pk, &zero))
+        }).collect();
+
+        if !ctx.is_last() {
+            let next = Forward::<S> { pk: data.pk, computed_values: new_cts };
+            return Ok(vec![Action::send(ctx.init.sid + 1, FORWARD, &next)?]);
+        }
+        let packing = PackingLayout::new(ctx.init.num_parties, ctx.init.max_input_bits, &modulus);
+        self.reshare(ctx, Reshare::new(data.pk, new_cts, packing))
+    }
+
+    fn reshare(&self, ctx: &mut PartyContext, msg: Reshare<S>) -> Result<Vec<Action>, ProtocolError> {
+        let n = S::plaintext_modulus(&msg.pk);
+        let (mut actions, shares) = reshare::handle(ctx, RESHARE, msg)?;
+        // the product of num_parties inputs carries num_parties times the input scale
+        actions.push(Action::Output(SharedValue {
+            n,
+            shares,
+            scale: ctx.init.scale * ctx.init.num_parties as u32,
+            value_bits: ctx.init.max_input_bits * ctx.init.num_parties,
+        }));
+        Ok(actions)
+    }
+}
+
+impl<S: AdditiveHomomorphic> Protocol for Product<S> {
+    fn start(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        // every other client waits for the message chain to reach it
+        if ctx.init.sid != 0 {
+            return Ok(vec![]);
+        }
+        let (pk, _) = ctx.keys.session_keys::<S>(ctx.init.bits_security);
+        let modulus = S::plaintext_modulus(&pk);
+        // refuse to start if the signed product of all inputs could wrap around the plaintext modulus
+        check_signed_product_bound(ctx.init.num_parties, ctx.init.max_input_bits, &modulus)?;
+        let start_values = ctx.encoded_input(self.params.input, &modulus)?;
+        let computed_values = start_values.iter().map(|x| S::encrypt(&pk, x)).collect();
+        Ok(vec![Action::send(1, FORWARD, &Forward::<S> { pk, computed_values })?])
+    }
+
+    fn handle_round(&mut self, ctx: &mut PartyContext, from: usize, round: u32, payload: Value) -> Result<Vec<Action>, ProtocolError> {
+        // the chain goes forward from the previous party and comes back from the next one
+        let sender = match round {
+            FORWARD => ctx.init.sid.checked_sub(1),
+            RESHARE => Some(ctx.init.sid + 1),
+            _ => return Err(unexpected_round(ID, round)),
+        };
+        if sender != Some(from) {
+            return Err(ValidationError::InvalidSender { from }.into());
+        }
+        if !self.handled.insert(round) {
+            return Err(unexpected_round(ID, round));
+        }
+        match round {
+            FORWARD => self.forward(ctx, decode_payload(payload)?),
+            _ => self.reshare(ctx, decode_payload(payload)?),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::marker::PhantomData;
+
+use curv::arithmetic::traits::BitManipulation;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::errors::{ProtocolError, ValidationError};
+use crate::homomorphic::{AdditiveHomomorphic, DamgardJurikScheme, PaillierScheme, SchemeKind};
+#[cfg(feature = "insecure-mock")]
+use crate::homomorphic::InsecureMockScheme;
+use crate::protocol::{decode_payload, unexpected_round, unsupported_scheme, Action, PartyContext, Protocol};
+use crate::rng;
+use crate::share_store::SharedValue;
+use crate::types::{Computation, InitializeProtocol, RevealMode};
+
+/// Uniformly random shares mod the plaintext modulus of the first client's key, of a value nobody knows, e.g.
+/// the a and b of a Beaver triple (see protocol::beaver).
+///
+/// The first client announces its public key, so that every party draws its shares mod the same n.
+pub const ID: &str = "random";
+
+const KEY: u32 = 0;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+struct Key<S: AdditiveHomomorphic> {
+    pk: S::PublicKey,
+}
+
+pub struct Random<S> {
+    scheme: PhantomData<S>,
+}
+
+/// The value is only there to mask others, so it is never revealed. Its shares are combined with others under
+/// the same key, so the scheme must be one of those the multiplications run on.
+pub fn create(init: &InitializeProtocol) -> Result<Box<dyn Protocol>, ProtocolError> {
+    if init.reveal != RevealMode::Private {
+        return Err(ProtocolError::UnsupportedReveal { protocol: ID.to_string() });
+    }
+    match init.scheme {
+        SchemeKind::Paillier => Ok(Box::new(Random::<PaillierScheme> { scheme: PhantomData })),
+        SchemeKind::DamgardJurik => Ok(Box::new(Random::<DamgardJurikScheme> { scheme: PhantomData })),
+        SchemeKind::EcElGamal => Err(unsupported_scheme(ID, init.scheme)),
+        #[cfg(feature = "insecure-mock")]
+        SchemeKind::InsecureMock => Ok(Box::new(Random::<InsecureMockScheme> { scheme: PhantomData })),
+    }
+}
+
+pub fn computation(id: usize) -> Computation {
+    Computation { id, protocol: ID.to_string(), params: json!({}), reveal: RevealMode::Private }
+}
+
+impl<S: AdditiveHomomorphic> Random<S> {
+    fn output(&self, ctx: &PartyContext, pk: &S::PublicKey) -> Action {
+        let n = S::plaintext_modulus(pk);
+        Action::Output(SharedValue {
+            shares: (0..ctx.init.vector_len).map(|_| rng::sample_below(&n)).collect(),
+            scale: 0,
+            value_bits: n.bit_length(),
+            n,
+        })
+    }
+}
+
+impl<S: AdditiveHomomorphic> Protocol for Random<S> {
+    fn start(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        if ctx.init.sid != 0 {
+            return Ok(vec![]);
+        }
+        let (pk, _) = ctx.keys.session_keys::<S>(ctx.init.bits_security);
+        Ok(vec![Action::broadcast(KEY, &Key::<S> { pk: pk.clone() })?, self.output(ctx, &pk)])
+    }
+
+    fn handle_round(&mut self, ctx: &mut PartyContext, from: usize, round: u32, payload: Value) -> Result<Vec<Action>, ProtocolError> {
+        match round {
+            KEY if from == 0 => {
+                let key: Key<S> = decode_payload(payload)?;
+                ctx.check_public_key::<S>(&key.pk)?;
+                Ok(vec![self.output(ctx, &key.pk)])
+            }
+            KEY => Err(ValidationError::InvalidSender { from }.into()),
+            _ => Err(unexpected_round(ID, round)),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ProtocolError;
+use crate::homomorphic::{sample_masks, AdditiveHomomorphic};
+use crate::packing::PackingLayout;
+use crate::errors::ValidationError;
+use crate::protocol::{Action, PartyContext};
+use crate::validation::check_ciphertexts;
+
+/// Second round of the chain protocols (product, sum).
+///
+/// The last party holds encryptions of the result under the first client's key. Going back down the chain
+/// every party removes a random share from them, and the first client decrypts what is left as its own share.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Reshare<S: AdditiveHomomorphic> {
+    pub pk: S::PublicKey,
+    pub computed_values: Vec<S::Ciphertext>,
+    // None if not even one slot fits in the plaintext space, there is then one ciphertext per element
+    pub packing: Option<PackingLayout>,
+    pub vector_len: usize,
+}
+
+impl<S: AdditiveHomomorphic> Reshare<S> {
+    /// Built by the last party from one ciphertext per element, packed into as few ciphertexts as possible.
+    pub fn new(pk: S::PublicKey, ciphertexts: Vec<S::Ciphertext>, packing: Option<PackingLayout>) -> Self {
+        let vector_len = ciphertexts.len();
+        let computed_values = match &packing {
+            Some(layout) => layout.pack_ciphertexts::<S>(&pk, &ciphertexts),
+            None => ciphertexts,
+        };
+        Reshare { pk, computed_values, packing, vector_len }
+    }
+}
+
+/// Take this party's shares out of the message. Every party but the first masks the ciphertexts and passes
+/// them on to the previous party as `round`.
+pub fn handle<S: AdditiveHomomorphic>(ctx: &mut PartyContext, round: u32, msg: Reshare<S>) -> Result<(Vec<Action>, Vec<BigInt>), ProtocolError> {
+    ctx.check_public_key::<S>(&msg.pk)?;
+    if msg.vector_len != ctx.init.vector_len {
+        return Err(ValidationError::WrongLength { expected: ctx.init.vector_len, actual: msg.vector_len }.into());
+    }
+    let expected = msg.packing.as_ref().map_or(msg.vector_len, |layout| layout.num_ciphertexts(msg.vector_len));
+    check_ciphertexts::<S>(&msg.pk, &msg.computed_values, expected)?;
+    let sid = ctx.init.sid;
+    let modulus = S::plaintext_modulus(&msg.pk);
+    if sid == 0 {
+        let sk = ctx.keys.secret_key::<S>()?;
+        let decrypted = msg.computed_values.iter().map(|ct| S::decrypt(&msg.pk, sk, ct)).collect::<Result<Vec<_>, _>>()?;
+        let shares = match &msg.packing {
+            Some(layout) => layout.unpack_shares(&decrypted, msg.vector_len, &modulus),
+            None => decrypted,
+        };
+        println!("Decrypted {} shares of computation {}", shares.len(), ctx.init.computation_id);
+        return Ok((vec![], shares));
+    }
+    let (masked, shares) = match &msg.packing {
+        Some(layout) => layout.mask_ciphertexts::<S>(&msg.pk, &msg.computed_values, msg.vector_len),
+        None => {
+            // no packing: subtract a uniform share r from every element, E(p) + E(-r) = E(p - r)
+            let (masks, shares) = sample_masks::<S>(&msg.pk, msg.computed_values.len());
+            let masked = msg.computed_values.iter().zip(&masks).map(|(ct, m)| S::add(&msg.pk, ct, m)).collect();
+            (masked, shares)
+        }
+    };
+    println!("Masked {} shares of computation {}", shares.len(), ctx.init.computation_id);
+    let next = Reshare::<S> {
+        pk: msg.pk,
+        computed_values: masked,
+        packing: msg.packing,
+        vector_len: msg.vector_len,
+    };
+    Ok((vec![Action::send(sid - 1, round, &next)?], shares))
+}
@@ -0,0 +1,416 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use curv::arithmetic::traits::{BitManipulation, Converter, Modulo};
+use curv::elliptic::curves::{Point, Scalar, Secp256k1};
+use hmac::{Hmac, Mac};
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use crate::encoding::check_signed_result_bound;
+use crate::errors::{ProtocolError, ValidationError};
+use crate::protocol::channel::{self, Sealed};
+use crate::protocol::{decode_payload, unexpected_round, Action, PartyContext, Protocol};
+use crate::rng;
+use crate::share_store::SharedValue;
+use crate::sharing::{self, Field, Share};
+use crate::types::{Computation, InitializeProtocol, RevealMode};
+
+/// Dropout-tolerant sum of one private input vector of every party, after the secure aggregation of Bonawitz et
+/// al. (CCS 2017), with every remaining party playing the aggregator.
+///
+/// Every party advertises two secp256k1 keys and Shamir-shares, with threshold t, the secret key of the second
+/// one and a random self-mask seed among the others, encrypted to each of them under a key agreed with the
+/// first one. It then broadcasts its input plus the self mask and, for every party whose shares it received, a
+/// pairwise mask agreed with the second key, which the lower sid adds and the higher one subtracts. The parties
+/// whose masked input arrived then exchange their shares of the self-mask seeds of those parties and of the
+/// secret keys of the parties that dropped after handing out their shares, never both for the same party: any
+/// t of them remove every mask left from the sum, fewer learn nothing about a single input.
+///
+/// The sum is computed mod the group order of secp256k1, whatever the session's scheme, and every party that
+/// finishes learns it, so it is only revealed publicly. The server must relay the same broadcasts to everyone:
+/// one that substitutes advertised keys can read the shares.
+pub const ID: &str = "secagg";
+
+const ADVERTISE: u32 = 0;
+const SHARE_KEYS: u32 = 1;
+const MASKED_INPUT: u32 = 2;
+const UNMASK: u32 = 3;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// separate the seeds derived from the same agreed point
+const PAIR_DOMAIN: &[u8] = b"smpc secagg pairwise seed";
+const MASK_DOMAIN: &[u8] = b"smpc secagg mask";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Params {
+    // index of the private input vector every party adds in
+    pub input: usize,
+    // number of parties that must finish, more than half of them
+    pub threshold: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Advertise {
+    // agrees the key the shares are encrypted under
+    c_pk: Point<Secp256k1>,
+    // agrees the pairwise mask seeds
+    s_pk: Point<Secp256k1>,
+}
+
+// one party's shares of the secret key behind s_pk and of the self-mask seed of another party
+#[derive(Serialize, Deserialize)]
+struct KeyShares {
+    key: BigInt,
+    seed: BigInt,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MaskedInput {
+    // parties the sender added a pairwise mask for
+    included: BTreeSet<usize>,
+    masked: Vec<BigInt>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Unmask {
+    // parties whose masked input the sender received
+    survivors: BTreeSet<usize>,
+    // shares of the self-mask seed of every survivor
+    seeds: BTreeMap<usize, BigInt>,
+    // shares of the secret key of every party that handed out shares but did not send its masked input
+    keys: BTreeMap<usize, BigInt>,
+}
+
+// the round every party still in the computation is expected to send next
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Stage {
+    Advertise,
+    ShareKeys,
+    MaskedInput,
+    Unmask,
+    Done,
+}
+
+pub struct SecAgg {
+    params: Params,
+    stage: Stage,
+    c_sk: Scalar<Secp256k1>,
+    s_sk: Scalar<Secp256k1>,
+    seed: BigInt,
+    // messages of every round by sender, this party's own included
+    advertised: BTreeMap<usize, Advertise>,
+    shares: BTreeMap<usize, KeyShares>,
+    masked: BTreeMap<usize, MaskedInput>,
+    unmasked: BTreeMap<usize, Unmask>,
+}
+
+pub fn create(init: &InitializeProtocol) -> Result<Box<dyn Protocol>, ProtocolError> {
+    let params: Params = decode_payload(init.params.clone())?;
+    if init.reveal != RevealMode::Public {
+        return Err(ProtocolError::UnsupportedReveal { protocol: ID.to_string() });
+    }
+    if 2 * params.threshold <= init.num_parties || params.threshold > init.num_parties {
+        return Err(ProtocolError::InvalidThreshold { threshold: params.threshold, num_parties: init.num_parties });
+    }
+    Ok(Box::new(SecAgg {
+        params,
+        stage: Stage::Advertise,
+        c_sk: rng::sample_scalar(),
+        s_sk: rng::sample_scalar(),
+        seed: rng::sample_below(modulus()),
+        advertised: BTreeMap::new(),
+        shares: BTreeMap::new(),
+        masked: BTreeMap::new(),
+        unmasked: BTreeMap::new(),
+    }))
+}
+
+pub fn computation(id: usize, input: usize, threshold: usize) -> Computation {
+    Computation { id, protocol: ID.to_string(), params: json!({ "input": input, "threshold": threshold }), reveal: RevealMode::Public }
+}
+
+fn modulus() -> &'static BigInt {
+    Scalar::<Secp256k1>::group_order()
+}
+
+/// A sum of num_parties values below 2^max_input_bits is below 2^(max_input_bits + bits(num_parties)).
+fn value_bits(init: &InitializeProtocol) -> usize {
+    init.max_input_bits + BigInt::from(init.num_parties as u64).bit_length()
+}
+
+/// `len` values mod the group order drawn from a seed, each from 512 bits of HMAC-SHA256 output.
+fn mask(seed: &[u8], len: usize) -> Vec<BigInt> {
+    let prf = HmacSha256::new_from_slice(seed).expect("HMAC takes keys of any size");
+    (0..len as u64)
+        .map(|k| {
+            let mut bytes = Zeroizing::new(Vec::with_capacity(64));
+            for half in 0..2u8 {
+                let mut mac = prf.clone();
+                mac.update(MASK_DOMAIN);
+                mac.update(&k.to_be_bytes());
+                mac.update(&[half]);
+                bytes.extend_from_slice(&mac.finalize().into_bytes());
+            }
+            BigInt::modulus(&BigInt::from_bytes(&bytes), modulus())
+        })
+        .collect()
+}
+
+fn seed_bytes(seed: &BigInt) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(seed.to_bytes())
+}
+
+/// Check that every value received is an element of the field.
+fn check_elements<'a>(mut values: impl Iterator<Item = &'a BigInt>) -> Result<(), ValidationError> {
+    if values.any(|x| *x < BigInt::from(0) || x >= modulus()) {
+        return Err(ValidationError::CiphertextOutOfRange);
+    }
+    Ok(())
+}
+
+fn add_into(sum: &mut [BigInt], values: &[BigInt]) {
+    for (s, x) in sum.iter_mut().zip(values) {
+        *s = BigInt::mod_add(s, x, modulus());
+    }
+}
+
+fn subtract_from(sum: &mut [BigInt], values: &[BigInt]) {
+    for (s, x) in sum.iter_mut().zip(values) {
+        *s = BigInt::mod_sub(s, x, modulus());
+    }
+}
+
+impl SecAgg {
+    /// Parties expected to send the round in progress: those that sent the round before it and did not drop
+    /// out of the session.
+    fn expected(&self, ctx: &PartyContext) -> Vec<usize> {
+        let previous: Vec<usize> = match self.stage {
+            Stage::Advertise => (0..ctx.init.num_parties).collect(),
+            Stage::ShareKeys => self.advertised.keys().copied().collect(),
+            Stage::MaskedInput => self.shares.keys().copied().collect(),
+            Stage::Unmask => self.masked.keys().copied().collect(),
+            Stage::Done => vec![],
+        };
+        previous.into_iter().filter(|sid| !ctx.dropped.contains(sid)).collect()
+    }
+
+    fn received(&self, sid: usize) -> bool {
+        match self.stage {
+            Stage::Advertise => self.advertised.contains_key(&sid),
+            Stage::ShareKeys => self.shares.contains_key(&sid),
+            Stage::MaskedInput => self.masked.contains_key(&sid),
+            Stage::Unmask => self.unmasked.contains_key(&sid),
+            Stage::Done => true,
+        }
+    }
+
+    /// Move on to the next rounds for as long as every party expected in the round in progress sent it.
+    fn advance(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        let mut actions = Vec::new();
+        while self.stage != Stage::Done && self.expected(ctx).into_iter().all(|sid| self.received(sid)) {
+            match self.stage {
+                Stage::Advertise => actions.extend(self.share_keys(ctx)?),
+                Stage::ShareKeys => actions.extend(self.send_masked_input(ctx)?),
+                Stage::MaskedInput => actions.extend(self.unmask(ctx)?),
+                Stage::Unmask => actions.push(self.output(ctx)?),
+                Stage::Done => {}
+            }
+        }
+        Ok(actions)
+    }
+
+    fn check_survivors(&self, finished: usize) -> Result<(), ProtocolError> {
+        if finished < self.params.threshold {
+            return Err(ProtocolError::NotEnoughSurvivors { remaining: finished, threshold: self.params.threshold });
+        }
+        Ok(())
+    }
+
+    /// Share this party's mask key and self-mask seed among every party that advertised its keys.
+    fn share_keys(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        let sid = ctx.init.sid;
+        let parties: Vec<usize> = self.advertised.keys().copied().collect();
+        self.check_survivors(parties.len())?;
+        let threshold = self.params.threshold;
+        let field = Field::secp256k1();
+        let keys = field.share(&self.s_sk.to_bigint(), threshold, &parties);
+        let seeds = field.share(&self.seed, threshold, &parties);
+        let mut actions = Vec::new();
+        for ((&to, key), seed) in parties.iter().zip(keys).zip(seeds) {
+            let shares = KeyShares { key: key.y, seed: seed.y };
+            if to == sid {
+                self.shares.insert(sid, shares);
+            } else {
+                actions.push(Action::send(to, SHARE_KEYS, &self.seal(ctx, to, &shares)?)?);
+            }
+        }
+        self.stage = Stage::ShareKeys;
+        Ok(actions)
+    }
+
+    fn seal(&self, ctx: &PartyContext, to: usize, shares: &KeyShares) -> Result<Sealed, ProtocolError> {
+        channel::seal(&self.c_sk, &self.advertised[&to].c_pk, ctx.init.computation_id, ctx.init.sid, to, shares)
+    }
+
+    fn open(&self, ctx: &PartyContext, from: usize, sealed: Sealed) -> Result<KeyShares, ProtocolError> {
+        let c_pk = &self.advertised.get(&from).ok_or(ValidationError::InvalidSender { from })?.c_pk;
+        let shares: KeyShares = channel::open(&self.c_sk, c_pk, ctx.init.computation_id, from, ctx.init.sid, sealed)?;
+        check_elements([&shares.key, &shares.seed].into_iter())?;
+        Ok(shares)
+    }
+
+    /// The pairwise mask of this party with `other`, signed so that the two cancel out in the sum.
+    fn pairwise_mask(sid: usize, sk: &Scalar<Secp256k1>, other: usize, other_pk: &Point<Secp256k1>, len: usize) -> Vec<BigInt> {
+        let mask = mask(channel::agree(sk, other_pk, PAIR_DOMAIN).as_slice(), len);
+        if sid < other {
+            mask
+        } else {
+            mask.iter().map(|m| BigInt::mod_sub(&BigInt::from(0), m, modulus())).collect()
+        }
+    }
+
+    /// Broadcast this party's input plus its self mask and a pairwise mask for every party whose shares arrived.
+    fn send_masked_input(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        let sid = ctx.init.sid;
+        let included: BTreeSet<usize> = self.shares.keys().copied().filter(|&other| other != sid).collect();
+        self.check_survivors(included.len() + 1)?;
+        let len = ctx.init.vector_len;
+        let mut masked = ctx.encoded_input(self.params.input, modulus())?;
+        add_into(&mut masked, &mask(&seed_bytes(&self.seed), len));
+        for &other in &included {
+            add_into(&mut masked, &Self::pairwise_mask(sid, &self.s_sk, other, &self.advertised[&other].s_pk, len));
+        }
+        // once masked the input is no longer secret
+        let message = MaskedInput { included, masked: std::mem::take(&mut *masked) };
+        let action = Action::broadcast(MASKED_INPUT, &message)?;
+        self.masked.insert(sid, message);
+        self.stage = Stage::MaskedInput;
+        Ok(vec![action])
+    }
+
+    /// Check that every pair of survivors masked its inputs for each other, then broadcast this party's shares
+    /// of the seeds of the survivors and of the keys of the parties that dropped since they handed out theirs.
+    fn unmask(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        let sid = ctx.init.sid;
+        let survivors: BTreeSet<usize> = self.masked.keys().copied().collect();
+        self.check_survivors(survivors.len())?;
+        for (&u, input) in &self.masked {
+            if survivors.iter().any(|&v| v != u && input.included.contains(&v) != self.masked[&v].included.contains(&u)) {
+                return Err(ValidationError::ViewMismatch { from: u }.into());
+            }
+        }
+        let seeds = survivors.iter().map(|&u| (u, self.shares[&u].seed.clone())).collect();
+        let keys = self.shares.iter().filter(|(v, _)| !survivors.contains(v)).map(|(&v, shares)| (v, shares.key.clone())).collect();
+        let message = Unmask { survivors, seeds, keys };
+        let action = Action::broadcast(UNMASK, &message)?;
+        self.unmasked.insert(sid, message);
+        self.stage = Stage::Unmask;
+        Ok(vec![action])
+    }
+
+    /// Reconstruct the secret of `owner` from the shares every party sent of it.
+    fn reconstruct(&self, owner: usize, pick: impl Fn(&Unmask) -> Option<&BigInt>) -> Result<BigInt, ProtocolError> {
+        let shares: Vec<Share> = self
+            .unmasked
+            .iter()
+            .filter_map(|(&sid, unmask)| pick(unmask).map(|y| Share { x: sharing::point(sid), y: y.clone() }))
+            .collect();
+        self.check_survivors(shares.len())?;
+        Field::secp256k1().reconstruct(&shares).ok_or(ValidationError::InconsistentShares { owner }.into())
+    }
+
+    /// Remove every mask from the sum of the masked inputs.
+    fn output(&mut self, ctx: &mut PartyContext) -> Result<Action, ProtocolError> {
+        self.check_survivors(self.unmasked.len())?;
+        let survivors: BTreeSet<usize> = self.masked.keys().copied().collect();
+        if let Some((&from, _)) = self.unmasked.iter().find(|(_, unmask)| unmask.survivors != survivors) {
+            return Err(ValidationError::ViewMismatch { from }.into());
+        }
+        let len = ctx.init.vector_len;
+        let mut sum = vec![BigInt::from(0); len];
+        for input in self.masked.values() {
+            add_into(&mut sum, &input.masked);
+        }
+        for &u in &survivors {
+            let seed = self.reconstruct(u, |unmask| unmask.seeds.get(&u))?;
+            subtract_from(&mut sum, &mask(&seed_bytes(&seed), len));
+        }
+        let dropped: BTreeSet<usize> = self.masked.values().flat_map(|input| input.included.iter().copied()).filter(|v| !survivors.contains(v)).collect();
+        for v in dropped {
+            let s_pk = &self.advertised[&v].s_pk;
+            let key = Scalar::<Secp256k1>::from_bigint(&self.reconstruct(v, |unmask| unmask.keys.get(&v))?);
+            if Point::<Secp256k1>::generator() * &key != *s_pk {
+                return Err(ValidationError::InconsistentShares { owner: v }.into());
+            }
+            for (&u, _) in self.masked.iter().filter(|(_, input)| input.included.contains(&v)) {
+                subtract_from(&mut sum, &Self::pairwise_mask(u, &key, v, &self.advertised[&u].s_pk, len));
+            }
+        }
+        println!("Recovered the sum of {} inputs, {} parties dropped out", survivors.len(), ctx.init.num_parties - survivors.len());
+        self.stage = Stage::Done;
+        Ok(Action::Public(SharedValue { n: modulus().clone(), shares: sum, scale: ctx.init.scale, value_bits: value_bits(ctx.init) }))
+    }
+}
+
+impl Protocol for SecAgg {
+    fn start(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        check_signed_result_bound(value_bits(ctx.init), modulus())?;
+        let g = Point::<Secp256k1>::generator();
+        let message = Advertise { c_pk: g * &self.c_sk, s_pk: g * &self.s_sk };
+        let action = Action::broadcast(ADVERTISE, &message)?;
+        self.advertised.insert(ctx.init.sid, message);
+        let mut actions = vec![action];
+        actions.extend(self.advance(ctx)?);
+        Ok(actions)
+    }
+
+    fn handle_round(&mut self, ctx: &mut PartyContext, from: usize, round: u32, payload: Value) -> Result<Vec<Action>, ProtocolError> {
+        // rounds of other parties may come ahead of this party's, never after it moved past them
+        let current = self.stage;
+        let late = |stage| current > stage;
+        match round {
+            ADVERTISE if !late(Stage::Advertise) && !self.advertised.contains_key(&from) => {
+                let message: Advertise = decode_payload(payload)?;
+                channel::check_point(&message.c_pk)?;
+                channel::check_point(&message.s_pk)?;
+                self.advertised.insert(from, message);
+            }
+            SHARE_KEYS if !late(Stage::ShareKeys) && !self.shares.contains_key(&from) => {
+                let shares = self.open(ctx, from, decode_payload(payload)?)?;
+                self.shares.insert(from, shares);
+            }
+            MASKED_INPUT if !late(Stage::MaskedInput) && !self.masked.contains_key(&from) => {
+                let message: MaskedInput = decode_payload(payload)?;
+                if message.masked.len() != ctx.init.vector_len {
+                    return Err(ValidationError::WrongLength { expected: ctx.init.vector_len, actual: message.masked.len() }.into());
+                }
+                check_elements(message.masked.iter())?;
+                // its shares came before, and it can only have masked for parties that advertised their keys
+                let known = |sid: &usize| self.advertised.contains_key(sid) && *sid != from;
+                if !self.shares.contains_key(&from) || !message.included.iter().all(known) {
+                    return Err(ValidationError::ViewMismatch { from }.into());
+                }
+                self.masked.insert(from, message);
+            }
+            UNMASK if !late(Stage::Unmask) && !self.unmasked.contains_key(&from) => {
+                let message: Unmask = decode_payload(payload)?;
+                check_elements(message.seeds.values().chain(message.keys.values()))?;
+                self.unmasked.insert(from, message);
+            }
+            _ => return Err(unexpected_round(ID, round)),
+        }
+        self.advance(ctx)
+    }
+
+    fn threshold(&self) -> Option<usize> {
+        Some(self.params.threshold)
+    }
+
+    fn dropped(&mut self, ctx: &mut PartyContext, _sid: usize) -> Result<Vec<Action>, ProtocolError> {
+        self.advance(ctx)
+    }
+}
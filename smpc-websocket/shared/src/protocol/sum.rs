@@ -0,0 +1,150 @@
+use std::marker::PhantomData;
+
+use curv::arithmetic::traits::BitManipulation;
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::encoding::check_signed_result_bound;
+use crate::errors::ProtocolError;
+use crate::homomorphic::{AdditiveHomomorphic, DamgardJurikScheme, ElGamalScheme, PaillierScheme, SchemeKind};
+#[cfg(feature = "insecure-mock")]
+use crate::homomorphic::InsecureMockScheme;
+use crate::packing::PackingLayout;
+use crate::protocol::reshare::{self, Reshare};
+use crate::protocol::{decode_payload, unexpected_round, unsupported_scheme, Action, PartyContext, Protocol};
+use crate::share_store::SharedValue;
+use crate::types::{Computation, InitializeProtocol, RevealMode};
+use crate::validation::check_ciphertexts;
+
+/// Element-wise sum of one private input vector of every party.
+///
+/// Same chain as the product, but every party adds the encryption of its input instead of multiplying,
+/// E(x_0) + E(x_1) + ... = E(x_0 + x_1 + ...).
+///
+/// Schemes that can only decrypt small plaintexts (exponential ElGamal) cannot remove uniform masks, so there
+/// is no reshare round: the last party sends the sum to the first client, which takes it as its share while
+/// every other party takes 0. The first client thus learns the sum, which is only allowed when it would
+/// learn it anyway in the reveal phase.
+pub const ID: &str = "sum";
+
+const FORWARD: u32 = 0;
+const RESHARE: u32 = 1;
+const DECRYPT: u32 = 2;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Params {
+    // index of the private input vector every party adds in
+    pub input: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+struct Forward<S: AdditiveHomomorphic> {
+    pk: S::PublicKey,
+    computed_values: Vec<S::Ciphertext>,
+}
+
+pub struct Sum<S> {
+    params: Params,
+    scheme: PhantomData<S>,
+}
+
+pub fn create(init: &InitializeProtocol) -> Result<Box<dyn Protocol>, ProtocolError> {
+    let params = decode_payload(init.params.clone())?;
+    match init.scheme {
+        SchemeKind::Paillier => Ok(Box::new(Sum::<PaillierScheme> { params, scheme: PhantomData })),
+        SchemeKind::DamgardJurik => Ok(Box::new(Sum::<DamgardJurikScheme> { params, scheme: PhantomData })),
+        SchemeKind::EcElGamal => match init.reveal {
+            RevealMode::Public | RevealMode::OutputParty(0) => Ok(Box::new(Sum::<ElGamalScheme> { params, scheme: PhantomData })),
+            _ => Err(unsupported_scheme(ID, init.scheme)),
+        },
+        #[cfg(feature = "insecure-mock")]
+        SchemeKind::InsecureMock => Ok(Box::new(Sum::<InsecureMockScheme> { params, scheme: PhantomData })),
+    }
+}
+
+pub fn computation(id: usize, input: usize, reveal: RevealMode) -> Computation {
+    Computation { id, protocol: ID.to_string(), params: json!({ "input": input }), reveal }
+}
+
+/// A sum of num_parties values below 2^max_input_bits is below 2^(max_input_bits + bits(num_parties)).
+fn value_bits(init: &InitializeProtocol) -> usize {
+    init.max_input_bits + BigInt::from(init.num_parties as u64).bit_length()
+}
+
+impl<S: AdditiveHomomorphic> Sum<S> {
+    fn output(&self, ctx: &PartyContext, pk: &S::PublicKey, shares: Vec<BigInt>) -> Action {
+        Action::Output(SharedValue {
+            n: S::plaintext_modulus(pk),
+            shares,
+            scale: ctx.init.scale,
+            value_bits: value_bits(ctx.init),
+        })
+    }
+
+    fn forward(&self, ctx: &mut PartyContext, data: Forward<S>) -> Result<Vec<Action>, ProtocolError> {
+        ctx.check_public_key::<S>(&data.pk)?;
+        check_ciphertexts::<S>(&data.pk, &data.computed_values, ctx.init.vector_len)?;
+        let pk = data.pk;
+        let private_input = ctx.encoded_input(self.params.input, &S::plaintext_modulus(&pk))?;
+        let new_cts: Vec<S::Ciphertext> = data.computed_values.iter().zip(private_input.iter()).map(|(ct, x)| {
+            S::add(&pk, ct, &S::encrypt(&pk, x))
+        }).collect();
+
+        if S::max_decryptable_bits(&pk).is_some() {
+            let (to, round) = if ctx.is_last() { (0, DECRYPT) } else { (ctx.init.sid + 1, FORWARD) };
+            let zeros = vec![BigInt::from(0); new_cts.len()];
+            let next = Forward::<S> { pk: pk.clone(), computed_values: new_cts };
+            return Ok(vec![Action::send(to, round, &next)?, self.output(ctx, &pk, zeros)]);
+        }
+        if !ctx.is_last() {
+            let next = Forward::<S> { pk, computed_values: new_cts };
+            return Ok(vec![Action::send(ctx.init.sid + 1, FORWARD, &next)?]);
+        }
+        let packing = PackingLayout::with_value_bits(value_bits(ctx.init), ctx.init.num_parties, &S::plaintext_modulus(&pk));
+        self.reshare(ctx, Reshare::new(pk, new_cts, packing))
+    }
+
+    fn reshare(&self, ctx: &mut PartyContext, msg: Reshare<S>) -> Result<Vec<Action>, ProtocolError> {
+        let pk = msg.pk.clone();
+        let (mut actions, shares) = reshare::handle(ctx, RESHARE, msg)?;
+        actions.push(self.output(ctx, &pk, shares));
+        Ok(actions)
+    }
+
+    fn decrypt(&self, ctx: &mut PartyContext, data: Forward<S>) -> Result<Vec<Action>, ProtocolError> {
+        ctx.check_public_key::<S>(&data.pk)?;
+        check_ciphertexts::<S>(&data.pk, &data.computed_values, ctx.init.vector_len)?;
+        let sk = ctx.keys.secret_key::<S>()?;
+        let shares = data.computed_values.iter().map(|ct| S::decrypt(&data.pk, sk, ct)).collect::<Result<_, _>>()?;
+        Ok(vec![self.output(ctx, &data.pk, shares)])
+    }
+}
+
+impl<S: AdditiveHomomorphic> Protocol for Sum<S> {
+    fn start(&mut self, ctx: &mut PartyContext) -> Result<Vec<Action>, ProtocolError> {
+        if ctx.init.sid != 0 {
+            return Ok(vec![]);
+        }
+        let (pk, _) = ctx.keys.session_keys::<S>(ctx.init.bits_security);
+        let modulus = S::plaintext_modulus(&pk);
+        let required_bits = value_bits(ctx.init);
+        check_signed_result_bound(required_bits, &modulus)?;
+        if let Some(available_bits) = S::max_decryptable_bits(&pk) && required_bits > available_bits {
+            return Err(ProtocolError::ProductOutOfRange { required_bits, available_bits });
+        }
+        let start_values = ctx.encoded_input(self.params.input, &modulus)?;
+        let computed_values = start_values.iter().map(|x| S::encrypt(&pk, x)).collect();
+        Ok(vec![Action::send(1, FORWARD, &Forward::<S> { pk, computed_values })?])
+    }
+
+    fn handle_round(&mut self, ctx: &mut PartyContext, _from: usize, round: u32, payload: Value) -> Result<Vec<Action>, ProtocolError> {
+        match round {
+            FORWARD => self.forward(ctx, decode_payload(payload)?),
+            RESHARE => self.reshare(ctx, decode_payload(payload)?),
+            DECRYPT if ctx.init.sid == 0 => self.decrypt(ctx, decode_payload(payload)?),
+            _ => Err(unexpected_round(ID, round)),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! A hash chain over every frame the server sends, with checkpoints signed by the server.
+//!
+//! The server appends each frame it sends to a chain over the whole session and to a chain per client. When a
+//! computation is over, it signs the heads of all the chains and sends the checkpoint to every client, which
+//! compares its own head against the chain it computed over the frames it received. A relay that dropped,
+//! reordered or injected frames on their way to a client cannot hand out a checkpoint that matches for it, and
+//! the signature lets a client prove what the server claimed to have sent.
+//!
+//! Only frames the server sends are chained. A frame a client sent that the server never relayed, or never even
+//! received, leaves no trace in any chain, so the checkpoint cannot tell a dropped client-to-client message from
+//! one that was never sent; the protocol waiting for it stalls into a timeout instead. The verifying key is
+//! taken as announced in the first InitializeProtocol, so a client only detects a relay that changes it within a
+//! session, not one that impersonates the server from the start.
+use curv::arithmetic::traits::Converter;
+use curv::elliptic::curves::{Point, Scalar, Secp256k1};
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::rng;
+
+// separates the hashes of the chain and of the signatures from any other use of SHA-256
+const CHAIN_DOMAIN: &[u8] = b"smpc relay log";
+const SIGNATURE_DOMAIN: &[u8] = b"smpc relay checkpoint";
+
+/// Head of a chain of frames: head_0 = 0, head_(i+1) = SHA-256(domain || head_i || to || SHA-256(frame_i)).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelayLog {
+    // number of frames in the chain
+    pub len: u64,
+    // hex SHA-256
+    pub head: String,
+}
+
+impl Default for RelayLog {
+    fn default() -> Self {
+        RelayLog { len: 0, head: hex::encode([0u8; 32]) }
+    }
+}
+
+impl RelayLog {
+    /// Append a frame sent to the client with sid `to`.
+    pub fn append(&mut self, to: usize, frame: &str) {
+        let mut hasher = Sha256::new();
+        hasher.update(CHAIN_DOMAIN);
+        hasher.update(self.head.as_bytes());
+        hasher.update((to as u64).to_be_bytes());
+        hasher.update(Sha256::digest(frame.as_bytes()));
+        self.head = hex::encode(hasher.finalize());
+        self.len += 1;
+    }
+}
+
+/// Heads of the chains once a computation is over.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    // session the checkpoint belongs to, so that it cannot be replayed into another one
+    pub session_id: String,
+    pub computation_id: usize,
+    // chain over every frame of the session so far
+    pub session: RelayLog,
+    // chain over the frames sent to each client, indexed by sid
+    pub clients: Vec<RelayLog>,
+}
+
+/// Schnorr signature on secp256k1: s * G = R + e * P with e = SHA-256(domain || R || P || message).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signature {
+    pub r: Point<Secp256k1>,
+    pub s: Scalar<Secp256k1>,
+}
+
+/// Key the server signs checkpoints with. A new one is generated for every server unless one is provided. A
+/// client keeps its own identity key in its vault.
+#[derive(Clone)]
+pub struct SigningKey {
+    x: Scalar<Secp256k1>,
+    public: VerifyingKey,
+}
+
+/// Public key announced to the clients in every InitializeProtocol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerifyingKey {
+    pub point: Point<Secp256k1>,
+}
+
+fn challenge(r: &Point<Secp256k1>, public: &Point<Secp256k1>, message: &[u8]) -> Scalar<Secp256k1> {
+    let mut hasher = Sha256::new();
+    hasher.update(SIGNATURE_DOMAIN);
+    hasher.update(r.to_bytes(true));
+    hasher.update(public.to_bytes(true));
+    hasher.update(message);
+    Scalar::from_bigint(&BigInt::from_bytes(&hasher.finalize()))
+}
+
+impl SigningKey {
+    pub fn generate() -> Self {
+        SigningKey::from_scalar(rng::sample_scalar())
+    }
+
+    pub fn from_scalar(x: Scalar<Secp256k1>) -> Self {
+        let public = VerifyingKey { point: Point::generator() * &x };
+        SigningKey { x, public }
+    }
+
+    /// The secret scalar, e.g. to keep the key in a client's vault.
+    pub fn secret(&self) -> &Scalar<Secp256k1> {
+        &self.x
+    }
+
+    pub fn verifying_key(&self) -> &VerifyingKey {
+        &self.public
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        let k = rng::sample_scalar();
+        let r = Point::generator() * &k;
+        let e = challenge(&r, &self.public.point, message);
+        Signature { s: k + e * &self.x, r }
+    }
+}
+
+impl VerifyingKey {
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        let e = challenge(&signature.r, &self.point, message);
+        Point::generator() * &signature.s == &signature.r + &self.point * e
+    }
+
+    /// Hex of the compressed point, e.g. to compare it with the key the server prints at startup.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.point.to_bytes(true))
+    }
+}
+
+/// A checkpoint along with the server's signature on its JSON encoding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedCheckpoint {
+    pub checkpoint: Checkpoint,
+    pub signature: Signature,
+}
+
+impl SignedCheckpoint {
+    pub fn sign(checkpoint: Checkpoint, key: &SigningKey) -> Self {
+        let signature = key.sign(&Self::message(&checkpoint));
+        SignedCheckpoint { checkpoint, signature }
+    }
+
+    pub fn verify(&self, key: &VerifyingKey) -> bool {
+        key.verify(&Self::message(&self.checkpoint), &self.signature)
+    }
+
+    fn message(checkpoint: &Checkpoint) -> Vec<u8> {
+        // serializing a struct of strings and integers cannot fail
+        serde_json::to_vec(checkpoint).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_checkpoint() {
+        let key = SigningKey::generate();
+        let mut log = RelayLog::default();
+        log.append(1, "frame");
+        let checkpoint = Checkpoint { session_id: "a".to_string(), computation_id: 0, session: log.clone(), clients: vec![RelayLog::default(), log] };
+        let signed = SignedCheckpoint::sign(checkpoint, &key);
+        assert!(signed.verify(key.verifying_key()));
+        assert!(!signed.verify(SigningKey::generate().verifying_key()));
+
+        let mut forged = signed.clone();
+        forged.checkpoint.clients[1].append(1, "injected");
+        assert!(!forged.verify(key.verifying_key()));
+    }
+
+    #[test]
+    fn test_chain_depends_on_order_and_recipient() {
+        let chain = |frames: &[(usize, &str)]| {
+            let mut log = RelayLog::default();
+            frames.iter().for_each(|(to, frame)| log.append(*to, frame));
+            log
+        };
+        let log = chain(&[(0, "a"), (1, "b")]);
+        assert_eq!(log.len, 2);
+        assert_eq!(log, chain(&[(0, "a"), (1, "b")]));
+        assert_ne!(log, chain(&[(1, "b"), (0, "a")]));
+        assert_ne!(log, chain(&[(0, "a"), (2, "b")]));
+    }
+}
@@ -0,0 +1,74 @@
+//! Replay protection: every message a client sends carries a header binding it to the session, to the computation
+//! it belongs to and to a counter its sender increments with every message. The server and every client keep the
+//! counters they accepted from each sender, so that a message captured in an earlier session, or a copy of one
+//! already seen, is refused. Messages the network reordered still go through.
+use std::collections::{HashMap, HashSet};
+
+use curv::arithmetic::traits::Converter;
+
+use crate::errors::ProtocolError;
+use crate::rng;
+use crate::types::MessageHeader;
+
+/// A fresh random session id, as the server announces it in every InitializeProtocol.
+pub fn session_id() -> String {
+    format!("{:0>32}", rng::sample(128).to_str_radix(16))
+}
+
+/// Counters of the messages accepted from every sender of a session.
+#[derive(Debug, Default)]
+pub struct ReplayGuard {
+    session_id: String,
+    // counters accepted from each sender, by sid
+    seen: HashMap<usize, HashSet<u64>>,
+}
+
+impl ReplayGuard {
+    pub fn new(session_id: impl Into<String>) -> Self {
+        ReplayGuard { session_id: session_id.into(), seen: HashMap::new() }
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Accept a message of `from` if it belongs to this session and no message with the same counter was accepted
+    /// from the same sender.
+    pub fn check(&mut self, from: usize, header: &MessageHeader) -> Result<(), ProtocolError> {
+        if header.session_id != self.session_id {
+            return Err(ProtocolError::WrongSession { from, session_id: header.session_id.clone() });
+        }
+        if !self.seen.entry(from).or_default().insert(header.seq) {
+            return Err(ProtocolError::ReplayedMessage { from, seq: header.seq });
+        }
+        Ok(())
+    }
+
+    /// The largest counter up to which every message of `from` was accepted, 0 if none was.
+    pub fn contiguous(&self, from: usize) -> u64 {
+        let Some(seen) = self.seen.get(&from) else {
+            return 0;
+        };
+        (1..).find(|seq| !seen.contains(seq)).map_or(0, |seq| seq - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replays_are_refused() {
+        let header = |session_id: &str, seq| MessageHeader { session_id: session_id.to_string(), computation_id: 0, seq };
+        let mut guard = ReplayGuard::new("a");
+        assert_eq!(guard.check(0, &header("a", 1)), Ok(()));
+        assert_eq!(guard.check(1, &header("a", 1)), Ok(()));
+        assert_eq!(guard.check(0, &header("a", 3)), Ok(()));
+        // reordered, not replayed
+        assert_eq!(guard.check(0, &header("a", 2)), Ok(()));
+        assert_eq!(guard.check(0, &header("a", 3)), Err(ProtocolError::ReplayedMessage { from: 0, seq: 3 }));
+        assert_eq!(guard.check(1, &header("b", 5)), Err(ProtocolError::WrongSession { from: 1, session_id: "b".to_string() }));
+        assert_eq!((guard.contiguous(0), guard.contiguous(1), guard.contiguous(2)), (3, 1, 0));
+        assert_ne!(session_id(), session_id());
+    }
+}
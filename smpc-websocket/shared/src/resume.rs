@@ -0,0 +1,37 @@
+//! Resuming a session after the connection between the server and a client dropped.
+//!
+//! The server gives every client a random resume token with the first computation of a session, and keeps every
+//! message it sent each client. When a connection drops, the server stops sending to the client and dials it
+//! again, then sends a ResumeRequest with the client's token and the last counter up to which it received all
+//! the client's messages. The client, which kept its party while the connection was down, only hands it to a
+//! connection that knows the token. It answers with the number of messages it received from the server, sends
+//! its own later messages again, and the server sends it every message it missed, in order. Copies of messages
+//! that did get through are dropped by the replay guards. A client that is not back within the grace period is
+//! given up on: the server aborts the session and blames it, and the client wipes its party.
+use crate::replay;
+
+/// A fresh random resume token for one client of a session.
+pub fn token() -> String {
+    // as unpredictable as a session id, which it is drawn the same way as
+    replay::session_id()
+}
+
+/// Compare a token received with the one expected, in time that does not depend on where they differ.
+pub fn tokens_match(received: &str, expected: &str) -> bool {
+    let (received, expected) = (received.as_bytes(), expected.as_bytes());
+    received.len() == expected.len() && received.iter().zip(expected).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_match() {
+        let expected = token();
+        assert!(tokens_match(&expected.clone(), &expected));
+        assert!(!tokens_match(&token(), &expected));
+        assert!(!tokens_match(&expected[1..], &expected));
+        assert!(!tokens_match("", &expected));
+    }
+}
@@ -0,0 +1,124 @@
+//! The source of every random value the parties draw: primes of the keys, encryption randomness and the masks
+//! of the shares.
+//!
+//! Values come from the OS random number generator, through curv. With the test-only `seeded-rng` feature,
+//! `seed` swaps it for a ChaCha20 stream on the current thread, so that a run can be reproduced exactly, e.g.
+//! to emit test vectors.
+use curv::arithmetic::traits::{BitManipulation, Primes, Samplable};
+use curv::elliptic::curves::{Scalar, Secp256k1};
+use kzen_paillier::BigInt;
+
+#[cfg(all(feature = "seeded-rng", not(debug_assertions)))]
+compile_error!("the seeded-rng feature makes every key and mask predictable and cannot be enabled in release builds");
+
+#[cfg(feature = "seeded-rng")]
+mod seeded {
+    use std::cell::RefCell;
+
+    use curv::arithmetic::traits::Converter;
+    use kzen_paillier::BigInt;
+    use rand_chacha::rand_core::{RngCore, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    thread_local! {
+        static RNG: RefCell<Option<ChaCha20Rng>> = const { RefCell::new(None) };
+    }
+
+    pub fn seed(seed: Option<u64>) {
+        RNG.with(|rng| *rng.borrow_mut() = seed.map(ChaCha20Rng::seed_from_u64));
+    }
+
+    /// Same as curv's BigInt::sample, from the seeded stream if there is one.
+    pub fn sample(bits: usize) -> Option<BigInt> {
+        RNG.with(|rng| {
+            let mut rng = rng.borrow_mut();
+            let rng = rng.as_mut()?;
+            let bytes = (bits - 1) / 8 + 1;
+            let mut buf = vec![0; bytes];
+            rng.fill_bytes(&mut buf);
+            Some(BigInt::from_bytes(&buf) >> (bytes * 8 - bits))
+        })
+    }
+}
+
+/// Draw every following random value of this thread from a ChaCha20 stream seeded with `seed`.
+#[cfg(feature = "seeded-rng")]
+pub fn seed(seed: u64) {
+    seeded::seed(Some(seed));
+}
+
+/// Go back to the OS random number generator on this thread.
+#[cfg(feature = "seeded-rng")]
+pub fn unseed() {
+    seeded::seed(None);
+}
+
+/// A uniform number of at most `bits` bits.
+pub fn sample(bits: usize) -> BigInt {
+    if bits == 0 {
+        return BigInt::from(0);
+    }
+    #[cfg(feature = "seeded-rng")]
+    if let Some(n) = seeded::sample(bits) {
+        return n;
+    }
+    BigInt::sample(bits)
+}
+
+/// A uniform number in [0, upper).
+pub fn sample_below(upper: &BigInt) -> BigInt {
+    assert!(*upper > BigInt::from(0));
+    let bits = upper.bit_length();
+    loop {
+        let n = sample(bits);
+        if n < *upper {
+            return n;
+        }
+    }
+}
+
+/// A uniform non-zero scalar of secp256k1.
+pub fn sample_scalar() -> Scalar<Secp256k1> {
+    let order = Scalar::<Secp256k1>::group_order();
+    loop {
+        let k = sample_below(order);
+        if k != BigInt::from(0) {
+            return Scalar::from_bigint(&k);
+        }
+    }
+}
+
+/// A random prime of exactly `bits` bits, found the same way as kzen_paillier's keygen: the first probable
+/// prime among 500 odd numbers from a random starting point with the top bit set.
+pub fn sample_prime(bits: usize) -> BigInt {
+    loop {
+        let mut candidate = sample(bits);
+        candidate.set_bit(0, true);
+        candidate.set_bit(bits - 1, true);
+        for _ in 0..500 {
+            if candidate.is_probable_prime(64) {
+                return candidate;
+            }
+            candidate += BigInt::from(2);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "seeded-rng"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_streams_repeat() {
+        let draw = || (sample_below(&BigInt::from(1000)), sample(200), sample_prime(64));
+        seed(7);
+        let first = draw();
+        seed(7);
+        assert_eq!(draw(), first);
+        seed(8);
+        assert_ne!(draw(), first);
+        unseed();
+        assert!(first.1.bit_length() <= 200);
+        assert_eq!(first.2.bit_length(), 64);
+    }
+}
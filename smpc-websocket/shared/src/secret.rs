@@ -0,0 +1,168 @@
+//! Wiping secrets from memory: keys, private inputs, random shares and masks.
+//!
+//! Every secret number is a curv BigInt, i.e. a GMP integer whose limbs live on the C heap and get copied into
+//! temporaries by every operation. curv's `Zeroize` for BigInt overwrites the handle without freeing the limbs,
+//! leaving them in memory for good. Instead:
+//!
+//! - the secrets a party holds on to, its secret keys, private inputs and shares, live in a `Secret`, which
+//!   zeroes their limbs in place when dropped;
+//! - `wipe_freed_memory` makes GMP itself zero every block before freeing or moving it, which also covers the
+//!   temporaries. It changes the allocator of the whole process, so the binaries call it once, first thing in
+//!   `main`.
+//!
+//! Decimal strings of private inputs are Rust heap memory and are zeroized on drop (see
+//! `protocol::PrivateInputs`).
+//!
+//! A party drops its secrets as soon as it is done with them: the state of a protocol once the computation has
+//! its output, and everything else with `Party::wipe`, on abort and when the client actor stops.
+use std::ffi::{c_int, c_void};
+use std::fmt;
+use std::mem::size_of;
+use std::ops::{Deref, DerefMut};
+use std::sync::Once;
+
+use kzen_paillier::BigInt;
+use zeroize::Zeroize;
+
+#[link(name = "gmp")]
+unsafe extern "C" {
+    fn __gmp_set_memory_functions(
+        alloc: Option<extern "C" fn(usize) -> *mut c_void>,
+        realloc: Option<extern "C" fn(*mut c_void, usize, usize) -> *mut c_void>,
+        free: Option<extern "C" fn(*mut c_void, usize)>,
+    );
+    // bits in an mp_limb_t of the GMP linked in
+    static __gmp_bits_per_limb: c_int;
+}
+
+static WIPE_FREED_MEMORY: Once = Once::new();
+
+/// Have GMP zero every block of memory before it frees it or moves it to a larger one, for every GMP integer of
+/// the process, not only those of a party. Blocks are still allocated with malloc, so blocks GMP allocated before
+/// this call are freed the same way. Call it at the start of `main`, before any thread computes; idempotent.
+pub fn wipe_freed_memory() {
+    // SAFETY: the functions allocate and free with the C allocator GMP uses by default
+    WIPE_FREED_MEMORY.call_once(|| unsafe { __gmp_set_memory_functions(Some(alloc), Some(realloc), Some(free)) });
+}
+
+extern "C" fn alloc(size: usize) -> *mut c_void {
+    // SAFETY: malloc has no preconditions. GMP expects allocations to succeed, as its default ones do.
+    let ptr = unsafe { libc::malloc(size) };
+    if ptr.is_null() {
+        std::process::abort();
+    }
+    ptr
+}
+
+extern "C" fn realloc(ptr: *mut c_void, old_size: usize, new_size: usize) -> *mut c_void {
+    let new = alloc(new_size);
+    // SAFETY: GMP passes a block of old_size bytes it allocated, and new has new_size bytes
+    unsafe { std::ptr::copy_nonoverlapping(ptr as *const u8, new as *mut u8, old_size.min(new_size)) };
+    free(ptr, old_size);
+    new
+}
+
+extern "C" fn free(ptr: *mut c_void, size: usize) {
+    // SAFETY: GMP passes a block of size bytes it allocated and no longer uses
+    unsafe {
+        std::slice::from_raw_parts_mut(ptr as *mut u8, size).zeroize();
+        libc::free(ptr);
+    }
+}
+
+/// A value holding secret GMP integers, which it can zero in place.
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+/// Owns a secret and wipes it when dropped, as `zeroize::Zeroizing` does for the types zeroize knows.
+#[derive(Clone, Default)]
+pub struct Secret<T: Wipe>(T);
+
+impl<T: Wipe> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+// never prints the secret
+impl<T: Wipe> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+impl<T: Wipe> Deref for Secret<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Wipe> DerefMut for Secret<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Wipe> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+// GMP's __mpz_struct, which a curv BigInt is a wrapper around: a rust-gmp Mpz and nothing else
+#[repr(C)]
+struct RawMpz {
+    alloc: c_int,
+    size: c_int,
+    // mp_limb_t, whose width is only known from the GMP linked in
+    limbs: *mut u8,
+}
+
+const _: () = assert!(size_of::<BigInt>() == size_of::<RawMpz>());
+
+impl Wipe for BigInt {
+    fn wipe(&mut self) {
+        // SAFETY: a BigInt only holds an mpz_t, so as they have the same size it is at offset 0 of it. GMP
+        // allocated `alloc` limbs of __gmp_bits_per_limb bits for it, and a size of 0 leaves a valid integer, 0,
+        // to free as usual.
+        unsafe {
+            let raw = &mut *(self as *mut BigInt as *mut RawMpz);
+            if raw.alloc > 0 {
+                let limb_bytes = __gmp_bits_per_limb as usize / 8;
+                std::slice::from_raw_parts_mut(raw.limbs, raw.alloc as usize * limb_bytes).zeroize();
+            }
+            raw.size = 0;
+        }
+    }
+}
+
+impl<T: Wipe> Wipe for Vec<T> {
+    fn wipe(&mut self) {
+        self.iter_mut().for_each(Wipe::wipe);
+    }
+}
+
+impl Wipe for () {
+    fn wipe(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curv::arithmetic::traits::BasicOps;
+
+    #[test]
+    fn test_wipe_leaves_zero() {
+        let mut x = BigInt::from(3).pow(4096);
+        x.wipe();
+        assert_eq!(x, BigInt::from(0));
+        // still a GMP integer that computes and frees as usual
+        x = &x + BigInt::from(7);
+        assert_eq!(x, BigInt::from(7));
+        let secret = Secret::new(vec![BigInt::from(-5), BigInt::from(0)]);
+        assert_eq!(secret[0], BigInt::from(-5));
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use curv::arithmetic::{BigInt, Modulo};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ProtocolError;
+use crate::secret::{Secret, Wipe};
+
+/// This party's additive shares (mod n) of the result of one computation, element-wise.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SharedValue {
+    pub n: BigInt,
+    pub shares: Vec<BigInt>,
+    // fixed-point scale of the shared value
+    pub scale: u32,
+    // |value| < 2^value_bits, tracked to refuse multiplications that could wrap around n
+    pub value_bits: usize,
+}
+
+// n is public, only the shares are secret
+impl Wipe for SharedValue {
+    fn wipe(&mut self) {
+        self.shares.wipe();
+    }
+}
+
+/// Local share storage, keyed by computation id, so results can be consumed by later computations.
+#[derive(Default)]
+pub struct ShareStore {
+    // wiped when replaced, removed or dropped
+    values: HashMap<usize, Secret<SharedValue>>,
+}
+
+impl ShareStore {
+    pub fn insert(&mut self, computation_id: usize, value: SharedValue) {
+        self.values.insert(computation_id, Secret::new(value));
+    }
+
+    /// Wipe the shares of a computation, once a later one used them up.
+    pub fn remove(&mut self, computation_id: usize) {
+        self.values.remove(&computation_id);
+    }
+
+    pub fn get(&self, computation_id: usize) -> Result<&SharedValue, ProtocolError> {
+        self.values.get(&computation_id).map(|value| &**value).ok_or(ProtocolError::UnknownComputation(computation_id))
+    }
+
+    /// Operands of a binary operation. Both must be shared under the same key and have the same length.
+    pub fn operands(&self, lhs: usize, rhs: usize) -> Result<(&SharedValue, &SharedValue), ProtocolError> {
+        let (a, b) = (self.get(lhs)?, self.get(rhs)?);
+        if a.n != b.n || a.shares.len() != b.shares.len() {
+            return Err(ProtocolError::IncompatibleShares { lhs, rhs });
+        }
+        Ok((a, b))
+    }
+
+    /// [a] + [b] is just the sum of the local shares.
+    pub fn add(&self, lhs: usize, rhs: usize) -> Result<SharedValue, ProtocolError> {
+        let (a, b) = self.operands(lhs, rhs)?;
+        if a.scale != b.scale {
+            return Err(ProtocolError::ScaleMismatch { lhs: a.scale, rhs: b.scale });
+        }
+        Ok(SharedValue {
+            n: a.n.clone(),
+            shares: a.shares.iter().zip(&b.shares).map(|(x, y)| BigInt::mod_add(x, y, &a.n)).collect(),
+            scale: a.scale,
+            value_bits: a.value_bits.max(b.value_bits) + 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(n: &BigInt, shares: &[i32], scale: u32) -> SharedValue {
+        SharedValue { n: n.clone(), shares: shares.iter().map(|s| BigInt::from(*s)).collect(), scale, value_bits: 8 }
+    }
+
+    #[test]
+    fn test_add_shares() {
+        let n = BigInt::from(101);
+        let mut store = ShareStore::default();
+        store.insert(0, value(&n, &[60, 5], 2));
+        store.insert(1, value(&n, &[50, 7], 2));
+        store.insert(2, value(&n, &[1, 2], 4));
+        store.insert(3, value(&n, &[1], 2));
+
+        let sum = store.add(0, 1).unwrap();
+        assert_eq!(sum.shares, vec![BigInt::from(9), BigInt::from(12)]);
+        assert_eq!(sum.value_bits, 9);
+        assert_eq!(store.add(0, 2).unwrap_err(), ProtocolError::ScaleMismatch { lhs: 2, rhs: 4 });
+        assert_eq!(store.add(0, 3).unwrap_err(), ProtocolError::IncompatibleShares { lhs: 0, rhs: 3 });
+        assert_eq!(store.add(0, 4).unwrap_err(), ProtocolError::UnknownComputation(4));
+    }
+}
@@ -0,0 +1,280 @@
+//! Shamir secret sharing over a prime field: a secret is the constant term of a random polynomial of degree
+//! threshold - 1, and party i holds its value at x = i + 1. Any threshold shares give the secret back by
+//! Lagrange interpolation at 0, fewer say nothing about it.
+//!
+//! Shares are linear: the sum of two parties' shares, or a share times a public constant, is a share of the sum
+//! or of the multiple with the same threshold. Over the field of the secp256k1 group order a dealer can also
+//! commit to its polynomial, so that every party checks its share before using it: Feldman commitments reveal
+//! secret·G, Pedersen commitments hide the secret behind a second random polynomial.
+use curv::arithmetic::traits::{Modulo, Primes};
+use curv::elliptic::curves::{Point, Scalar, Secp256k1};
+use kzen_paillier::BigInt;
+use serde::{Deserialize, Serialize};
+
+use crate::rng;
+
+// rounds of Miller-Rabin before a modulus is taken for a prime
+const PRIMALITY_ROUNDS: u32 = 40;
+
+/// The value at `x` of the polynomial sharing a secret.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Share {
+    pub x: BigInt,
+    pub y: BigInt,
+}
+
+/// The point party `sid` holds its shares at.
+pub fn point(sid: usize) -> BigInt {
+    BigInt::from(sid as u64 + 1)
+}
+
+/// The integers mod a prime, where shares and secrets live.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    p: BigInt,
+}
+
+impl Field {
+    /// None unless `p` is a (probable) prime.
+    pub fn new(p: BigInt) -> Option<Self> {
+        p.is_probable_prime(PRIMALITY_ROUNDS).then_some(Field { p })
+    }
+
+    /// The field of the secp256k1 scalars, the only one commitments work in.
+    pub fn secp256k1() -> Self {
+        Field { p: Scalar::<Secp256k1>::group_order().clone() }
+    }
+
+    pub fn modulus(&self) -> &BigInt {
+        &self.p
+    }
+
+    /// Whether `x` is an element of the field, in [0, p).
+    pub fn contains(&self, x: &BigInt) -> bool {
+        *x >= BigInt::from(0) && *x < self.p
+    }
+
+    /// Share `secret` among the parties `sids`, so that any `threshold` of them can reconstruct it. The shares
+    /// are in the order of `sids`.
+    pub fn share(&self, secret: &BigInt, threshold: usize, sids: &[usize]) -> Vec<Share> {
+        let coefficients = self.polynomial(secret, threshold, sids);
+        self.evaluate(&coefficients, sids)
+    }
+
+    /// The secret shared by `shares`, interpolated at 0. It is only the right one if there are at least as many
+    /// shares as the threshold. None if there are no shares or two of them are at the same point.
+    pub fn reconstruct(&self, shares: &[Share]) -> Option<BigInt> {
+        self.interpolate(shares, &BigInt::from(0))
+    }
+
+    /// The value at `x` of the lowest degree polynomial through `shares`.
+    pub fn interpolate(&self, shares: &[Share], x: &BigInt) -> Option<BigInt> {
+        let points: Vec<BigInt> = shares.iter().map(|share| share.x.clone()).collect();
+        let coefficients = self.lagrange_coefficients(&points, x)?;
+        Some(shares.iter().zip(&coefficients).fold(BigInt::from(0), |acc, (share, c)| {
+            BigInt::mod_add(&acc, &BigInt::mod_mul(&share.y, c, &self.p), &self.p)
+        }))
+    }
+
+    /// The Lagrange basis polynomials of `points` evaluated at `x`: the value at `x` of any polynomial of degree
+    /// below `points.len()` is the sum of its values at `points` times these. None if there are no points or
+    /// two of them are equal.
+    pub fn lagrange_coefficients(&self, points: &[BigInt], x: &BigInt) -> Option<Vec<BigInt>> {
+        if points.is_empty() {
+            return None;
+        }
+        points
+            .iter()
+            .enumerate()
+            .map(|(i, xi)| {
+                // prod over j != i of (x - x_j) / (x_i - x_j)
+                let (mut numerator, mut denominator) = (BigInt::from(1), BigInt::from(1));
+                for (j, xj) in points.iter().enumerate() {
+                    if i != j {
+                        numerator = BigInt::mod_mul(&numerator, &BigInt::mod_sub(x, xj, &self.p), &self.p);
+                        denominator = BigInt::mod_mul(&denominator, &BigInt::mod_sub(xi, xj, &self.p), &self.p);
+                    }
+                }
+                Some(BigInt::mod_mul(&numerator, &BigInt::mod_inv(&denominator, &self.p)?, &self.p))
+            })
+            .collect()
+    }
+
+    /// The share of the sum of two secrets, from shares at the same point. None if the points differ.
+    pub fn add(&self, a: &Share, b: &Share) -> Option<Share> {
+        (a.x == b.x).then(|| Share { x: a.x.clone(), y: BigInt::mod_add(&a.y, &b.y, &self.p) })
+    }
+
+    /// The share of `k` times the secret.
+    pub fn scalar_mul(&self, share: &Share, k: &BigInt) -> Share {
+        Share { x: share.x.clone(), y: BigInt::mod_mul(&share.y, &BigInt::modulus(k, &self.p), &self.p) }
+    }
+
+    // coefficients of a random polynomial of degree threshold - 1 through (0, secret), lowest first
+    fn polynomial(&self, secret: &BigInt, threshold: usize, sids: &[usize]) -> Vec<BigInt> {
+        assert!(threshold >= 1 && threshold <= sids.len(), "cannot share among {} parties with threshold {}", sids.len(), threshold);
+        // the points must be distinct and non-zero in the field
+        assert!(point(sids.iter().copied().max().unwrap_or(0)) < self.p, "cannot share among {} parties mod {}", sids.len(), self.p);
+        let mut coefficients = vec![BigInt::modulus(secret, &self.p)];
+        coefficients.extend((1..threshold).map(|_| rng::sample_below(&self.p)));
+        coefficients
+    }
+
+    fn evaluate(&self, coefficients: &[BigInt], sids: &[usize]) -> Vec<Share> {
+        sids.iter()
+            .map(|&sid| {
+                let x = point(sid);
+                // Horner's rule from the highest coefficient down
+                let y = coefficients
+                    .iter()
+                    .rev()
+                    .fold(BigInt::from(0), |acc, c| BigInt::mod_add(&BigInt::mod_mul(&acc, &x, &self.p), c, &self.p));
+                Share { x, y }
+            })
+            .collect()
+    }
+}
+
+/// Feldman commitments a_j·G to the coefficients of a sharing polynomial over `Field::secp256k1()`. The first
+/// one is secret·G.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Feldman(pub Vec<Point<Secp256k1>>);
+
+/// Pedersen commitments a_j·G + b_j·H to the coefficients of a sharing polynomial and of a blinding one, where
+/// H is curv's second generator, whose discrete log nobody knows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pedersen(pub Vec<Point<Secp256k1>>);
+
+/// Share `secret` like `Field::share` over `Field::secp256k1()`, with Feldman commitments to the polynomial.
+pub fn share_feldman(secret: &BigInt, threshold: usize, sids: &[usize]) -> (Vec<Share>, Feldman) {
+    let field = Field::secp256k1();
+    let coefficients = field.polynomial(secret, threshold, sids);
+    let commitments = coefficients.iter().map(|a| Point::generator() * Scalar::from_bigint(a)).collect();
+    (field.evaluate(&coefficients, sids), Feldman(commitments))
+}
+
+/// Share `secret` like `Field::share` over `Field::secp256k1()`, with the shares of a random blinding
+/// polynomial and Pedersen commitments to both. Every party needs its share and its blinding share to verify.
+pub fn share_pedersen(secret: &BigInt, threshold: usize, sids: &[usize]) -> (Vec<Share>, Vec<Share>, Pedersen) {
+    let field = Field::secp256k1();
+    let coefficients = field.polynomial(secret, threshold, sids);
+    let blinding = field.polynomial(&rng::sample_below(field.modulus()), threshold, sids);
+    let commitments = coefficients
+        .iter()
+        .zip(&blinding)
+        .map(|(a, b)| Point::generator() * Scalar::from_bigint(a) + Point::base_point2() * Scalar::from_bigint(b))
+        .collect();
+    (field.evaluate(&coefficients, sids), field.evaluate(&blinding, sids), Pedersen(commitments))
+}
+
+impl Feldman {
+    /// Whether `share` is on the committed polynomial.
+    pub fn verify(&self, share: &Share) -> bool {
+        !self.0.is_empty() && Point::generator() * Scalar::from_bigint(&share.y) == evaluate_in_exponent(&self.0, &share.x)
+    }
+
+    /// The commitments to the sum of the two committed polynomials, which the sums of the shares are on.
+    pub fn add(&self, other: &Feldman) -> Feldman {
+        Feldman(add_commitments(&self.0, &other.0))
+    }
+
+    /// The commitments to `k` times the committed polynomial.
+    pub fn scalar_mul(&self, k: &BigInt) -> Feldman {
+        let k = Scalar::from_bigint(k);
+        Feldman(self.0.iter().map(|c| c * &k).collect())
+    }
+}
+
+impl Pedersen {
+    /// Whether `share` and `blinding` are on the committed polynomials.
+    pub fn verify(&self, share: &Share, blinding: &Share) -> bool {
+        let committed = Point::generator() * Scalar::from_bigint(&share.y) + Point::base_point2() * Scalar::from_bigint(&blinding.y);
+        !self.0.is_empty() && share.x == blinding.x && committed == evaluate_in_exponent(&self.0, &share.x)
+    }
+
+    /// The commitments to the sums of the two pairs of committed polynomials.
+    pub fn add(&self, other: &Pedersen) -> Pedersen {
+        Pedersen(add_commitments(&self.0, &other.0))
+    }
+
+    /// The commitments to `k` times the committed polynomials.
+    pub fn scalar_mul(&self, k: &BigInt) -> Pedersen {
+        let k = Scalar::from_bigint(k);
+        Pedersen(self.0.iter().map(|c| c * &k).collect())
+    }
+}
+
+// sum over j of x^j·C_j, by Horner's rule
+fn evaluate_in_exponent(commitments: &[Point<Secp256k1>], x: &BigInt) -> Point<Secp256k1> {
+    let x = Scalar::from_bigint(x);
+    commitments.iter().rev().fold(Point::zero(), |acc, c| acc * &x + c)
+}
+
+// the polynomials may have different degrees, the missing coefficients are 0
+fn add_commitments(a: &[Point<Secp256k1>], b: &[Point<Secp256k1>]) -> Vec<Point<Secp256k1>> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    long.iter().enumerate().map(|(j, c)| short.get(j).map_or_else(|| c.clone(), |d| c + d)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_any_threshold_shares_reconstruct() {
+        let field = Field::new(BigInt::from(2_147_483_647)).unwrap();
+        let secret = BigInt::from(123_456_789);
+        let shares = field.share(&secret, 3, &[0, 1, 2, 3, 4]);
+        assert_eq!(shares[4].x, BigInt::from(5));
+        for subset in [[0, 1, 2], [1, 3, 4], [4, 2, 0]] {
+            let chosen: Vec<Share> = subset.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(field.reconstruct(&chosen), Some(secret.clone()));
+            // any three shares give the fourth one back
+            assert_eq!(field.interpolate(&chosen, &BigInt::from(4)), Some(shares[3].y.clone()));
+        }
+        assert_eq!(field.reconstruct(&shares), Some(secret.clone()));
+        // two shares of a degree 2 polynomial interpolate another line
+        assert_ne!(field.reconstruct(&shares[..2]), Some(secret));
+        assert_eq!(field.reconstruct(&[shares[0].clone(), shares[0].clone()]), None);
+        assert_eq!(field.reconstruct(&[]), None);
+        assert_eq!(Field::new(BigInt::from(2_147_483_649u64)), None);
+    }
+
+    #[test]
+    fn test_shares_are_linear() {
+        let field = Field::new(BigInt::from(1_000_003)).unwrap();
+        let sids = [0, 1, 2, 3];
+        let a = field.share(&BigInt::from(1_000_000), 2, &sids);
+        let b = field.share(&BigInt::from(17), 2, &sids);
+        let combined: Vec<Share> = a
+            .iter()
+            .zip(&b)
+            .map(|(a, b)| field.add(&field.scalar_mul(a, &BigInt::from(-3)), b).unwrap())
+            .collect();
+        // -3 * 1000000 + 17 mod 1000003
+        assert_eq!(field.reconstruct(&combined[2..]), Some(BigInt::from(26)));
+        assert_eq!(field.add(&a[0], &b[1]), None);
+    }
+
+    #[test]
+    fn test_commitments_verify_shares() {
+        let sids = [0, 1, 2];
+        let (shares, feldman) = share_feldman(&BigInt::from(42), 2, &sids);
+        assert_eq!(feldman.0[0], Point::generator() * Scalar::from(42));
+        assert!(shares.iter().all(|share| feldman.verify(share)));
+        let mut forged = shares[1].clone();
+        forged.y += 1;
+        assert!(!feldman.verify(&forged));
+
+        let (other, other_feldman) = share_feldman(&BigInt::from(8), 3, &sids);
+        let field = Field::secp256k1();
+        let sum = field.add(&field.scalar_mul(&shares[2], &BigInt::from(2)), &other[2]).unwrap();
+        assert!(feldman.scalar_mul(&BigInt::from(2)).add(&other_feldman).verify(&sum));
+
+        let (shares, blinding, pedersen) = share_pedersen(&BigInt::from(42), 2, &sids);
+        assert!(shares.iter().zip(&blinding).all(|(share, r)| pedersen.verify(share, r)));
+        assert!(!pedersen.verify(&shares[0], &blinding[1]));
+        assert!(!pedersen.verify(&forged, &blinding[1]));
+        assert_eq!(field.reconstruct(&shares[1..]), Some(BigInt::from(42)));
+    }
+}
@@ -0,0 +1,183 @@
+use curv::arithmetic::Modulo;
+use kzen_paillier::BigInt;
+
+use crate::coordinator::{Coordinator, ServerAction, SessionConfig, SessionEnd};
+use crate::encoding::FixedPoint;
+use crate::errors::ProtocolError;
+use crate::party::{Party, SecretStore};
+use crate::protocol::InputSource;
+pub use crate::protocol::PrivateInputs;
+use crate::transport::{Envelope, MemoryTransport, Transport};
+use crate::types::{Computation, WebsocketMessage};
+use crate::validation::Policy;
+
+/// Runs a whole session in one process: the server's coordinator and one party per input, exchanging the
+/// same JSON messages as over websockets through a transport. A client whose connection drops reconnects once
+/// nothing else is in flight, within the grace period unless the simulator runs `without_resume`.
+pub struct Simulator<T: Transport = MemoryTransport> {
+    coordinator: Coordinator,
+    parties: Vec<Party>,
+    transport: T,
+    transcript: Vec<Envelope>,
+    // clients whose connection dropped, and whether they reconnect before the grace period is over
+    down: Vec<usize>,
+    resume: bool,
+}
+
+/// State of every party once the session is over.
+pub struct SimulationReport {
+    // how the server ended the session, None if messages stopped flowing before it did
+    pub end: Option<SessionEnd>,
+    // indexed by sid
+    pub parties: Vec<Party>,
+    // every message delivered, in order
+    pub transcript: Vec<Envelope>,
+}
+
+impl Simulator {
+    /// The simulated parties accept the session's own security profile, however weak.
+    pub fn new<I: InputSource + 'static>(config: SessionConfig, plan: Vec<Computation>, inputs: Vec<I>) -> Self {
+        let policy = Policy { min_profile: config.profile };
+        Simulator {
+            coordinator: Coordinator::new(config, plan),
+            parties: inputs.into_iter().map(|i| Party::new(Box::new(i)).with_policy(policy)).collect(),
+            transport: MemoryTransport::default(),
+            transcript: Vec::new(),
+            down: Vec::new(),
+            resume: true,
+        }
+    }
+}
+
+impl<T: Transport> Simulator<T> {
+    /// Have every party accept sessions and keys according to this policy.
+    pub fn with_policy(self, policy: Policy) -> Self {
+        let parties = self.parties.into_iter().map(|party| party.with_policy(policy)).collect();
+        Simulator { parties, ..self }
+    }
+
+    /// Have party `sid` keep its key pairs and output shares in this store, and reuse the key pairs in it.
+    pub fn with_store(mut self, sid: usize, store: Box<dyn SecretStore>) -> Self {
+        let party = self.parties.remove(sid);
+        self.parties.insert(sid, party.with_store(store));
+        self
+    }
+
+    /// Carry the messages over another transport.
+    pub fn with_transport<U: Transport>(self, transport: U) -> Simulator<U> {
+        let Simulator { coordinator, parties, transcript, down, resume, .. } = self;
+        Simulator { coordinator, parties, transport, transcript, down, resume }
+    }
+
+    /// Clients whose connection drops never come back, so the server gives up on them.
+    pub fn without_resume(self) -> Self {
+        Simulator { resume: false, ..self }
+    }
+
+    /// Deliver messages until the server ends the session. If nothing is left in flight while a computation is
+    /// still running, the server's timeout for it fires. Messages the server sent before closing the session
+    /// still reach the clients, e.g. the last checkpoint of the relay log, but their replies go nowhere.
+    pub fn run(mut self) -> SimulationReport {
+        let actions = self.coordinator.start(self.parties.len());
+        let mut end = self.perform(actions);
+        while end.is_none() {
+            if let Some(envelope) = self.transport.receive() {
+                end = self.deliver(envelope);
+            } else if !self.down.is_empty() {
+                end = self.reconnect();
+            } else {
+                break;
+            }
+            for sid in self.transport.dropped() {
+                self.coordinator.disconnected(sid);
+                self.down.push(sid);
+            }
+        }
+        if end.is_none() && let Some(epoch) = self.coordinator.epoch() {
+            let actions = self.coordinator.timeout(epoch);
+            end = self.perform(actions);
+        }
+        while let Some(envelope) = self.transport.receive() {
+            if let Envelope::ToClient { to, text } = &envelope {
+                self.parties[*to].handle_text(text);
+                self.transcript.push(envelope);
+            }
+        }
+        SimulationReport { end, parties: self.parties, transcript: self.transcript }
+    }
+
+    /// Have the server dial the clients whose connection dropped and resume the session with them, or give up on
+    /// them.
+    fn reconnect(&mut self) -> Option<SessionEnd> {
+        for sid in std::mem::take(&mut self.down) {
+            if !self.resume {
+                let actions = self.coordinator.lost(sid);
+                if let Some(end) = self.perform(actions) {
+                    return Some(end);
+                }
+                continue;
+            }
+            self.transport.reconnect(sid);
+            if let Some(request) = self.coordinator.resume_request(sid) {
+                self.transport.send(Envelope::ToClient { to: sid, text: request.to_string() });
+            }
+        }
+        None
+    }
+
+    fn deliver(&mut self, envelope: Envelope) -> Option<SessionEnd> {
+        self.transcript.push(envelope.clone());
+        match envelope {
+            Envelope::ToServer { from, text } => match serde_json::from_str::<WebsocketMessage>(&text) {
+                Ok(msg) => {
+                    let actions = self.coordinator.handle_message(msg, from);
+                    self.perform(actions)
+                }
+                Err(e) => {
+                    println!("Failed to parse message: {}", e);
+                    None
+                }
+            },
+            Envelope::ToClient { to, text } => {
+                for msg in self.parties[to].handle_text(&text) {
+                    match serde_json::to_string(&msg) {
+                        Ok(text) => self.transport.send(Envelope::ToServer { from: to, text }),
+                        Err(e) => eprintln!("Failed to serialize message: {}", e),
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    fn perform(&mut self, actions: Vec<ServerAction>) -> Option<SessionEnd> {
+        for action in actions {
+            match action {
+                ServerAction::Send { to, message } => self.transport.send(Envelope::ToClient { to, text: message.to_string() }),
+                ServerAction::Close(end) => return Some(end),
+            }
+        }
+        None
+    }
+}
+
+impl SimulationReport {
+    /// The result of a computation as reconstructed in the reveal phase by the first party that learned it.
+    pub fn result(&self, computation_id: usize) -> Option<&[String]> {
+        self.parties.iter().find_map(|p| p.result(computation_id))
+    }
+
+    /// Reconstruct a computation from the shares of every party, whatever its reveal mode.
+    pub fn reconstruct(&self, computation_id: usize) -> Result<Vec<String>, ProtocolError> {
+        let values = self.parties.iter().map(|p| p.shares().get(computation_id)).collect::<Result<Vec<_>, _>>()?;
+        let Some(first) = values.first() else {
+            return Ok(vec![]);
+        };
+        Ok((0..first.shares.len())
+            .map(|k| {
+                let sum = values.iter().fold(BigInt::from(0), |acc, v| BigInt::mod_add(&acc, &v.shares[k], &first.n));
+                FixedPoint::decode(&sum, &first.n, first.scale).to_string()
+            })
+            .collect())
+    }
+}
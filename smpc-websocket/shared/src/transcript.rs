@@ -0,0 +1,537 @@
+//! Structured transcripts of a session, for audits.
+//!
+//! The server records every frame it receives from or sends to a client, and a client may record the frames
+//! it exchanges with the server. Each frame becomes one JSON line with a timestamp, its sender and receiver,
+//! what kind of message it is and the SHA-256 of its text, the text itself only if asked to. `verify` replays a
+//! transcript to check that the relay behaved, and `cross_check` compares a client's transcript with the
+//! server's.
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::profile::SecurityProfile;
+use crate::relay_log::{RelayLog, VerifyingKey};
+use crate::transport::Envelope;
+use crate::types::{ClientMessage, CompletionReport, RelayedMessage, WebsocketMessage};
+
+/// One end of a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Endpoint {
+    Server,
+    // the client with this sid
+    Client(usize),
+}
+
+/// A frame of the session as recorded by one of its ends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    // position of the entry in the transcript, from 0
+    pub seq: u64,
+    // milliseconds since the UNIX epoch
+    pub timestamp: u64,
+    pub from: Endpoint,
+    pub to: Endpoint,
+    // variant of the WebsocketMessage or ClientMessage, "Close" for a close frame or "Malformed"
+    pub kind: String,
+    pub computation_id: Option<usize>,
+    pub round: Option<u32>,
+    // sid the message names: its sender, or its receiver for an InitializeProtocol
+    pub sid: Option<usize>,
+    // for a Unicast, the client the server is asked to relay it to
+    pub relay_to: Option<usize>,
+    // for an InitializeProtocol, the security profile the server announced
+    #[serde(default)]
+    pub profile: Option<SecurityProfile>,
+    // hex SHA-256 of the frame
+    pub hash: String,
+    // for a Unicast or Broadcast, hex SHA-256 of the message the server is asked to relay
+    pub relayed_hash: Option<String>,
+    // the frame itself, if the recorder keeps payloads
+    pub payload: Option<String>,
+}
+
+/// Hex SHA-256 of a frame.
+pub fn hash(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+impl TranscriptEntry {
+    /// Describe a text frame, client to server frames being WebsocketMessages and the others ClientMessages.
+    pub fn new(seq: u64, timestamp: u64, from: Endpoint, to: Endpoint, text: &str, keep_payload: bool) -> Self {
+        let mut entry = TranscriptEntry {
+            seq,
+            timestamp,
+            from,
+            to,
+            kind: "Malformed".to_string(),
+            computation_id: None,
+            round: None,
+            sid: None,
+            relay_to: None,
+            profile: None,
+            hash: hash(text),
+            relayed_hash: None,
+            payload: keep_payload.then(|| text.to_string()),
+        };
+        if to == Endpoint::Server {
+            if let Ok(msg) = serde_json::from_str::<WebsocketMessage>(text) {
+                entry.describe_websocket_message(msg);
+            }
+        } else if let Ok(msg) = serde_json::from_str::<ClientMessage>(text) {
+            entry.describe_client_message(msg);
+        }
+        entry
+    }
+
+    /// A close frame sent to a client, with its reason.
+    pub fn close(seq: u64, timestamp: u64, to: usize, reason: &str, keep_payload: bool) -> Self {
+        TranscriptEntry {
+            kind: "Close".to_string(),
+            ..TranscriptEntry::new(seq, timestamp, Endpoint::Server, Endpoint::Client(to), reason, keep_payload)
+        }
+    }
+
+    fn describe_websocket_message(&mut self, msg: WebsocketMessage) {
+        let relayed = match msg {
+            WebsocketMessage::Unicast(msg) => {
+                self.kind = "Unicast".to_string();
+                (self.sid, self.relay_to) = (Some(msg.from), Some(msg.to));
+                RelayedMessage::wrap(msg.from, msg.header, msg.data)
+            }
+            WebsocketMessage::Broadcast(msg) => {
+                self.kind = "Broadcast".to_string();
+                self.sid = Some(msg.from);
+                RelayedMessage::wrap(msg.from, msg.header, msg.data)
+            }
+            WebsocketMessage::Relayer(msg) => {
+                self.kind = "Relayer".to_string();
+                self.sid = Some(msg.from);
+                self.computation_id = serde_json::from_value::<CompletionReport>(msg.data).ok().map(|r| r.computation_id);
+                return;
+            }
+            WebsocketMessage::Abort(msg) => {
+                self.kind = "Abort".to_string();
+                self.sid = Some(msg.from);
+                return;
+            }
+            WebsocketMessage::Resumed(msg) => {
+                self.kind = "Resumed".to_string();
+                self.sid = Some(msg.from);
+                return;
+            }
+        };
+        // the server relays the data with its header as it serializes it, see Coordinator::handle_message
+        self.relayed_hash = Some(hash(&relayed.to_string()));
+        if let Ok(inner) = serde_json::from_value::<ClientMessage>(relayed) {
+            let sid = self.sid;
+            self.describe_client_message(inner);
+            self.sid = sid;
+        }
+    }
+
+    fn describe_client_message(&mut self, msg: ClientMessage) {
+        match msg {
+            ClientMessage::InitializeProtocol(init) => {
+                self.kind = "InitializeProtocol".to_string();
+                (self.computation_id, self.sid) = (Some(init.computation_id), Some(init.sid));
+                self.profile = Some(init.profile);
+            }
+            ClientMessage::Round(msg) => {
+                self.kind = "Round".to_string();
+                (self.computation_id, self.round, self.sid) = (Some(msg.computation_id), Some(msg.round), Some(msg.from));
+            }
+            ClientMessage::RevealShare(msg) => {
+                self.kind = "RevealShare".to_string();
+                (self.computation_id, self.sid) = (Some(msg.computation_id), Some(msg.sid));
+            }
+            ClientMessage::Checkpoint(msg) => {
+                self.kind = "Checkpoint".to_string();
+                self.computation_id = Some(msg.checkpoint.computation_id);
+            }
+            ClientMessage::Resume(request) => {
+                self.kind = "Resume".to_string();
+                self.sid = Some(request.sid);
+            }
+            ClientMessage::Dropout(msg) => {
+                self.kind = "Dropout".to_string();
+                (self.computation_id, self.sid) = (Some(msg.computation_id), Some(msg.sid));
+            }
+            // described as the message it carries
+            ClientMessage::Relayed(msg) => {
+                if let Ok(inner) = serde_json::from_value::<ClientMessage>(msg.data) {
+                    self.describe_client_message(inner);
+                }
+            }
+        }
+    }
+}
+
+/// Appends the entries of a transcript, one JSON line each, as frames are sent and received.
+pub struct Recorder {
+    out: Box<dyn Write + Send>,
+    seq: u64,
+    keep_payloads: bool,
+}
+
+impl Recorder {
+    pub fn new(out: Box<dyn Write + Send>, keep_payloads: bool) -> Self {
+        Recorder { out, seq: 0, keep_payloads }
+    }
+
+    /// Record to a new file, flushed after every entry so that nothing is lost if the process dies.
+    pub fn create(path: impl AsRef<Path>, keep_payloads: bool) -> io::Result<Self> {
+        Ok(Recorder::new(Box::new(LineWriter::new(File::create(path)?)), keep_payloads))
+    }
+
+    /// Record a text frame.
+    pub fn record(&mut self, from: Endpoint, to: Endpoint, text: &str) {
+        let entry = TranscriptEntry::new(self.seq, now(), from, to, text, self.keep_payloads);
+        self.write(entry);
+    }
+
+    /// Record the close frame the server sends a client.
+    pub fn record_close(&mut self, to: usize, reason: &str) {
+        let entry = TranscriptEntry::close(self.seq, now(), to, reason, self.keep_payloads);
+        self.write(entry);
+    }
+
+    fn write(&mut self, entry: TranscriptEntry) {
+        self.seq += 1;
+        let written = serde_json::to_string(&entry)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(self.out, "{}", line));
+        if let Err(e) = written {
+            eprintln!("Failed to record transcript entry {}: {}", entry.seq, e);
+        }
+    }
+}
+
+/// Read a transcript written by a Recorder.
+pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<TranscriptEntry>> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+/// The server's view of a simulated session, with payloads, from the messages the simulator delivered.
+pub fn from_envelopes(envelopes: &[Envelope]) -> Vec<TranscriptEntry> {
+    envelopes
+        .iter()
+        .zip(0..)
+        .map(|(envelope, seq)| match envelope {
+            Envelope::ToServer { from, text } => TranscriptEntry::new(seq, 0, Endpoint::Client(*from), Endpoint::Server, text, true),
+            Envelope::ToClient { to, text } => TranscriptEntry::new(seq, 0, Endpoint::Server, Endpoint::Client(*to), text, true),
+        })
+        .collect()
+}
+
+/// An inconsistency found in a transcript.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    // entries are not numbered 0, 1, 2...
+    OutOfOrder { seq: u64, expected: u64 },
+    // an entry is older than the one before it
+    TimeWentBack { seq: u64 },
+    // the recorded payload does not match the recorded hash
+    HashMismatch { seq: u64 },
+    // the frame could not be parsed
+    Malformed { seq: u64 },
+    // an entry is neither from nor to the server
+    NotThroughServer { seq: u64 },
+    // a client sent a message in the name of another sid
+    Impersonation { seq: u64, sender: usize, claimed: usize },
+    // the server started a computation on a client with another client's sid
+    WrongSid { seq: u64, client: usize, sid: usize },
+    // the server started a computation without announcing a security profile
+    MissingProfile { seq: u64 },
+    // the server announced another security profile than the one the session started with
+    ProfileChanged { seq: u64, profile: SecurityProfile },
+    // a message refers to a computation the server never started
+    UnknownComputation { seq: u64, computation_id: usize },
+    // a client asked for a message to be relayed to a client that is not part of the session
+    InvalidRecipient { seq: u64, to: usize },
+    // the server sent a client a message no client asked it to relay
+    Injected { seq: u64 },
+    // the server relayed a message to a client before an earlier one for the same client
+    Reordered { seq: u64, skipped: u64 },
+    // a message was never relayed to one of its recipients
+    NotRelayed { seq: u64, to: usize },
+    // a checkpoint sent to a client is not signed by the server, or does not match the frames the client was sent
+    BadCheckpoint { seq: u64, client: usize },
+    // the server started a new computation on a client without a checkpoint of the one before
+    MissingCheckpoint { seq: u64, client: usize, computation_id: usize },
+    // a client's transcript disagrees with the server's on a frame they exchanged
+    Diverges { sid: usize, client_seq: u64 },
+    // a client recorded a frame from the server that the server did not record sending
+    Missing { sid: usize, client_seq: u64 },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::OutOfOrder { seq, expected } => write!(f, "entry {} is out of order, expected {}", seq, expected),
+            Violation::TimeWentBack { seq } => write!(f, "entry {} is older than the one before it", seq),
+            Violation::HashMismatch { seq } => write!(f, "payload of entry {} does not match its hash", seq),
+            Violation::Malformed { seq } => write!(f, "entry {} is not a valid message", seq),
+            Violation::NotThroughServer { seq } => write!(f, "entry {} does not go through the server", seq),
+            Violation::Impersonation { seq, sender, claimed } => {
+                write!(f, "entry {}: client {} sent a message as client {}", seq, sender, claimed)
+            }
+            Violation::WrongSid { seq, client, sid } => write!(f, "entry {}: client {} was given sid {}", seq, client, sid),
+            Violation::MissingProfile { seq } => write!(f, "entry {} announces no security profile", seq),
+            Violation::ProfileChanged { seq, profile } => {
+                write!(f, "entry {} announces profile {}, not the one the session started with", seq, profile)
+            }
+            Violation::UnknownComputation { seq, computation_id } => {
+                write!(f, "entry {} refers to computation {}, which was never started", seq, computation_id)
+            }
+            Violation::InvalidRecipient { seq, to } => write!(f, "entry {} is addressed to client {}, not part of the session", seq, to),
+            Violation::Injected { seq } => write!(f, "entry {} was relayed without any client sending it", seq),
+            Violation::Reordered { seq, skipped } => write!(f, "entry {} was relayed before entry {}", seq, skipped),
+            Violation::NotRelayed { seq, to } => write!(f, "entry {} was never relayed to client {}", seq, to),
+            Violation::BadCheckpoint { seq, client } => write!(f, "entry {} is not a valid checkpoint for client {}", seq, client),
+            Violation::MissingCheckpoint { seq, client, computation_id } => {
+                write!(f, "entry {}: client {} got no checkpoint of computation {}", seq, client, computation_id)
+            }
+            Violation::Diverges { sid, client_seq } => {
+                write!(f, "entry {} of client {}'s transcript differs from the server's", client_seq, sid)
+            }
+            Violation::Missing { sid, client_seq } => {
+                write!(f, "entry {} of client {}'s transcript is missing from the server's", client_seq, sid)
+            }
+        }
+    }
+}
+
+// a message received by the server that it still has to relay
+struct Pending {
+    seq: u64,
+    hash: String,
+    recipients: Vec<usize>,
+}
+
+// the chains of the relay log rebuilt from the frames the server sent, to check its checkpoints against
+#[derive(Default)]
+struct Chains {
+    // key and session announced in the first InitializeProtocol
+    key: Option<VerifyingKey>,
+    session_id: Option<String>,
+    clients: HashMap<usize, RelayLog>,
+    // false once a frame was recorded without its payload, as the chains cannot be rebuilt any more
+    complete: bool,
+    // hashes of the frames sent to each client, to skip those sent again after a resume
+    sent: HashMap<usize, HashSet<String>>,
+    // computation each client was last started on, until a checkpoint confirms it
+    unconfirmed: HashMap<usize, usize>,
+}
+
+impl Chains {
+    fn new() -> Self {
+        Chains { complete: true, ..Default::default() }
+    }
+
+    /// Follow a frame the server sent a client, checking it if it is a checkpoint.
+    fn sent(&mut self, entry: &TranscriptEntry, client: usize, resumed: bool) -> Option<Violation> {
+        let seq = entry.seq;
+        if !self.sent.entry(client).or_default().insert(entry.hash.clone()) && resumed {
+            return None;
+        }
+        let message = entry.payload.as_ref().and_then(|payload| serde_json::from_str::<ClientMessage>(payload).ok());
+        match (entry.kind.as_str(), message) {
+            ("Checkpoint", message) => {
+                let started = self.unconfirmed.remove(&client);
+                let Some(ClientMessage::Checkpoint(signed)) = message else {
+                    // a checkpoint recorded without its payload can only be matched with its computation
+                    return (entry.payload.is_some() || started != entry.computation_id).then_some(Violation::BadCheckpoint { seq, client });
+                };
+                let chain = self.clients.get(&client).cloned().unwrap_or_default();
+                let valid = self.key.as_ref().is_some_and(|key| signed.verify(key))
+                    && self.session_id.as_ref() == Some(&signed.checkpoint.session_id)
+                    && started == Some(signed.checkpoint.computation_id)
+                    && (!self.complete || signed.checkpoint.clients.get(client) == Some(&chain));
+                return (!valid).then_some(Violation::BadCheckpoint { seq, client });
+            }
+            ("InitializeProtocol", Some(ClientMessage::InitializeProtocol(init))) => {
+                self.key = self.key.take().or(init.server_key.clone());
+                self.session_id = self.session_id.take().or(Some(init.session_id.clone()));
+            }
+            _ => {}
+        }
+        match &entry.payload {
+            Some(payload) => self.clients.entry(client).or_default().append(client, payload),
+            None => self.complete = false,
+        }
+        if entry.kind == "InitializeProtocol" && let Some(computation_id) = entry.computation_id {
+            let previous = self.unconfirmed.insert(client, computation_id);
+            if let Some(previous) = previous && previous != computation_id {
+                return Some(Violation::MissingCheckpoint { seq, client, computation_id: previous });
+            }
+        }
+        None
+    }
+}
+
+/// Replay the server's transcript of a session and return every inconsistency in it:
+///
+/// 1. entries are numbered in order, timestamps never go back and payloads match their hashes;
+/// 2. every client got the sid it was addressed as, and only ever sent messages under it, and every computation
+///    was announced under the same security profile;
+/// 3. messages only refer to computations the server started;
+/// 4. every message the server sent a client was relayed from a client, unchanged, in the order the server
+///    received it, and every message was relayed to all its recipients. Once the server resumed a session with a
+///    client, it may send the client again messages it already relayed to it;
+/// 5. every computation a client was started on is confirmed by a checkpoint before the next one, signed with the
+///    key announced in the first computation. If the payloads were kept, the head of the client's chain in each
+///    checkpoint must match the chain rebuilt from the frames the client was sent before it. The session's chain is
+///    not rebuilt: frames held for a disconnected client are only recorded once it resumes, out of their order in
+///    the chain.
+///
+/// The protocols carry no proofs of their own, so beyond the checkpoints there is nothing to check in the payloads.
+pub fn verify(entries: &[TranscriptEntry]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut started = HashSet::new();
+    let mut num_clients = 0;
+    let mut profile = None;
+    let mut pending: Vec<Pending> = Vec::new();
+    // hashes of the messages relayed to each client, and the clients the server resumed the session with
+    let mut relayed: HashMap<usize, HashSet<String>> = HashMap::new();
+    let mut resumed = HashSet::new();
+    // clients dropped from the session, which nothing is relayed to any more
+    let mut dropped = HashSet::new();
+    let mut chains = Chains::new();
+    let mut last_timestamp = 0;
+    for (entry, expected) in entries.iter().zip(0..) {
+        let seq = entry.seq;
+        if seq != expected {
+            violations.push(Violation::OutOfOrder { seq, expected });
+        }
+        if entry.timestamp < last_timestamp {
+            violations.push(Violation::TimeWentBack { seq });
+        }
+        last_timestamp = entry.timestamp;
+        if let Some(payload) = &entry.payload && hash(payload) != entry.hash {
+            violations.push(Violation::HashMismatch { seq });
+        }
+        if entry.kind == "Malformed" {
+            violations.push(Violation::Malformed { seq });
+            continue;
+        }
+        if entry.kind == "InitializeProtocol" && let Some(computation_id) = entry.computation_id {
+            started.insert(computation_id);
+        } else if let Some(computation_id) = entry.computation_id && !started.contains(&computation_id) {
+            violations.push(Violation::UnknownComputation { seq, computation_id });
+        }
+        if let (Endpoint::Server, Endpoint::Client(client)) = (entry.from, entry.to) && entry.kind != "Close" && entry.kind != "Resume" {
+            violations.extend(chains.sent(entry, client, resumed.contains(&client)));
+        }
+        match (entry.from, entry.to) {
+            (Endpoint::Client(sender), Endpoint::Server) => {
+                if let Some(claimed) = entry.sid && claimed != sender {
+                    violations.push(Violation::Impersonation { seq, sender, claimed });
+                }
+                let Some(relayed_hash) = &entry.relayed_hash else {
+                    continue;
+                };
+                let recipients: Vec<usize> = match entry.relay_to {
+                    Some(to) if to >= num_clients => {
+                        violations.push(Violation::InvalidRecipient { seq, to });
+                        continue;
+                    }
+                    Some(to) => vec![to],
+                    None => (0..num_clients).filter(|&to| to != sender).collect(),
+                };
+                let recipients = recipients.into_iter().filter(|to| !dropped.contains(to)).collect();
+                pending.push(Pending { seq, hash: relayed_hash.clone(), recipients });
+            }
+            (Endpoint::Server, Endpoint::Client(client)) if entry.kind == "InitializeProtocol" => {
+                if entry.sid != Some(client) {
+                    violations.push(Violation::WrongSid { seq, client, sid: entry.sid.unwrap_or(client) });
+                }
+                match (entry.profile, profile) {
+                    (None, _) => violations.push(Violation::MissingProfile { seq }),
+                    (Some(announced), None) => profile = Some(announced),
+                    (Some(announced), Some(first)) if announced != first => {
+                        violations.push(Violation::ProfileChanged { seq, profile: announced });
+                    }
+                    _ => {}
+                }
+                num_clients = num_clients.max(client + 1);
+            }
+            // the server's own messages
+            (Endpoint::Server, Endpoint::Client(_)) if entry.kind == "Close" || entry.kind == "Checkpoint" => {}
+            (Endpoint::Server, Endpoint::Client(client)) if entry.kind == "Resume" => {
+                resumed.insert(client);
+            }
+            // sent to every remaining client, the first one stops the relaying to the client that dropped
+            (Endpoint::Server, Endpoint::Client(_)) if entry.kind == "Dropout" => {
+                if let Some(sid) = entry.sid && dropped.insert(sid) {
+                    pending.iter_mut().for_each(|p| p.recipients.retain(|&to| to != sid));
+                    pending.retain(|p| !p.recipients.is_empty());
+                }
+            }
+            (Endpoint::Server, Endpoint::Client(client)) => {
+                let Some(found) = pending.iter().position(|p| p.hash == entry.hash && p.recipients.contains(&client)) else {
+                    let sent_again = resumed.contains(&client) && relayed.get(&client).is_some_and(|r| r.contains(&entry.hash));
+                    if !sent_again {
+                        violations.push(Violation::Injected { seq });
+                    }
+                    continue;
+                };
+                relayed.entry(client).or_default().insert(entry.hash.clone());
+                // the server relays every message as soon as it receives it, so nothing received earlier may still wait
+                if let Some(skipped) = pending[..found].iter().find(|p| p.recipients.contains(&client)) {
+                    violations.push(Violation::Reordered { seq, skipped: skipped.seq });
+                }
+                pending[found].recipients.retain(|&to| to != client);
+                if pending[found].recipients.is_empty() {
+                    pending.remove(found);
+                }
+            }
+            _ => violations.push(Violation::NotThroughServer { seq }),
+        }
+    }
+    for p in pending {
+        violations.extend(p.recipients.into_iter().map(|to| Violation::NotRelayed { seq: p.seq, to }));
+    }
+    violations
+}
+
+/// Compare the transcript a client recorded with the server's. The server must have sent the client exactly the
+/// frames the client received, and received the frames the client sent in the same order, except for those
+/// still in flight when the session was closed.
+pub fn cross_check(server: &[TranscriptEntry], client: &[TranscriptEntry]) -> Vec<Violation> {
+    let Some(sid) = client.iter().find_map(|e| match (e.from, e.to) {
+        (Endpoint::Client(sid), _) | (_, Endpoint::Client(sid)) => Some(sid),
+        _ => None,
+    }) else {
+        return vec![];
+    };
+    let me = Endpoint::Client(sid);
+    let mut violations = Vec::new();
+    for (from, to) in [(Endpoint::Server, me), (me, Endpoint::Server)] {
+        let on_server: Vec<&TranscriptEntry> = server.iter().filter(|e| (e.from, e.to) == (from, to) && e.kind != "Close").collect();
+        let on_client = client.iter().filter(|e| (e.from, e.to) == (from, to));
+        for (k, entry) in on_client.enumerate() {
+            match on_server.get(k) {
+                Some(theirs) if theirs.hash == entry.hash => {}
+                Some(_) => violations.push(Violation::Diverges { sid, client_seq: entry.seq }),
+                // frames the client sent may not have been read before the server closed the session
+                None if from == me => break,
+                None => violations.push(Violation::Missing { sid, client_seq: entry.seq }),
+            }
+        }
+    }
+    violations
+}
@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::transport::{Envelope, MemoryTransport, Transport};
+
+/// Which messages a scripted fault applies to. Occurrences count from 0, in the order messages are sent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    // the n-th message sent over the transport
+    Nth(usize),
+    // the n-th message the client with this sid sends to the server
+    FromClient { sid: usize, nth: usize },
+    // the n-th message the server sends to the client with this sid
+    ToClient { sid: usize, nth: usize },
+}
+
+/// What happens to a targeted message.
+#[derive(Debug, Clone, Copy)]
+pub enum Fault {
+    // the message is lost
+    Drop,
+    // the message is held back until this many other messages have been delivered, or until nothing else is
+    // in flight. Delay(1) swaps it with the next message.
+    Delay(usize),
+    // the message is delivered twice in a row
+    Duplicate,
+    // the message is cut in half, which no longer parses
+    Corrupt,
+    // the message is rewritten, e.g. to change a field while keeping it well-formed
+    Tamper(fn(&str) -> String),
+    // the connection of the client the message is from or to drops: the message, and every message to or from
+    // the client still in flight or sent before it reconnects, is lost
+    Disconnect,
+}
+
+/// Wraps a transport and applies a scripted schedule of faults to the messages it carries, to see how the
+/// server and the clients behave under an unreliable or adversarial relay.
+pub struct FaultyTransport<T: Transport = MemoryTransport> {
+    inner: T,
+    schedule: Vec<(Target, Fault)>,
+    // messages sent so far, in total and per client in each direction
+    sent: usize,
+    from_client: HashMap<usize, usize>,
+    to_client: HashMap<usize, usize>,
+    // delayed messages with the number of deliveries they still wait for
+    held: Vec<(usize, Envelope)>,
+    // every fault applied, with the message it was applied to
+    log: Vec<(Fault, Envelope)>,
+    // clients whose connection is down, and those of them the caller was not told about yet
+    down: HashSet<usize>,
+    dropped: Vec<usize>,
+}
+
+impl FaultyTransport {
+    pub fn new(schedule: Vec<(Target, Fault)>) -> Self {
+        FaultyTransport::wrap(MemoryTransport::default(), schedule)
+    }
+}
+
+impl<T: Transport> FaultyTransport<T> {
+    pub fn wrap(inner: T, schedule: Vec<(Target, Fault)>) -> Self {
+        FaultyTransport {
+            inner,
+            schedule,
+            sent: 0,
+            from_client: HashMap::new(),
+            to_client: HashMap::new(),
+            held: Vec::new(),
+            log: Vec::new(),
+            down: HashSet::new(),
+            dropped: Vec::new(),
+        }
+    }
+
+    /// Faults applied so far, in order.
+    pub fn log(&self) -> &[(Fault, Envelope)] {
+        &self.log
+    }
+
+    /// The fault scheduled for a message, counting it as sent.
+    fn fault_for(&mut self, envelope: &Envelope) -> Option<Fault> {
+        let nth = self.sent;
+        self.sent += 1;
+        let (sid, counter) = match envelope {
+            Envelope::ToServer { from, .. } => (*from, &mut self.from_client),
+            Envelope::ToClient { to, .. } => (*to, &mut self.to_client),
+        };
+        let count = counter.entry(sid).or_default();
+        let per_client = *count;
+        *count += 1;
+        self.schedule.iter().find_map(|(target, fault)| {
+            let hit = match (*target, envelope) {
+                (Target::Nth(n), _) => n == nth,
+                (Target::FromClient { sid: s, nth: n }, Envelope::ToServer { .. }) => s == sid && n == per_client,
+                (Target::ToClient { sid: s, nth: n }, Envelope::ToClient { .. }) => s == sid && n == per_client,
+                _ => false,
+            };
+            hit.then_some(*fault)
+        })
+    }
+}
+
+fn with_text(envelope: &Envelope, text: String) -> Envelope {
+    match envelope {
+        Envelope::ToServer { from, .. } => Envelope::ToServer { from: *from, text },
+        Envelope::ToClient { to, .. } => Envelope::ToClient { to: *to, text },
+    }
+}
+
+fn client(envelope: &Envelope) -> usize {
+    match envelope {
+        Envelope::ToServer { from, .. } => *from,
+        Envelope::ToClient { to, .. } => *to,
+    }
+}
+
+fn text(envelope: &Envelope) -> &str {
+    match envelope {
+        Envelope::ToServer { text, .. } | Envelope::ToClient { text, .. } => text,
+    }
+}
+
+impl<T: Transport> Transport for FaultyTransport<T> {
+    fn send(&mut self, envelope: Envelope) {
+        let fault = self.fault_for(&envelope);
+        if self.down.contains(&client(&envelope)) {
+            return self.log.push((Fault::Disconnect, envelope));
+        }
+        let Some(fault) = fault else {
+            return self.inner.send(envelope);
+        };
+        self.log.push((fault, envelope.clone()));
+        match fault {
+            Fault::Drop => {}
+            Fault::Delay(deliveries) => self.held.push((deliveries, envelope)),
+            Fault::Duplicate => {
+                self.inner.send(envelope.clone());
+                self.inner.send(envelope);
+            }
+            Fault::Corrupt => {
+                let text = text(&envelope);
+                let cut = (0..=text.len() / 2).rev().find(|&i| text.is_char_boundary(i)).unwrap_or(0);
+                let damaged = with_text(&envelope, text[..cut].to_string());
+                self.inner.send(damaged);
+            }
+            Fault::Tamper(rewrite) => {
+                let tampered = with_text(&envelope, rewrite(text(&envelope)));
+                self.inner.send(tampered);
+            }
+            Fault::Disconnect => {
+                let sid = client(&envelope);
+                self.down.insert(sid);
+                self.dropped.push(sid);
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Option<Envelope> {
+        loop {
+            let envelope = self.next()?;
+            if !self.down.contains(&client(&envelope)) {
+                return Some(envelope);
+            }
+            self.log.push((Fault::Disconnect, envelope));
+        }
+    }
+
+    fn dropped(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.dropped)
+    }
+
+    fn reconnect(&mut self, sid: usize) {
+        self.down.remove(&sid);
+    }
+}
+
+impl<T: Transport> FaultyTransport<T> {
+    // the next message to deliver, whether its connection is up or not
+    fn next(&mut self) -> Option<Envelope> {
+        if let Some(pos) = self.held.iter().position(|(wait, _)| *wait == 0) {
+            return Some(self.held.remove(pos).1);
+        }
+        match self.inner.receive() {
+            Some(envelope) => {
+                for (wait, _) in &mut self.held {
+                    *wait = wait.saturating_sub(1);
+                }
+                Some(envelope)
+            }
+            // nothing else in flight: the held messages arrive late, oldest first
+            None if !self.held.is_empty() => Some(self.held.remove(0).1),
+            None => None,
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+pub mod faulty;
+
+/// A message in flight between a client and the server, as the JSON text a websocket frame would carry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Envelope {
+    // a WebsocketMessage from the client with this sid
+    ToServer { from: usize, text: String },
+    // a ClientMessage for the client with this sid
+    ToClient { to: usize, text: String },
+}
+
+/// Carries the messages of a session between the clients and the relaying server.
+///
+/// The websocket actors have one socket per client for this; the simulator runs every party in one process
+/// and only needs somewhere to put messages until they are delivered.
+pub trait Transport {
+    fn send(&mut self, envelope: Envelope);
+
+    /// The next message to deliver, or None if nothing is in flight.
+    fn receive(&mut self) -> Option<Envelope>;
+
+    /// Clients whose connection dropped since the last call.
+    fn dropped(&mut self) -> Vec<usize> {
+        Vec::new()
+    }
+
+    /// Connect a client whose connection dropped again.
+    fn reconnect(&mut self, _sid: usize) {}
+}
+
+/// Reliable in-memory transport: every message is delivered once, in the order it was sent.
+pub struct MemoryTransport {
+    sender: Sender<Envelope>,
+    receiver: Receiver<Envelope>,
+}
+
+impl Default for MemoryTransport {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        MemoryTransport { sender, receiver }
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn send(&mut self, envelope: Envelope) {
+        // the receiver lives as long as the sender, so this cannot fail
+        let _ = self.sender.send(envelope);
+    }
+
+    fn receive(&mut self) -> Option<Envelope> {
+        self.receiver.try_recv().ok()
+    }
+}
@@ -1,4 +1,7 @@
 use kzen_paillier::BigInt;
+use crate::homomorphic::SchemeKind;
+use crate::profile::SecurityProfile;
+use crate::relay_log::{SignedCheckpoint, VerifyingKey};
 use serde::{Deserialize, Serialize};
 use actix::prelude::*;
 use serde_json::Value;
@@ -8,43 +11,166 @@ pub enum WebsocketMessage {
     Unicast(UnicastMessage<Value>),
     Broadcast(BroadcastMessage<Value>),
     Relayer(RelayerMessage<Value>),
+    Abort(AbortMessage),
+    // answer to the server's ResumeRequest once a dropped connection is back, see shared::resume
+    Resumed(ResumedMessage),
+}
+
+impl WebsocketMessage {
+    pub fn header(&self) -> &MessageHeader {
+        match self {
+            WebsocketMessage::Unicast(msg) => &msg.header,
+            WebsocketMessage::Broadcast(msg) => &msg.header,
+            WebsocketMessage::Relayer(msg) => &msg.header,
+            WebsocketMessage::Abort(msg) => &msg.header,
+            WebsocketMessage::Resumed(msg) => &msg.header,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ClientMessage{
     InitializeProtocol(InitializeProtocol),
-    FirstRoundResponse(FirstRoundResponse),
-    SecondRoundResponse(SecondRoundResponse),
+    Round(RoundMessage),
+    RevealShare(RevealShare),
+    // sent by the server to every client once a computation is over, see shared::relay_log
+    Checkpoint(SignedCheckpoint),
+    // a Round or RevealShare of another client, as the server relays it
+    Relayed(RelayedMessage),
+    // first frame the server sends on a new connection to a client whose connection dropped, see shared::resume
+    Resume(ResumeRequest),
+    // sent by the server to every remaining client when a client is dropped from the session for good
+    Dropout(Dropout),
 }
 
-// Sent from server to the first  client to initialize the protocol. the sid will always be 0 in this msg type.
-#[derive(Debug, Serialize, Deserialize)]
+/// Binds a message a client sends to the session, the computation and the sender's counter, see shared::replay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageHeader {
+    // random id the server announced for the session
+    pub session_id: String,
+    pub computation_id: usize,
+    // incremented by the sender with every message it sends in the session, from 1
+    pub seq: u64,
+}
+
+// The data of a Unicast or Broadcast, as the server relays it to the recipients along with its header.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayedMessage {
+    // sid of the client the server received the message from
+    pub from: usize,
+    pub header: MessageHeader,
+    pub data: Value,
+}
+
+impl RelayedMessage {
+    /// What the server sends the recipients of a Unicast or Broadcast it received from the client `from`.
+    pub fn wrap(from: usize, header: MessageHeader, data: Value) -> Value {
+        serde_json::to_value(ClientMessage::Relayed(RelayedMessage { from, header, data })).unwrap_or_else(|e| {
+            eprintln!("Failed to convert RelayedMessage to value: {}", e);
+            Value::Null
+        })
+    }
+}
+
+/// What happens to the output shares once a computation is over.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RevealMode {
+    // every party broadcasts its shares and everyone reconstructs the result
+    Public,
+    // every party sends its shares to the given party, the only one that learns the result
+    OutputParty(usize),
+    // shares stay with their owners for later composition
+    Private,
+}
+
+/// One step of the session plan run by the server. Results are always additive shares (mod n of the first
+/// client's key) stored by every party under the computation id, so they can be consumed by later computations.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Computation {
+    pub id: usize,
+    // id of the protocol in the registry, see shared::protocol
+    pub protocol: String,
+    // protocol specific parameters, e.g. which private input or which earlier results to use
+    pub params: Value,
+    pub reveal: RevealMode,
+}
+
+// Sent from server to every client to start a computation. The sid is the index of the receiving client.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InitializeProtocol{
     pub bits_security: usize,
+    // named security level of the session, bits_security is its modulus size
+    pub profile: SecurityProfile,
     pub num_parties: usize,
     pub sid: usize,
+    pub computation_id: usize,
+    pub protocol: String,
+    pub params: Value,
+    // encryption scheme every computation of the session runs on
+    #[serde(default)]
+    pub scheme: SchemeKind,
+    // public upper bound on the bit length of every party's private input.
+    pub max_input_bits: usize,
+    // number of decimal digits every party keeps after the point when encoding its input.
+    pub scale: u32,
+    // every party contributes a vector of this many elements and gets element-wise shares back.
+    pub vector_len: usize,
+    pub reveal: RevealMode,
+    // key the server signs its relay log checkpoints with
+    #[serde(default)]
+    pub server_key: Option<VerifyingKey>,
+    // random id of the session, which every message of the clients is bound to
+    #[serde(default)]
+    pub session_id: String,
+    // secret the server proves it is the same server with when it resumes the session after the connection
+    // to this client dropped, the same for every computation of the session
+    #[serde(default)]
+    pub resume_token: String,
 }
 
-// Sent from one cient to other clients. Every client will add 1 to its sid and send it to the next client. Server just relays this message.
-// Every client will check if it is the last client in the protocol by checking if its sid is equal to num_parties - 1.
-// If it is the last client, it will send the SecondRoundResponse message to the server.
-#[derive(Debug, Serialize, Deserialize)]
-pub struct FirstRoundResponse{
-    pub computed_value: BigInt,
-    pub num_parties: usize,
+// Asks a client whose connection dropped to go on with the session on this new connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumeRequest {
+    pub session_id: String,
+    pub sid: usize,
+    // the resume token of the client's InitializeProtocol
+    pub token: String,
+    // every message of the client up to this counter reached the server, the client sends the later ones again
+    pub last_seq: u64,
+}
+
+// A client whose connection was lost for good during a computation that tolerates dropouts. It takes no part
+// in the rest of the session.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dropout {
+    pub computation_id: usize,
     pub sid: usize,
-    // used to get the publick key of the first  
-    pub n_squared: BigInt,
-    pub n: BigInt,
 }
 
+// One round of a protocol, sent from one client to another and relayed by the server. The client hands the
+// payload to the protocol registered for the computation, which alone knows what it contains.
 #[derive(Debug, Serialize, Deserialize)]
-pub struct SecondRoundResponse{
-    pub computed_value: BigInt,
-    pub n_squared: BigInt,
-    pub num_parties: usize,
+pub struct RoundMessage{
+    pub computation_id: usize,
+    pub from: usize,
+    pub round: u32,
+    pub payload: Value,
+}
+
+// Sent by every party in the reveal phase, broadcast in Public mode or unicast to the output party.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevealShare{
+    pub computation_id: usize,
     pub sid: usize,
-    pub n: BigInt,
+    pub shares: Vec<BigInt>,
+}
+
+// Sent to the mediator (wrapped in a RelayerMessage) once a party is done with a computation. The server
+// moves to the next computation when every party has reported. The result is only filled in Public mode.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompletionReport{
+    pub computation_id: usize,
+    pub result: Option<Vec<String>>,
 }
 
 
@@ -64,12 +190,13 @@ pub trait OriginMessage {
 pub struct UnicastMessage<T> {
   pub from: usize,
   pub to: usize,
+  pub header: MessageHeader,
   pub data: T,
 }
 
 impl<T> UnicastMessage<T> {
-  pub fn new(from: usize, to: usize, data: T) -> Self {
-    Self { from, to, data }
+  pub fn new(from: usize, to: usize, header: MessageHeader, data: T) -> Self {
+    Self { from, to, header, data }
   }
 
   pub fn into_inner(self) -> T {
@@ -96,12 +223,13 @@ impl<T> OriginMessage for UnicastMessage<T> {
 pub struct RelayerMessage<T> {
   pub from: usize,
   pub to: (),
+  pub header: MessageHeader,
   pub data: T,
 }
 
 impl<T> RelayerMessage<T> {
-  pub fn new(from: usize, data: T) -> Self {
-    Self { from, to: (), data }
+  pub fn new(from: usize, header: MessageHeader, data: T) -> Self {
+    Self { from, to: (), header, data }
   }
 
   pub fn into_inner(self) -> T {
@@ -115,6 +243,58 @@ impl<T> OriginMessage for RelayerMessage<T> {
   }
 }
 
+///
+/// Message sent to the mediator when a party refuses to go on with the protocol.
+/// The server closes every websocket and resets the session.
+///
+#[derive(Debug, Clone, Serialize, Deserialize, Message)]
+#[serde(rename_all = "camelCase")]
+#[rtype(result = "()")]
+pub struct AbortMessage {
+  pub from: usize,
+  pub header: MessageHeader,
+  pub reason: String,
+  // party the sender holds responsible, e.g. the sender of a message it could not process
+  #[serde(default)]
+  pub blame: Option<usize>,
+}
+
+impl AbortMessage {
+  pub fn new(from: usize, header: MessageHeader, reason: impl Into<String>) -> Self {
+    Self { from, header, reason: reason.into(), blame: None }
+  }
+
+  pub fn blaming(self, party: Option<usize>) -> Self {
+    Self { blame: party, ..self }
+  }
+}
+
+impl OriginMessage for AbortMessage {
+  fn get_from(&self) -> usize {
+    self.from
+  }
+}
+
+///
+/// Message sent to the mediator when a client accepted a ResumeRequest. The server sends the client again
+/// every message after the first `received` it sent in the session.
+///
+#[derive(Debug, Clone, Serialize, Deserialize, Message)]
+#[serde(rename_all = "camelCase")]
+#[rtype(result = "()")]
+pub struct ResumedMessage {
+  pub from: usize,
+  pub header: MessageHeader,
+  // number of messages the client received from the server in the session, resume requests aside
+  pub received: u64,
+}
+
+impl OriginMessage for ResumedMessage {
+  fn get_from(&self) -> usize {
+    self.from
+  }
+}
+
 ///
 /// Message to send to or receive from ALL websockets
 ///
@@ -123,6 +303,7 @@ impl<T> OriginMessage for RelayerMessage<T> {
 #[rtype(result = "()")]
 pub struct BroadcastMessage<T> {
   pub from: usize,
+  pub header: MessageHeader,
   pub data: T,
 }
 
@@ -133,8 +314,8 @@ impl<T> OriginMessage for BroadcastMessage<T> {
 }
 
 impl<T> BroadcastMessage<T> {
-  pub fn new(from: usize, data: T) -> Self {
-    Self { from, data }
+  pub fn new(from: usize, header: MessageHeader, data: T) -> Self {
+    Self { from, header, data }
   }
 
   pub fn into_inner(self) -> T {
@@ -1,18 +1,25 @@
-use std::marker::PhantomData;
+use curv::arithmetic::traits::{BasicOps, BitManipulation};
+use kzen_paillier::BigInt;
 
-use kzen_paillier::*;
-use serde::{Deserialize, Serialize};
+use crate::errors::ProtocolError;
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct EncodedCiphertextRepr<T> {
-    #[serde(with = "kzen_paillier::serialize::bigint")]
-    pub raw: BigInt,
-    pub components: usize,
-    _phantom: PhantomData<T>,
+/// Check that the magnitude of a private input fits in the per-party bound agreed for the session.
+pub fn check_input_bound(input: &BigInt, max_input_bits: usize) -> Result<(), ProtocolError> {
+    let input_bits = input.abs().bit_length();
+    if input_bits > max_input_bits {
+        return Err(ProtocolError::InputOutOfRange { input_bits, max_input_bits });
+    }
+    Ok(())
 }
 
-pub fn get_bigint_from_encoded_ciphertext<T>(encoded: &EncodedCiphertext<T>) -> BigInt {
-    let encrypted_string = serde_json::to_string(&encoded).unwrap();
-    let deserialized: EncodedCiphertextRepr<u64> = serde_json::from_str(&encrypted_string).unwrap();
-    return deserialized.raw;
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_bound() {
+        assert!(check_input_bound(&BigInt::from(255), 8).is_ok());
+        assert!(check_input_bound(&BigInt::from(256), 8).is_err());
+        assert!(check_input_bound(&BigInt::from(-255), 8).is_ok());
+    }
+}
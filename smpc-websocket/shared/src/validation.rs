@@ -0,0 +1,139 @@
+//! Checks on everything a party computes on that another party or the server chose: the parameters of a
+//! computation, the first client's public key and every ciphertext received.
+//!
+//! A ciphertext of 0 or 1, or one that shares a factor with n, is not the encryption of anything under an honest
+//! key: computing on it could leak a party's input or silently corrupt the result. Each scheme checks its own keys
+//! and ciphertexts (`AdditiveHomomorphic::validate_public_key` and `validate_ciphertext`) with the helpers below.
+use curv::arithmetic::traits::{BitManipulation, EGCD};
+use kzen_paillier::BigInt;
+
+use crate::errors::ValidationError;
+use crate::homomorphic::AdditiveHomomorphic;
+use crate::profile::SecurityProfile;
+use crate::types::InitializeProtocol;
+
+/// What a party is willing to run a session with, whatever the server announces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Policy {
+    // weakest security profile a session may announce
+    pub min_profile: SecurityProfile,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy { min_profile: SecurityProfile::Standard2048 }
+    }
+}
+
+/// Check the parameters the server announced for a computation.
+pub fn check_init(init: &InitializeProtocol, policy: &Policy) -> Result<(), ValidationError> {
+    if init.num_parties < 2 {
+        return Err(ValidationError::TooFewParties(init.num_parties));
+    }
+    if init.sid >= init.num_parties {
+        return Err(ValidationError::InvalidSid { sid: init.sid, num_parties: init.num_parties });
+    }
+    if init.vector_len == 0 {
+        return Err(ValidationError::EmptyVector);
+    }
+    if init.profile < policy.min_profile {
+        return Err(ValidationError::BelowPolicy { profile: init.profile, min_profile: policy.min_profile });
+    }
+    if init.bits_security != init.profile.modulus_bits() {
+        return Err(ValidationError::ProfileMismatch { bits: init.bits_security, profile: init.profile });
+    }
+    Ok(())
+}
+
+/// Check that the sender of a round is another party of the computation.
+pub fn check_sender(init: &InitializeProtocol, from: usize) -> Result<(), ValidationError> {
+    if from >= init.num_parties || from == init.sid {
+        return Err(ValidationError::InvalidSender { from });
+    }
+    Ok(())
+}
+
+/// Check the shares of a result another party revealed: one per element of this party's own, each mod n.
+pub fn check_shares(shares: &[BigInt], len: usize, n: &BigInt) -> Result<(), ValidationError> {
+    if shares.len() != len {
+        return Err(ValidationError::WrongLength { expected: len, actual: shares.len() });
+    }
+    if shares.iter().any(|share| *share < BigInt::from(0) || share >= n) {
+        return Err(ValidationError::CiphertextOutOfRange);
+    }
+    Ok(())
+}
+
+/// A modulus made of two primes of security_bits / 2 bits each has security_bits or security_bits - 1 bits.
+pub fn check_modulus_size(n: &BigInt, security_bits: usize) -> Result<(), ValidationError> {
+    let bits = n.bit_length();
+    if bits > security_bits || bits + 1 < security_bits {
+        return Err(ValidationError::ModulusSize { bits, expected: security_bits });
+    }
+    Ok(())
+}
+
+/// Check that c is a non-trivial unit of Z_modulus, where modulus is a power of n.
+pub fn check_unit(c: &BigInt, n: &BigInt, modulus: &BigInt) -> Result<(), ValidationError> {
+    if *c <= BigInt::from(0) || c >= modulus {
+        return Err(ValidationError::CiphertextOutOfRange);
+    }
+    if *c == BigInt::from(1) {
+        return Err(ValidationError::TrivialCiphertext);
+    }
+    if BigInt::egcd(c, n).0 != BigInt::from(1) {
+        return Err(ValidationError::NotAUnit);
+    }
+    Ok(())
+}
+
+/// Check `expected` ciphertexts received from another party.
+pub fn check_ciphertexts<S: AdditiveHomomorphic>(pk: &S::PublicKey, ciphertexts: &[S::Ciphertext], expected: usize) -> Result<(), ValidationError> {
+    if ciphertexts.len() != expected {
+        return Err(ValidationError::WrongLength { expected, actual: ciphertexts.len() });
+    }
+    ciphertexts.iter().try_for_each(|c| S::validate_ciphertext(pk, c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homomorphic::{DamgardJurikScheme, ElGamalScheme, PaillierScheme};
+    use crate::homomorphic::damgard_jurik::DamgardJurikPublicKey;
+    use crate::homomorphic::ec_elgamal::ElGamalCiphertext;
+    use curv::elliptic::curves::{Point, Secp256k1};
+    use kzen_paillier::EncryptionKey;
+
+    #[test]
+    fn test_paillier_ciphertexts() {
+        let (pk, _) = PaillierScheme::keypair(512);
+        let c = PaillierScheme::encrypt(&pk, &BigInt::from(5));
+        assert_eq!(PaillierScheme::validate_public_key(&pk, 512), Ok(()));
+        assert_eq!(PaillierScheme::validate_ciphertext(&pk, &c), Ok(()));
+        assert_eq!(PaillierScheme::validate_ciphertext(&pk, &BigInt::from(0)), Err(ValidationError::CiphertextOutOfRange));
+        assert_eq!(PaillierScheme::validate_ciphertext(&pk, &BigInt::from(1)), Err(ValidationError::TrivialCiphertext));
+        assert_eq!(PaillierScheme::validate_ciphertext(&pk, &pk.nn), Err(ValidationError::CiphertextOutOfRange));
+        assert_eq!(PaillierScheme::validate_ciphertext(&pk, &(&pk.n * BigInt::from(3))), Err(ValidationError::NotAUnit));
+
+        let inconsistent = EncryptionKey { n: pk.n.clone(), nn: &pk.nn + BigInt::from(2) };
+        assert!(matches!(PaillierScheme::validate_public_key(&inconsistent, 512), Err(ValidationError::InconsistentKey(_))));
+        assert!(matches!(PaillierScheme::validate_public_key(&pk, 1024), Err(ValidationError::ModulusSize { expected: 1024, .. })));
+        assert!(check_ciphertexts::<PaillierScheme>(&pk, &[c], 2).is_err());
+    }
+
+    #[test]
+    fn test_other_schemes() {
+        let (pk, _) = DamgardJurikScheme::keypair(256);
+        let c = DamgardJurikScheme::encrypt(&pk, &BigInt::from(5));
+        assert_eq!(DamgardJurikScheme::validate_ciphertext(&pk, &c), Ok(()));
+        assert_eq!(DamgardJurikScheme::validate_ciphertext(&pk, &pk.n), Err(ValidationError::NotAUnit));
+        let huge_s = DamgardJurikPublicKey { s: 1000, ..pk };
+        assert!(DamgardJurikScheme::validate_public_key(&huge_s, 256).is_err());
+
+        let (pk, _) = ElGamalScheme::keypair(0);
+        let c = ElGamalScheme::encrypt(&pk, &BigInt::from(5));
+        assert_eq!(ElGamalScheme::validate_ciphertext(&pk, &c), Ok(()));
+        let trivial = ElGamalCiphertext { c1: Point::<Secp256k1>::zero(), c2: c.c2 };
+        assert_eq!(ElGamalScheme::validate_ciphertext(&pk, &trivial), Err(ValidationError::TrivialCiphertext));
+    }
+}
@@ -0,0 +1,336 @@
+//! A client's secrets kept on disk between sessions: its identity key, the key pairs it generated as first
+//! client, and its shares of the results, tagged by session and computation.
+//!
+//! The file is JSON with the entries encrypted under a key derived from a passphrase with PBKDF2-HMAC-SHA256,
+//! by XChaCha20-Poly1305 over a fresh random nonce, the header authenticated along with them. A wrong passphrase
+//! and a tampered file both fail the tag check and are not told apart. Every change is written to a temporary
+//! file first and moved over the vault, so a crash leaves the old vault or the new one.
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use std::fmt;
+
+use chacha20poly1305::aead::{AeadInPlace, KeyInit};
+use chacha20poly1305::{Tag, XChaCha20Poly1305, XNonce};
+use curv::arithmetic::traits::Converter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::errors::VaultError;
+use crate::homomorphic::SchemeKind;
+use crate::party::SecretStore;
+use crate::relay_log::SigningKey;
+use crate::rng;
+use crate::share_store::SharedValue;
+
+// version 1 encrypted with an HMAC-SHA256 keystream, and is not read any more
+const VERSION: u32 = 2;
+// separates the header of the vault from any other associated data
+const HEADER_DOMAIN: &[u8] = b"smpc vault";
+const SALT_BYTES: usize = 16;
+const NONCE_BYTES: usize = 24;
+const KEY_BYTES: usize = 32;
+const TAG_BYTES: usize = 16;
+
+/// PBKDF2 iterations of a new vault, as recommended by OWASP for PBKDF2-HMAC-SHA256.
+pub const DEFAULT_ITERATIONS: u32 = 600_000;
+/// Most PBKDF2 iterations a vault may ask for, so that a damaged file cannot keep the client busy for hours.
+pub const MAX_ITERATIONS: u32 = 10 * DEFAULT_ITERATIONS;
+
+pub const IDENTITY_ID: &str = "identity";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EntryKind {
+    // the client's long-term signing key
+    Identity,
+    // a key pair of a scheme, generated as first client
+    Keys,
+    // the client's shares of the result of a computation
+    Share,
+}
+
+/// One secret of the vault, its data as JSON. The data is wiped from memory when the entry is dropped.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    pub kind: EntryKind,
+    // seconds since the Unix epoch
+    pub created: u64,
+    pub data: String,
+}
+
+// the data is left out, like a Secret's
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Entry").field("id", &self.id).field("kind", &self.kind).field("created", &self.created).finish_non_exhaustive()
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        self.data.zeroize();
+    }
+}
+
+/// Id of the key pair of `scheme` with `bits`-bit keys, e.g. "keys/Paillier/2048".
+pub fn keys_id(scheme: SchemeKind, bits: usize) -> String {
+    format!("keys/{:?}/{}", scheme, bits)
+}
+
+/// Id of the shares of the result of a computation of a session, e.g. "share/3f2a.../0".
+pub fn share_id(session_id: &str, computation_id: usize) -> String {
+    format!("share/{}/{}", session_id, computation_id)
+}
+
+// the file on disk, binary fields in hex
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+    tag: String,
+}
+
+/// The vault of a client, decrypted in memory. Every change is written to disk at once.
+pub struct Vault {
+    path: PathBuf,
+    iterations: u32,
+    salt: [u8; SALT_BYTES],
+    key: Zeroizing<[u8; KEY_BYTES]>,
+    entries: Vec<Entry>,
+}
+
+impl Vault {
+    /// A new empty vault at `path`, which must not exist yet.
+    pub fn create(path: impl AsRef<Path>, passphrase: &str, iterations: u32) -> Result<Vault, VaultError> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            return Err(VaultError::Io(format!("{} already exists", path.display())));
+        }
+        check_iterations(iterations)?;
+        let salt = random_bytes::<SALT_BYTES>();
+        let key = derive_key(passphrase, &salt, iterations);
+        let vault = Vault { path, iterations, salt, key, entries: Vec::new() };
+        vault.save()?;
+        Ok(vault)
+    }
+
+    pub fn open(path: impl AsRef<Path>, passphrase: &str) -> Result<Vault, VaultError> {
+        let path = path.as_ref().to_path_buf();
+        let file: VaultFile = serde_json::from_str(&fs::read_to_string(&path)?)
+            .map_err(|e| VaultError::Corrupted(e.to_string()))?;
+        if file.version != VERSION {
+            return Err(VaultError::Corrupted(format!("unknown version {}", file.version)));
+        }
+        // before deriving anything: the count comes from the file, which nothing authenticated yet
+        check_iterations(file.iterations)?;
+        let salt: [u8; SALT_BYTES] = decode_fixed(&file.salt, "salt")?;
+        let nonce: [u8; NONCE_BYTES] = decode_fixed(&file.nonce, "nonce")?;
+        let tag: [u8; TAG_BYTES] = decode_fixed(&file.tag, "tag")?;
+        let mut plaintext = Zeroizing::new(decode_hex(&file.ciphertext)?);
+        let key = derive_key(passphrase, &salt, file.iterations);
+        XChaCha20Poly1305::new(key.as_ref().into())
+            .decrypt_in_place_detached(XNonce::from_slice(&nonce), &header(file.iterations, &salt), plaintext.as_mut(), Tag::from_slice(&tag))
+            .map_err(|_| VaultError::Unauthenticated)?;
+        let entries = serde_json::from_slice(&plaintext).map_err(|e| VaultError::Corrupted(e.to_string()))?;
+        Ok(Vault { path, iterations: file.iterations, salt, key, entries })
+    }
+
+    /// The vault at `path`, created with the default number of iterations if there is none yet.
+    pub fn open_or_create(path: impl AsRef<Path>, passphrase: &str) -> Result<Vault, VaultError> {
+        if path.as_ref().exists() {
+            Vault::open(path, passphrase)
+        } else {
+            Vault::create(path, passphrase, DEFAULT_ITERATIONS)
+        }
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    /// Add an entry, or replace the one with the same id.
+    pub fn put(&mut self, id: &str, kind: EntryKind, data: String) -> Result<(), VaultError> {
+        let created = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        self.entries.retain(|entry| entry.id != id);
+        self.entries.push(Entry { id: id.to_string(), kind, created, data });
+        self.save()
+    }
+
+    pub fn delete(&mut self, id: &str) -> Result<(), VaultError> {
+        let count = self.entries.len();
+        self.entries.retain(|entry| entry.id != id);
+        if self.entries.len() == count {
+            return Err(VaultError::UnknownEntry(id.to_string()));
+        }
+        self.save()
+    }
+
+    /// The client's identity key, generated and kept the first time it is asked for.
+    pub fn identity(&mut self) -> Result<SigningKey, VaultError> {
+        if let Some(entry) = self.get(IDENTITY_ID) {
+            let x = serde_json::from_str(&entry.data).map_err(|e| VaultError::Corrupted(e.to_string()))?;
+            return Ok(SigningKey::from_scalar(x));
+        }
+        let key = SigningKey::generate();
+        // serializing a scalar cannot fail
+        let data = serde_json::to_string(key.secret()).expect("scalar serializes");
+        self.put(IDENTITY_ID, EntryKind::Identity, data)?;
+        Ok(key)
+    }
+
+    fn save(&self) -> Result<(), VaultError> {
+        // encrypted in place
+        let mut ciphertext = serde_json::to_vec(&self.entries).expect("entries serialize");
+        let nonce = random_bytes::<NONCE_BYTES>();
+        let tag = XChaCha20Poly1305::new(self.key.as_ref().into())
+            .encrypt_in_place_detached(XNonce::from_slice(&nonce), &header(self.iterations, &self.salt), &mut ciphertext)
+            .map_err(|_| VaultError::Io("the entries are too large to encrypt".to_string()))?;
+        let file = VaultFile {
+            version: VERSION,
+            iterations: self.iterations,
+            salt: hex::encode(self.salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(&ciphertext),
+            tag: hex::encode(tag),
+        };
+        let tmp = self.path.with_extension("tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut out = options.open(&tmp)?;
+        out.write_all(serde_json::to_string_pretty(&file).expect("vault file serializes").as_bytes())?;
+        out.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+impl SecretStore for Vault {
+    fn keys(&self, scheme: SchemeKind, bits: usize) -> Option<Value> {
+        serde_json::from_str(&self.get(&keys_id(scheme, bits))?.data).ok()
+    }
+
+    fn put_keys(&mut self, scheme: SchemeKind, bits: usize, keys: Value) {
+        if let Err(e) = self.put(&keys_id(scheme, bits), EntryKind::Keys, keys.to_string()) {
+            eprintln!("Failed to keep the {:?} keys in the vault: {}", scheme, e);
+        }
+    }
+
+    fn put_share(&mut self, session_id: &str, computation_id: usize, value: &SharedValue) {
+        let data = serde_json::to_string(value).expect("shares serialize");
+        if let Err(e) = self.put(&share_id(session_id, computation_id), EntryKind::Share, data) {
+            eprintln!("Failed to keep the shares of computation {} in the vault: {}", computation_id, e);
+        }
+    }
+
+    fn delete_share(&mut self, session_id: &str, computation_id: usize) {
+        if let Err(e) = self.delete(&share_id(session_id, computation_id)) {
+            eprintln!("Failed to delete the shares of computation {} from the vault: {}", computation_id, e);
+        }
+    }
+}
+
+pub(crate) fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    let sample = rng::sample(8 * N).to_bytes();
+    bytes[N - sample.len()..].copy_from_slice(&sample);
+    bytes
+}
+
+fn decode_hex(field: &str) -> Result<Vec<u8>, VaultError> {
+    hex::decode(field).map_err(|e| VaultError::Corrupted(e.to_string()))
+}
+
+fn decode_fixed<const N: usize>(field: &str, name: &str) -> Result<[u8; N], VaultError> {
+    decode_hex(field)?.try_into().map_err(|_| VaultError::Corrupted(format!("{} of the wrong size", name)))
+}
+
+fn check_iterations(iterations: u32) -> Result<(), VaultError> {
+    if !(1..=MAX_ITERATIONS).contains(&iterations) {
+        return Err(VaultError::Corrupted(format!("{} iterations, expected 1 to {}", iterations, MAX_ITERATIONS)));
+    }
+    Ok(())
+}
+
+/// PBKDF2-HMAC-SHA256 (RFC 8018) of the passphrase.
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> Zeroizing<[u8; KEY_BYTES]> {
+    let mut key = Zeroizing::new([0u8; KEY_BYTES]);
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, key.as_mut_slice());
+    key
+}
+
+/// The header of the file, authenticated along with the entries.
+fn header(iterations: u32, salt: &[u8]) -> Vec<u8> {
+    [HEADER_DOMAIN, &VERSION.to_be_bytes(), &iterations.to_be_bytes(), salt].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ITERATIONS: u32 = 1000;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("smpc-vault-{}-{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let path = temp_path("roundtrip");
+        let mut vault = Vault::create(&path, "correct horse", ITERATIONS).unwrap();
+        let identity = vault.identity().unwrap();
+        vault.put("share/s/0", EntryKind::Share, "[1,2]".to_string()).unwrap();
+        vault.put("share/s/1", EntryKind::Share, "[3]".to_string()).unwrap();
+        vault.delete("share/s/1").unwrap();
+        assert_eq!(vault.delete("share/s/1"), Err(VaultError::UnknownEntry("share/s/1".to_string())));
+
+        let mut vault = Vault::open(&path, "correct horse").unwrap();
+        assert_eq!(vault.entries().iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), [IDENTITY_ID, "share/s/0"]);
+        assert_eq!(vault.get("share/s/0").unwrap().data, "[1,2]");
+        assert_eq!(vault.identity().unwrap().verifying_key(), identity.verifying_key());
+        assert!(!fs::read_to_string(&path).unwrap().contains("[1,2]"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_wrong_passphrase_and_tampering_are_refused() {
+        let path = temp_path("tamper");
+        let mut vault = Vault::create(&path, "correct horse", ITERATIONS).unwrap();
+        vault.put("keys/Paillier/512", EntryKind::Keys, "{}".to_string()).unwrap();
+        assert!(matches!(Vault::open(&path, "battery staple"), Err(VaultError::Unauthenticated)));
+
+        let mut file: VaultFile = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let mut ciphertext = hex::decode(&file.ciphertext).unwrap();
+        ciphertext[0] ^= 1;
+        file.ciphertext = hex::encode(ciphertext);
+        fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+        assert!(matches!(Vault::open(&path, "correct horse"), Err(VaultError::Unauthenticated)));
+
+        // refused before spending any time on the key
+        file.iterations = u32::MAX;
+        fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+        assert!(matches!(Vault::open(&path, "correct horse"), Err(VaultError::Corrupted(_))));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_entry_debug_hides_data() {
+        let entry = Entry { id: "share/s/0".to_string(), kind: EntryKind::Share, created: 0, data: "[12345]".to_string() };
+        let debug = format!("{:?}", entry);
+        assert!(debug.contains("share/s/0") && !debug.contains("12345"), "{}", debug);
+    }
+}
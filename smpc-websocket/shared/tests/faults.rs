@@ -0,0 +1,275 @@
+use shared::coordinator::{Coordinator, SessionConfig, SessionEnd};
+use shared::errors::ProtocolError;
+use shared::homomorphic::SchemeKind;
+use shared::profile::SecurityProfile;
+use shared::protocol::{secagg, sum};
+use shared::simulator::{PrivateInputs, SimulationReport, Simulator};
+use shared::transcript;
+use shared::transport::faulty::{Fault, FaultyTransport, Target};
+use shared::transport::Envelope;
+use shared::types::{RevealMode, WebsocketMessage};
+use shared::validation::Policy;
+
+// Three parties summing one vector, revealed to everyone. Every party sends, in order:
+//   party 0: forward, reveal, report
+//   party 1: forward, reshare, reveal, report
+//   party 2: reshare, reveal, report
+// and gets its InitializeProtocol message first, then the rounds, then the checkpoint of the relay log.
+fn config() -> SessionConfig {
+    SessionConfig { profile: SecurityProfile::Toy512, max_input_bits: 24, scale: 2, vector_len: 2, scheme: SchemeKind::Paillier }
+}
+
+fn simulator(schedule: Vec<(Target, Fault)>) -> Simulator<FaultyTransport> {
+    let inputs = vec![
+        PrivateInputs::new(&[&["1.5", "-2"]]),
+        PrivateInputs::new(&[&["2", "3"]]),
+        PrivateInputs::new(&[&["-1", "0.5"]]),
+    ];
+    let plan = vec![sum::computation(0, 0, RevealMode::Public)];
+    Simulator::new(config(), plan, inputs).with_transport(FaultyTransport::new(schedule))
+}
+
+fn run(schedule: Vec<(Target, Fault)>) -> SimulationReport {
+    simulator(schedule).run()
+}
+
+fn aborted(report: &SimulationReport) -> (&str, &[usize]) {
+    match &report.end {
+        Some(SessionEnd::Aborted { reason, blame }) => (reason, blame),
+        end => panic!("session did not abort: {:?}", end),
+    }
+}
+
+#[test]
+fn test_reordered_and_duplicated_messages() {
+    let schedule = vec![
+        // party 1's forward round is swapped with the next message
+        (Target::FromClient { sid: 1, nth: 0 }, Fault::Delay(1)),
+        // reveal shares and completion reports can be repeated safely
+        (Target::FromClient { sid: 1, nth: 2 }, Fault::Duplicate),
+        (Target::FromClient { sid: 2, nth: 2 }, Fault::Duplicate),
+        // party 0's reveal share only arrives once everything else is done
+        (Target::FromClient { sid: 0, nth: 1 }, Fault::Delay(usize::MAX)),
+    ];
+    let report = run(schedule);
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    for party in &report.parties {
+        assert_eq!(party.result(0).unwrap(), ["2.50", "1.50"]);
+        // the server relayed messages in another order than they were sent, but told every party the truth
+        assert!(matches!(party.relay_check(), Some(Ok(_))));
+    }
+}
+
+#[test]
+fn test_repeated_messages_are_dropped() {
+    let schedule = vec![
+        // the server gets party 1's forward round twice
+        (Target::FromClient { sid: 1, nth: 0 }, Fault::Duplicate),
+        // party 2 gets the forward round twice from the relay
+        (Target::ToClient { sid: 2, nth: 1 }, Fault::Duplicate),
+    ];
+    let report = run(schedule);
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    for party in &report.parties {
+        assert_eq!(party.result(0).unwrap(), ["2.50", "1.50"]);
+    }
+    // the copy the relay made is not in the log it signed
+    assert!(matches!(report.parties[2].relay_check(), Some(Err(ProtocolError::RelayLogMismatch { .. }))));
+}
+
+#[test]
+fn test_messages_of_another_session_are_dropped() {
+    let earlier = run(vec![]);
+    let later = run(vec![]);
+    let (from, text) = earlier.transcript.iter().find_map(|envelope| match envelope {
+        Envelope::ToServer { from, text } if text.contains("Unicast") => Some((*from, text)),
+        _ => None,
+    }).unwrap();
+    let relayed = earlier.transcript.iter().find_map(|envelope| match envelope {
+        Envelope::ToClient { to: 2, text } if text.contains("Relayed") => Some(text),
+        _ => None,
+    }).unwrap();
+
+    // a new session on the server, with the same plan and clients
+    let mut coordinator = Coordinator::new(config(), vec![sum::computation(0, 0, RevealMode::Public)]);
+    coordinator.start(3);
+    let msg: WebsocketMessage = serde_json::from_str(text).unwrap();
+    assert!(coordinator.handle_message(msg, from).is_empty());
+
+    // a party of the later session, which would otherwise run the round again
+    let mut party = later.parties.into_iter().nth(2).unwrap();
+    assert!(party.handle_text(relayed).is_empty());
+}
+
+#[test]
+fn test_reordered_relay_fails_the_checkpoint() {
+    // the first reveal share relayed to party 2 only reaches it last
+    let report = run(vec![(Target::ToClient { sid: 2, nth: 2 }, Fault::Delay(usize::MAX))]);
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    assert_eq!(report.parties[2].result(0).unwrap(), ["2.50", "1.50"]);
+    assert_eq!(report.parties[2].relay_check(), Some(&Err(ProtocolError::RelayLogMismatch { received: 4, sent: 4 })));
+    assert_eq!(report.parties[0].relay_check(), Some(&Ok(4)));
+}
+
+#[test]
+fn test_forged_checkpoint_is_refused() {
+    // claim that the relay sent 10 more messages than it did
+    let rewrite: fn(&str) -> String = |text| text.replacen("\"len\":", "\"len\":1", 1);
+    // party 0 gets its init, one round of the sum and two reveal shares before the checkpoint
+    let report = run(vec![(Target::ToClient { sid: 0, nth: 4 }, Fault::Tamper(rewrite))]);
+    assert_eq!(report.parties[0].relay_check(), Some(&Err(ProtocolError::InvalidCheckpoint)));
+    assert_eq!(report.parties[1].relay_check(), Some(&Ok(5)));
+    // the abort wiped party 0's shares, not the result it already reconstructed
+    assert!(report.parties[0].shares().get(0).is_err());
+    assert_eq!(report.parties[0].result(0).unwrap(), ["2.50", "1.50"]);
+    assert!(report.parties[1].shares().get(0).is_ok());
+}
+
+#[test]
+fn test_dropped_report_times_out() {
+    let report = run(vec![(Target::FromClient { sid: 2, nth: 2 }, Fault::Drop)]);
+    let (reason, blame) = aborted(&report);
+    assert_eq!(reason, "computation 0 timed out waiting for clients [2]");
+    assert_eq!(blame, [2]);
+    // the other parties did reconstruct the result
+    assert_eq!(report.parties[0].result(0).unwrap(), ["2.50", "1.50"]);
+}
+
+#[test]
+fn test_dropped_round_times_out() {
+    // party 2 never gets the forward round, so nobody gets to the reveal phase
+    let report = run(vec![(Target::ToClient { sid: 2, nth: 1 }, Fault::Drop)]);
+    let (_, blame) = aborted(&report);
+    assert_eq!(blame, [0, 1, 2]);
+    assert_eq!(report.result(0), None);
+}
+
+#[test]
+fn test_corrupted_message_aborts() {
+    // a frame that does not parse could have been damaged by anyone on the way
+    let report = run(vec![(Target::ToClient { sid: 1, nth: 1 }, Fault::Corrupt)]);
+    let (reason, blame) = aborted(&report);
+    assert!(reason.starts_with("Client 1 aborted"), "{}", reason);
+    assert!(blame.is_empty());
+}
+
+#[test]
+fn test_tampered_round_blames_sender() {
+    // a well-formed message with a round its protocol does not have is held against its sender
+    let rewrite: fn(&str) -> String = |text| text.replacen("\"round\":0", "\"round\":7", 1);
+    let report = run(vec![(Target::ToClient { sid: 1, nth: 1 }, Fault::Tamper(rewrite))]);
+    let (reason, blame) = aborted(&report);
+    assert_eq!(reason, "Client 1 aborted: protocol 'sum' has no round 7");
+    assert_eq!(blame, [0]);
+}
+
+#[test]
+fn test_trivial_ciphertext_blames_sender() {
+    // party 1 receives E(x_0) replaced with 1, which encrypts 0 without any randomness
+    let rewrite: fn(&str) -> String = |text| {
+        let start = text.find("\"computed_values\":[\"").unwrap() + "\"computed_values\":[\"".len();
+        let end = start + text[start..].find('"').unwrap();
+        format!("{}01{}", &text[..start], &text[end..])
+    };
+    let report = run(vec![(Target::ToClient { sid: 1, nth: 1 }, Fault::Tamper(rewrite))]);
+    let (reason, blame) = aborted(&report);
+    assert_eq!(reason, "Client 1 aborted: invalid message: ciphertext is trivial");
+    assert_eq!(blame, [0]);
+}
+
+#[test]
+fn test_truncated_reveal_blames_sender() {
+    // party 2 reveals a share of the first element only, which would leave the second one a partial sum
+    let rewrite: fn(&str) -> String = |text| {
+        let start = text.find("\"shares\":[").unwrap();
+        let comma = start + text[start..].find(',').unwrap();
+        let end = start + text[start..].find(']').unwrap();
+        format!("{}{}", &text[..comma], &text[end..])
+    };
+    let report = run(vec![(Target::FromClient { sid: 2, nth: 1 }, Fault::Tamper(rewrite))]);
+    let (reason, blame) = aborted(&report);
+    assert!(reason.ends_with("invalid message: expected 2 ciphertexts but received 1"), "{}", reason);
+    assert_eq!(blame, [2]);
+    assert!(report.parties.iter().all(|party| party.result(0).is_none()));
+}
+
+#[test]
+fn test_session_below_policy_is_refused() {
+    let inputs = vec![PrivateInputs::new(&[&["1", "2"]]), PrivateInputs::new(&[&["3", "4"]])];
+    let plan = vec![sum::computation(0, 0, RevealMode::Public)];
+    let policy = Policy { min_profile: SecurityProfile::Standard2048 };
+    let report = Simulator::new(config(), plan, inputs).with_policy(policy).run();
+    let (reason, blame) = aborted(&report);
+    assert_eq!(reason, "Client 0 aborted: invalid message: the session runs at profile toy-512 but at least standard-2048 is required");
+    assert!(blame.is_empty(), "{:?}", blame);
+}
+
+#[test]
+fn test_dropped_connections_resume() {
+    let report = run(vec![
+        // the forward round to party 2 is lost with its connection
+        (Target::ToClient { sid: 2, nth: 1 }, Fault::Disconnect),
+        // party 1's reshare is lost with its connection
+        (Target::FromClient { sid: 1, nth: 1 }, Fault::Disconnect),
+    ]);
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    for party in &report.parties {
+        assert_eq!(party.result(0).unwrap(), ["2.50", "1.50"]);
+        // every message missed was sent again, once and in order
+        assert!(matches!(party.relay_check(), Some(Ok(_))), "{:?}", party.relay_check());
+        // the checkpoint acknowledged every message of the computation
+        assert_eq!(party.unacknowledged(), 0);
+    }
+    let resumed = report.transcript.iter().filter(|e| matches!(e, Envelope::ToServer { text, .. } if text.contains("Resumed")));
+    assert_eq!(resumed.count(), 2);
+}
+
+#[test]
+fn test_connection_not_resumed_aborts() {
+    let report = simulator(vec![(Target::ToClient { sid: 2, nth: 1 }, Fault::Disconnect)]).without_resume().run();
+    let (reason, blame) = aborted(&report);
+    assert_eq!(reason, "client 2 did not reconnect within the grace period");
+    assert_eq!(blame, [2]);
+}
+
+// Five parties summing with secure aggregation, any three of which must finish. Every party sends, in order:
+// its advertised keys, one sealed share per other party, its masked input, its unmasking shares, a report.
+fn secagg_simulator(schedule: Vec<(Target, Fault)>) -> Simulator<FaultyTransport> {
+    let inputs: Vec<PrivateInputs> = ["1.5", "2", "-1", "4", "10"].iter().map(|x| PrivateInputs::new(&[&[x, "1"]])).collect();
+    let plan = vec![secagg::computation(0, 0, 3)];
+    Simulator::new(config(), plan, inputs).with_transport(FaultyTransport::new(schedule)).without_resume()
+}
+
+#[test]
+fn test_secagg_survives_dropouts() {
+    let report = secagg_simulator(vec![
+        // party 4 is gone before anyone gets its keys
+        (Target::FromClient { sid: 4, nth: 0 }, Fault::Disconnect),
+        // party 3 handed out its shares to parties 0, 1 and 2, but its masked input is lost
+        (Target::FromClient { sid: 3, nth: 4 }, Fault::Disconnect),
+    ])
+    .run();
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    // the sum of the inputs of the parties that finished, party 3's pairwise masks removed
+    for party in &report.parties[..3] {
+        assert_eq!(party.result(0).unwrap(), ["2.50", "3.00"]);
+        assert!(matches!(party.relay_check(), Some(Ok(_))), "{:?}", party.relay_check());
+    }
+    assert!(report.parties[3..].iter().all(|party| party.result(0).is_none()));
+    assert_eq!(transcript::verify(&transcript::from_envelopes(&report.transcript)), []);
+}
+
+#[test]
+fn test_secagg_below_threshold_aborts() {
+    let report = secagg_simulator(vec![
+        (Target::FromClient { sid: 1, nth: 0 }, Fault::Disconnect),
+        (Target::FromClient { sid: 3, nth: 0 }, Fault::Disconnect),
+        (Target::FromClient { sid: 4, nth: 0 }, Fault::Disconnect),
+    ])
+    .run();
+    // two parties may drop out, not a third one
+    let (reason, blame) = aborted(&report);
+    assert_eq!(reason, "client 4 did not reconnect within the grace period");
+    assert_eq!(blame, [4]);
+    assert_eq!(report.result(0), None);
+}
@@ -0,0 +1,205 @@
+use kzen_paillier::BigInt;
+use serde_json::Value;
+use serde_json::json;
+use shared::coordinator::{SessionConfig, SessionEnd};
+use shared::encoding::{FixedPoint, FixedPointEncoder};
+use shared::homomorphic::SchemeKind;
+use shared::profile::SecurityProfile;
+use shared::protocol::{add, beaver, bgw, multiply, product, secagg, sum};
+use shared::simulator::{PrivateInputs, Simulator};
+use shared::types::{Computation, RevealMode};
+use shared::vault::{self, Vault};
+use smpc_simple::run_local_product;
+
+fn config() -> SessionConfig {
+    SessionConfig { profile: SecurityProfile::Toy512, max_input_bits: 24, scale: 2, vector_len: 2, scheme: SchemeKind::Paillier }
+}
+
+fn inputs() -> Vec<PrivateInputs> {
+    vec![
+        PrivateInputs::new(&[&["1.5", "-2"], &["10", "0.25"]]),
+        PrivateInputs::new(&[&["2", "3"], &["-4", "0.5"]]),
+        PrivateInputs::new(&[&["-1", "0.5"], &["1.5", "1"]]),
+    ]
+}
+
+/// Multiply the decimal factors of every row with the reference implementation in smpc-simple, each with
+/// `scale` digits after the point.
+fn reference_products(rows: &[Vec<&str>], scale: u32) -> Vec<String> {
+    let encoder = FixedPointEncoder::new(scale);
+    rows.iter()
+        .map(|factors| {
+            let mantissas: Vec<BigInt> = factors.iter().map(|x| encoder.parse(x).unwrap().mantissa).collect();
+            let transcript = run_local_product(&mantissas, 512);
+            FixedPoint::new(transcript.product, scale * factors.len() as u32).to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn test_public_product() {
+    let plan = vec![product::computation(0, 0, RevealMode::Public)];
+    let report = Simulator::new(config(), plan, inputs()).run();
+
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    // every party reconstructs the result in Public mode
+    for party in &report.parties {
+        assert_eq!(party.result(0).unwrap(), ["-3.000000", "-3.000000"]);
+    }
+    assert_eq!(report.reconstruct(0).unwrap(), ["-3.000000", "-3.000000"]);
+    let rows = vec![vec!["1.5", "2", "-1"], vec!["-2", "3", "0.5"]];
+    assert_eq!(report.reconstruct(0).unwrap(), reference_products(&rows, 2));
+}
+
+#[test]
+fn test_zero_factor() {
+    // 0 * E(x) is the trivial ciphertext 1, which the next party would refuse if it was not re-randomized
+    let mut inputs = inputs();
+    inputs[1] = PrivateInputs::new(&[&["0", "3"]]);
+    let report = Simulator::new(config(), vec![product::computation(0, 0, RevealMode::Public)], inputs).run();
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    assert_eq!(report.reconstruct(0).unwrap(), ["0.000000", "-3.000000"]);
+}
+
+#[test]
+fn test_composed_plan() {
+    let plan = vec![
+        sum::computation(0, 0, RevealMode::Private),
+        sum::computation(1, 1, RevealMode::Private),
+        multiply::computation(2, 0, 1, RevealMode::OutputParty(1)),
+        add::computation(3, 0, 0, RevealMode::Public),
+    ];
+    let report = Simulator::new(config(), plan, inputs()).run();
+
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    assert_eq!(report.result(0), None);
+    assert_eq!(report.reconstruct(0).unwrap(), ["2.50", "1.50"]);
+    assert_eq!(report.reconstruct(1).unwrap(), ["7.50", "1.75"]);
+    // only the output party learns the product
+    assert_eq!(report.parties[0].result(2), None);
+    assert_eq!(report.parties[1].result(2).unwrap(), ["18.7500", "2.6250"]);
+    let sums = [report.reconstruct(0).unwrap(), report.reconstruct(1).unwrap()];
+    let rows: Vec<Vec<&str>> = (0..2).map(|k| vec![sums[0][k].as_str(), sums[1][k].as_str()]).collect();
+    assert_eq!(report.parties[1].result(2).unwrap(), reference_products(&rows, 2));
+    assert_eq!(report.result(3).unwrap(), ["5.00", "3.00"]);
+}
+
+#[test]
+fn test_secagg_matches_sum() {
+    let plan = vec![sum::computation(0, 0, RevealMode::Public), secagg::computation(1, 0, 2)];
+    let report = Simulator::new(config(), plan, inputs()).run();
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    for party in &report.parties {
+        assert_eq!(party.result(1).unwrap(), party.result(0).unwrap());
+    }
+    // the result is computed in the clear by every party, there are no shares of it
+    assert!(report.reconstruct(1).is_err());
+}
+
+#[test]
+fn test_bgw_matches_product() {
+    // five factors leave one over at the first two levels of the tree
+    let rows = vec![vec!["1.5", "2", "-1", "0.5", "3"], vec!["-2", "3", "0.5", "-1.25", "10"]];
+    let inputs: Vec<PrivateInputs> = (0..5).map(|i| PrivateInputs::new(&[&[rows[0][i], rows[1][i]]])).collect();
+    let plan = vec![bgw::computation(0, 0, RevealMode::Private), add::computation(1, 0, 0, RevealMode::Public)];
+    let report = Simulator::new(config(), plan, inputs).run();
+
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    assert_eq!(report.reconstruct(0).unwrap(), reference_products(&rows, 2));
+    // its shares compose with the protocols on shared values
+    assert_eq!(report.result(1).unwrap(), ["-9.0000000000", "75.0000000000"]);
+}
+
+#[test]
+fn test_beaver_matches_multiply() {
+    let (mut plan, triple) = beaver::preprocessing(10);
+    plan.extend([
+        sum::computation(0, 0, RevealMode::Private),
+        sum::computation(1, 1, RevealMode::Private),
+        multiply::computation(2, 0, 1, RevealMode::Public),
+        beaver::computation(3, 0, 1, triple.clone(), RevealMode::Public),
+        // a triple masks a single multiplication
+        beaver::computation(4, 1, 0, triple, RevealMode::Public),
+    ]);
+    let report = Simulator::new(config(), plan, inputs()).run();
+
+    assert_eq!(report.result(3).unwrap(), ["18.7500", "2.6250"]);
+    assert_eq!(report.result(3), report.result(2));
+    let Some(SessionEnd::Aborted { reason, .. }) = &report.end else {
+        panic!("session did not abort: {:?}", report.end);
+    };
+    assert!(reason.ends_with("no shares stored for computation 10"), "{}", reason);
+    assert_eq!(report.result(4), None);
+}
+
+#[test]
+fn test_beaver_dealer() {
+    let plan = vec![
+        sum::computation(0, 0, RevealMode::Private),
+        sum::computation(1, 1, RevealMode::Private),
+        beaver::computation(2, 0, 1, beaver::Triple::Dealer, RevealMode::Public),
+    ];
+    let report = Simulator::new(config(), plan, inputs()).run();
+
+    // only the server in test mode deals triples, since it learns every operand
+    if cfg!(feature = "insecure-mock") {
+        assert_eq!(report.end, Some(SessionEnd::Completed));
+        assert_eq!(report.result(2).unwrap(), ["18.7500", "2.6250"]);
+    } else {
+        let reason = "no triples were dealt for this computation, the server only deals them in test mode".to_string();
+        assert_eq!(report.end, Some(SessionEnd::Aborted { reason, blame: vec![] }));
+    }
+}
+
+#[test]
+fn test_abort() {
+    let mut inputs = inputs();
+    // above the 24 bit bound once scaled
+    inputs[2] = PrivateInputs::new(&[&["1000000", "1"]]);
+    let report = Simulator::new(config(), vec![sum::computation(0, 0, RevealMode::Public)], inputs).run();
+
+    let Some(SessionEnd::Aborted { reason, blame }) = &report.end else {
+        panic!("session did not abort: {:?}", report.end);
+    };
+    assert!(reason.starts_with("Client 2 aborted"));
+    // the input is checked when the chain reaches party 2, which blames itself
+    assert_eq!(blame, &[2]);
+    assert_eq!(report.result(0), None);
+}
+
+#[test]
+fn test_rejected_plan() {
+    let plan = vec![Computation { id: 0, protocol: "division".to_string(), params: json!({}), reveal: RevealMode::Public }];
+    let report = Simulator::new(config(), plan, inputs()).run();
+
+    let reason = "no protocol registered as 'division'".to_string();
+    assert_eq!(report.end, Some(SessionEnd::Aborted { reason, blame: vec![] }));
+    assert!(report.reconstruct(0).is_err());
+}
+
+#[test]
+fn test_vault_keeps_keys_and_shares_across_sessions() {
+    let path = std::env::temp_dir().join(format!("smpc-simulator-vault-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    Vault::create(&path, "passphrase", 1000).unwrap();
+    let keys_id = vault::keys_id(SchemeKind::Paillier, 512);
+
+    let mut moduli = Vec::new();
+    for _ in 0..2 {
+        let store = Box::new(Vault::open(&path, "passphrase").unwrap());
+        let plan = vec![sum::computation(0, 0, RevealMode::Private)];
+        let report = Simulator::new(config(), plan, inputs()).with_store(0, store).run();
+        assert_eq!(report.end, Some(SessionEnd::Completed));
+        assert_eq!(report.reconstruct(0).unwrap(), ["2.50", "1.50"]);
+        moduli.push(report.parties[0].shares().get(0).unwrap().n.clone());
+    }
+    // the second session runs on the key pair the first one generated
+    assert_eq!(moduli[0], moduli[1]);
+
+    let vault = Vault::open(&path, "passphrase").unwrap();
+    let keys: Value = serde_json::from_str(&vault.get(&keys_id).unwrap().data).unwrap();
+    assert_eq!(keys["public"]["n"], moduli[0].to_string());
+    // one entry of shares per session
+    assert_eq!(vault.entries().iter().filter(|entry| entry.kind == vault::EntryKind::Share).count(), 2);
+    std::fs::remove_file(&path).unwrap();
+}
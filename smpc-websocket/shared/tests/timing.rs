@@ -0,0 +1,69 @@
+use std::hint::black_box;
+use std::time::Instant;
+
+use curv::arithmetic::traits::Modulo;
+use kzen_paillier::BigInt;
+use shared::homomorphic::{AdditiveHomomorphic, DamgardJurikScheme, PaillierScheme};
+use shared::rng;
+
+const SAMPLES: usize = 3000;
+// |t| above this tells the two classes apart with overwhelming confidence. dudect reports a leak from 4.5 on;
+// the margin absorbs the noise of a shared test machine.
+const LEAK_THRESHOLD: f64 = 10.0;
+
+/// Welch's t statistic between the running times of `op` on a fixed exponent and on fresh random exponents,
+/// measured interleaved in a random order so that drifts of the machine hit both classes alike, as dudect does.
+/// The slowest tenth of the measurements is cropped, most of it being interrupts.
+fn welch_t(fixed: &BigInt, random: impl Fn() -> BigInt, op: impl Fn(&BigInt) -> BigInt) -> f64 {
+    let mut times: [Vec<f64>; 2] = [Vec::new(), Vec::new()];
+    for _ in 0..2 * SAMPLES {
+        let class = usize::from(rng::sample(1) == BigInt::from(1));
+        let exponent = if class == 0 { fixed.clone() } else { random() };
+        let start = Instant::now();
+        black_box(op(black_box(&exponent)));
+        times[class].push(start.elapsed().as_nanos() as f64);
+    }
+    let mut all: Vec<f64> = times.iter().flatten().copied().collect();
+    all.sort_by(f64::total_cmp);
+    let crop = all[all.len() * 9 / 10];
+    let [a, b] = times.map(|t| {
+        let t: Vec<f64> = t.into_iter().filter(|&x| x <= crop).collect();
+        let mean = t.iter().sum::<f64>() / t.len() as f64;
+        let var = t.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (t.len() - 1) as f64;
+        (mean, var, t.len() as f64)
+    });
+    (a.0 - b.0) / (a.1 / a.2 + b.1 / b.2).sqrt()
+}
+
+// wall-clock statistics are only meaningful on an optimized build and a quiet machine, so these run on demand:
+// cargo test --release -p shared --test timing -- --ignored
+#[test]
+#[ignore]
+fn test_scalar_mul_time_does_not_depend_on_the_input() {
+    let (pk, _) = PaillierScheme::keypair(512);
+    let c = PaillierScheme::encrypt(&pk, &BigInt::from(42));
+    let random = || rng::sample_below(&pk.n);
+
+    // the harness sees the leak of the variable time exponentiation between an input of 1 and a full-size one
+    let t = welch_t(&BigInt::from(1), random, |k| BigInt::mod_pow(&c, k, &pk.nn));
+    assert!(t.abs() > LEAK_THRESHOLD, "mod_pow: t = {}", t);
+
+    // short inputs, and negative ones with every high bit set, take as long as random ones
+    for fixed in [BigInt::from(1), &pk.n - BigInt::from(1)] {
+        let t = welch_t(&fixed, random, |k| PaillierScheme::scalar_mul(&pk, &c, k));
+        assert!(t.abs() < LEAK_THRESHOLD, "scalar_mul: t = {} for k = {}", t, fixed);
+    }
+}
+
+#[test]
+#[ignore]
+fn test_damgard_jurik_encrypt_time_does_not_depend_on_the_input() {
+    let (pk, _) = DamgardJurikScheme::keypair(512);
+    let n = DamgardJurikScheme::plaintext_modulus(&pk);
+    let random = || rng::sample_below(&n);
+
+    for fixed in [BigInt::from(1), &n - BigInt::from(1)] {
+        let t = welch_t(&fixed, random, |m| DamgardJurikScheme::encrypt(&pk, m));
+        assert!(t.abs() < LEAK_THRESHOLD, "encrypt: t = {} for m = {}", t, fixed);
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs;
+
+use shared::coordinator::{SessionConfig, SessionEnd};
+use shared::homomorphic::SchemeKind;
+use shared::profile::SecurityProfile;
+use shared::protocol::sum;
+use shared::relay_log::{SignedCheckpoint, SigningKey};
+use shared::simulator::{PrivateInputs, SimulationReport, Simulator};
+use shared::transcript::{self, Endpoint, Recorder, TranscriptEntry, Violation};
+use shared::transport::faulty::{Fault, FaultyTransport, Target};
+use shared::transport::Envelope;
+use shared::types::{ClientMessage, RevealMode};
+
+fn run(schedule: Vec<(Target, Fault)>) -> SimulationReport {
+    run_plan(vec![sum::computation(0, 0, RevealMode::Public)], schedule)
+}
+
+fn run_plan(plan: Vec<shared::types::Computation>, schedule: Vec<(Target, Fault)>) -> SimulationReport {
+    let config = SessionConfig { profile: SecurityProfile::Toy512, max_input_bits: 24, scale: 2, vector_len: 2, scheme: SchemeKind::Paillier };
+    let inputs = vec![
+        PrivateInputs::new(&[&["1.5", "-2"]]),
+        PrivateInputs::new(&[&["2", "3"]]),
+        PrivateInputs::new(&[&["-1", "0.5"]]),
+    ];
+    Simulator::new(config, plan, inputs).with_transport(FaultyTransport::new(schedule)).run()
+}
+
+#[test]
+fn test_honest_session_verifies() {
+    let report = run(vec![]);
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    let mut entries = transcript::from_envelopes(&report.transcript);
+    assert_eq!(transcript::verify(&entries), []);
+
+    // every computation is announced under the session's profile
+    let inits: Vec<usize> = (0..entries.len()).filter(|&k| entries[k].kind == "InitializeProtocol").collect();
+    assert!(inits.iter().all(|&k| entries[k].profile == Some(SecurityProfile::Toy512)));
+    entries[inits[1]].profile = Some(SecurityProfile::Standard2048);
+    let seq = entries[inits[1]].seq;
+    assert_eq!(transcript::verify(&entries), [Violation::ProfileChanged { seq, profile: SecurityProfile::Standard2048 }]);
+}
+
+#[test]
+fn test_resumed_session_verifies() {
+    // the forward round to party 2 is lost with its connection, and sent again once the session resumes
+    let report = run(vec![(Target::ToClient { sid: 2, nth: 1 }, Fault::Disconnect)]);
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    let mut entries = transcript::from_envelopes(&report.transcript);
+    assert_eq!(transcript::verify(&entries), []);
+
+    // the server records the frames it sends on the dropped connection too, and again once resumed
+    let resume = entries.iter().position(|e| e.kind == "Resume").unwrap();
+    let resent = (resume + 1..entries.len()).find(|&k| entries[k].to == Endpoint::Client(2) && entries[k].kind == "Round").unwrap();
+    entries.insert(resume, entries[resent].clone());
+    for (entry, seq) in entries.iter_mut().zip(0..) {
+        entry.seq = seq;
+    }
+    assert_eq!(transcript::verify(&entries), []);
+    // but only after a resume request, else client 2 got the frame twice and its checkpoint cannot match
+    entries.remove(resume + 1);
+    for (entry, seq) in entries.iter_mut().zip(0..) {
+        entry.seq = seq;
+    }
+    let violations = transcript::verify(&entries);
+    assert!(matches!(violations[..], [Violation::Injected { .. }, Violation::BadCheckpoint { client: 2, .. }]), "{:?}", violations);
+}
+
+// a checkpoint entry with its payload rewritten, and its hash to match
+fn rewrite_checkpoint(entry: &TranscriptEntry, rewrite: impl FnOnce(&mut SignedCheckpoint)) -> TranscriptEntry {
+    let Ok(ClientMessage::Checkpoint(mut signed)) = serde_json::from_str(entry.payload.as_ref().unwrap()) else {
+        panic!("entry {} is not a checkpoint", entry.seq);
+    };
+    rewrite(&mut signed);
+    let text = serde_json::to_string(&ClientMessage::Checkpoint(signed)).unwrap();
+    TranscriptEntry::new(entry.seq, entry.timestamp, entry.from, entry.to, &text, true)
+}
+
+#[test]
+fn test_forged_checkpoint_is_caught() {
+    let report = run(vec![]);
+    let mut entries = transcript::from_envelopes(&report.transcript);
+    let k = entries.iter().position(|e| e.kind == "Checkpoint" && e.to == Endpoint::Client(1)).unwrap();
+    let (seq, honest) = (entries[k].seq, entries[k].clone());
+
+    // heads that leave out the last frame sent to client 1
+    entries[k] = rewrite_checkpoint(&honest, |signed| signed.checkpoint.clients[1].len -= 1);
+    assert_eq!(transcript::verify(&entries), [Violation::BadCheckpoint { seq, client: 1 }]);
+
+    // the same heads signed with another key than the one the server announced
+    let forger = SigningKey::generate();
+    entries[k] = rewrite_checkpoint(&honest, |signed| *signed = SignedCheckpoint::sign(signed.checkpoint.clone(), &forger));
+    assert_eq!(transcript::verify(&entries), [Violation::BadCheckpoint { seq, client: 1 }]);
+
+    // a checkpoint that is signed by the server, but whose heads do not match what client 1 was sent
+    let report = run(vec![]);
+    let other = transcript::from_envelopes(&report.transcript);
+    entries[k] = other.into_iter().find(|e| e.kind == "Checkpoint" && e.to == Endpoint::Client(1)).unwrap();
+    entries[k].seq = seq;
+    assert!(transcript::verify(&entries).contains(&Violation::BadCheckpoint { seq, client: 1 }));
+}
+
+#[test]
+fn test_missing_checkpoint_is_caught() {
+    let plan = vec![sum::computation(0, 0, RevealMode::Public), sum::computation(1, 0, RevealMode::Public)];
+    let report = run_plan(plan, vec![]);
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    let mut entries = transcript::from_envelopes(&report.transcript);
+    assert_eq!(transcript::verify(&entries), []);
+
+    // the checkpoint of the first computation never reaches client 0
+    let k = entries.iter().position(|e| e.kind == "Checkpoint" && e.to == Endpoint::Client(0)).unwrap();
+    entries.remove(k);
+    for (entry, seq) in entries.iter_mut().zip(0..) {
+        entry.seq = seq;
+    }
+    let next = entries.iter().find(|e| e.kind == "InitializeProtocol" && e.to == Endpoint::Client(0) && e.computation_id == Some(1)).unwrap();
+    assert_eq!(transcript::verify(&entries), [Violation::MissingCheckpoint { seq: next.seq, client: 0, computation_id: 0 }]);
+}
+
+#[test]
+fn test_tampered_relay_is_caught() {
+    let rewrite: fn(&str) -> String = |text| text.replacen("\"round\":0", "\"round\":7", 1);
+    let report = run(vec![(Target::ToClient { sid: 1, nth: 1 }, Fault::Tamper(rewrite))]);
+    let violations = transcript::verify(&transcript::from_envelopes(&report.transcript));
+    // what party 1 got was not what party 0 sent it
+    assert!(matches!(violations[0], Violation::Injected { .. }), "{:?}", violations);
+    assert!(violations.iter().any(|v| matches!(v, Violation::NotRelayed { to: 1, .. })));
+}
+
+#[test]
+fn test_reordered_relay_is_caught() {
+    // party 0's reveal share reaches the others after party 1's
+    let report = run(vec![(Target::FromClient { sid: 0, nth: 1 }, Fault::Delay(1))]);
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    let entries = transcript::from_envelopes(&report.transcript);
+    // the server's log is in order: it is the delivery to the server that was delayed
+    assert_eq!(transcript::verify(&entries), []);
+
+    // the first reveal share relayed to party 2 only reaches it last
+    let report = run(vec![(Target::ToClient { sid: 2, nth: 2 }, Fault::Delay(usize::MAX))]);
+    assert_eq!(report.end, Some(SessionEnd::Completed));
+    let violations = transcript::verify(&transcript::from_envelopes(&report.transcript));
+    assert!(violations.iter().any(|v| matches!(v, Violation::Reordered { .. })), "{:?}", violations);
+}
+
+#[test]
+fn test_recorded_transcripts_cross_check() {
+    let report = run(vec![]);
+    let dir = std::env::temp_dir().join(format!("transcript-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (server_path, client_path) = (dir.join("server.jsonl"), dir.join("client.jsonl"));
+
+    // both ends record the same frames, the client only the ones it exchanged
+    let mut server = Recorder::create(&server_path, false).unwrap();
+    let mut client = Recorder::create(&client_path, false).unwrap();
+    for envelope in &report.transcript {
+        match envelope {
+            Envelope::ToServer { from, text } => {
+                server.record(Endpoint::Client(*from), Endpoint::Server, text);
+                if *from == 1 {
+                    client.record(Endpoint::Client(1), Endpoint::Server, text);
+                }
+            }
+            Envelope::ToClient { to, text } => {
+                server.record(Endpoint::Server, Endpoint::Client(*to), text);
+                if *to == 1 {
+                    // a client that got something else than what the server recorded sending
+                    let text = if text.contains("RevealShare") { text.replace("\"sid\":0", "\"sid\":2") } else { text.clone() };
+                    client.record(Endpoint::Server, Endpoint::Client(1), &text);
+                }
+            }
+        }
+    }
+    server.record_close(1, "completed");
+    drop((server, client));
+
+    let server = transcript::read(&server_path).unwrap();
+    let client = transcript::read(&client_path).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(transcript::verify(&server), []);
+    assert_eq!(server.last().unwrap().kind, "Close");
+    let violations = transcript::cross_check(&server, &client);
+    assert_eq!(violations.len(), 1);
+    assert!(matches!(violations[0], Violation::Diverges { sid: 1, .. }));
+}
@@ -0,0 +1,21 @@
+// A binary of its own: the hook changes the allocator of every GMP integer of the process, so it must be
+// installed before any other test thread computes.
+use curv::arithmetic::traits::{BasicOps, Converter};
+use kzen_paillier::BigInt;
+use shared::secret::{wipe_freed_memory, Wipe};
+
+#[test]
+fn test_gmp_still_computes() {
+    wipe_freed_memory();
+    wipe_freed_memory();
+    // allocates and frees ever larger limbs
+    let mut x = BigInt::from(3);
+    for _ in 0..12 {
+        x = &x * &x;
+    }
+    assert_eq!(x, BigInt::from(3).pow(4096));
+    assert_eq!(BigInt::from_bytes(&x.to_bytes()), x);
+    // a wiped integer is still freed through the hook
+    x.wipe();
+    assert_eq!(x, BigInt::from(0));
+}
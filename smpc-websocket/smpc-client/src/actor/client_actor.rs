@@ -1,163 +1,94 @@
-use std::marker::PhantomData;
-
 use actix::{Actor, StreamHandler};
 use actix_web_actors::ws;
 use actix::ActorContext;
-use kzen_paillier::*;
-use shared::types::{ClientMessage, FirstRoundResponse, InitializeProtocol, RelayerMessage, SecondRoundResponse, UnicastMessage, WebsocketMessage};
+
+use shared::party::Party;
+use shared::types::ClientMessage;
+use shared::protocol::PrivateInputs;
+use shared::transcript::{Endpoint, Recorder};
+use shared::validation::Policy;
 use crate::actor::consts::SETUP;
-use curv::arithmetic::{BigInt, Modulo};
-use shared::utils::{EncodedCiphertextRepr, get_bigint_from_encoded_ciphertext};
+use crate::actor::parked::{self, Parked};
+use crate::cli;
 
+/// Websocket connection from the server to this client. The session logic lives in `shared::party::Party`,
+/// the actor only moves its messages between the socket and the party. If the connection drops in the middle
+/// of a session, the party is parked for the server to resume the session on a new connection.
 pub struct ClientActor{
-    // if this is the first client, it will generate and store the decryption key which will be used to decrypt and obtain the final result
-    decryption_key: Option<DecryptionKey>,
+    party: Party,
+    recorder: Option<Recorder>,
+    // whether the first frame was handled, after which the connection no longer resumes a session
+    started: bool,
+    // whether the server closed the connection, which it does once the session is over
+    closed: bool,
 }
 
 
 impl Actor for ClientActor {
     type Context = ws::WebsocketContext<Self>;
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        if self.closed || self.party.session_id().is_none() {
+            return self.party.wipe();
+        }
+        println!("Connection dropped in the middle of the session, keeping it for {:?}", SETUP.resume_grace);
+        let party = std::mem::replace(&mut self.party, Party::new(Box::new(PrivateInputs::default())));
+        parked::park(Parked { party, recorder: self.recorder.take() });
+    }
 }
 
 impl ClientActor{
     pub fn new() -> Self {
+        // a party without inputs aborts the computations that need them
+        let inputs = PrivateInputs::read(SETUP.inputs_file).unwrap_or_else(|e| {
+            eprintln!("Failed to read the private inputs from {}: {}", SETUP.inputs_file, e);
+            PrivateInputs::default()
+        });
+        let mut party = Party::new(Box::new(inputs)).with_policy(Policy { min_profile: SETUP.min_profile });
+        if SETUP.vault.is_some() {
+            match cli::open_vault(true) {
+                Ok(mut vault) => {
+                    match vault.identity() {
+                        Ok(identity) => println!("Client identity: {}", identity.verifying_key().to_hex()),
+                        Err(e) => eprintln!("Failed to load the identity key: {}", e),
+                    }
+                    party = party.with_store(Box::new(vault));
+                }
+                Err(e) => eprintln!("Running without the vault: {}", e),
+            }
+        }
         ClientActor{
-            decryption_key: None,
+            party,
+            recorder: None,
+            started: false,
+            closed: false,
         }
     }
 
-    pub fn send_unicast<T>(&self, from: usize, to: usize, data: T, ctx: &mut ws::WebsocketContext<Self>) where T: serde::Serialize {
-        let data_value = serde_json::to_value(data).unwrap_or_else(|e| {
-            eprintln!("Failed to convert UnicastMessage to value: {}", e);
-            serde_json::Value::Null
-        });
-        let uni_msg = UnicastMessage::new(from, to, data_value);
-
-        let msg: WebsocketMessage = WebsocketMessage::Unicast(uni_msg);
-        let json_str = serde_json::to_string(&msg).unwrap_or_else(|e| {
-            eprintln!("Failed to serialize message: {}", e);
-            "".to_string()
+    /// Take over the parked session the first frame asks to resume, if any, or start recording a new one.
+    fn start(&mut self, text: &str) {
+        self.started = true;
+        if let Ok(ClientMessage::Resume(request)) = serde_json::from_str(text) && let Some(parked) = parked::unpark(&request) {
+            println!("Resuming the session on a new connection");
+            self.party.wipe();
+            Parked { party: self.party, recorder: self.recorder } = parked;
+            return;
+        }
+        self.recorder = SETUP.transcript.and_then(|path| {
+            Recorder::create(path, false).map_err(|e| eprintln!("Failed to create transcript {}: {}", path, e)).ok()
         });
-        ctx.text(json_str);
     }
 
-    pub fn send_json<T>(&self, msg: &T, ctx: &mut ws::WebsocketContext<Self>) where T: serde::Serialize {
+    pub fn send_json<T>(&mut self, msg: &T, ctx: &mut ws::WebsocketContext<Self>) where T: serde::Serialize {
         let json_str = serde_json::to_string(msg).unwrap_or_else(|e| {
             eprintln!("Failed to serialize message: {}", e);
             "".to_string()
         });
-        ctx.text(json_str);
-
-    }
-
-    pub fn send_relayer_msg(self: &ClientActor, relayer_msg:RelayerMessage<serde_json::Value>, ctx: &mut ws::WebsocketContext<Self>){
-        self.send_json(&WebsocketMessage::Relayer(relayer_msg), ctx);
-    }
-
-
-    pub fn start_protocol(&mut self, init: InitializeProtocol,  ctx: &mut ws::WebsocketContext<Self>) {
-        // Start the protocol by sending an initialization message or any other setup
-        let num_bits = init.bits_security;
-        // generate the private and public Paillier keys here
-        let kp = Paillier::keypair_with_modulus_size(num_bits).keys();
-        self.decryption_key = Some(kp.1.clone());
-        let start_value =  SETUP.private_input;
-        println!("Private input chosen, {}", start_value);
-        let encrypted_value = Paillier::encrypt(&kp.0, start_value);
-        let encrypted_value_bigint = get_bigint_from_encoded_ciphertext(&encrypted_value);
-        let new_msg = FirstRoundResponse{
-            computed_value: encrypted_value_bigint,
-            num_parties: init.num_parties,
-            sid: init.sid + 1,
-            n_squared:kp.0.nn,
-            n: kp.0.n
-        };
-        self.send_unicast(init.sid, init.sid+1, ClientMessage::FirstRoundResponse(new_msg), ctx);
-        
-        
-    }
-
-    pub fn second_round_response(&self, response: SecondRoundResponse, ctx: &mut ws::WebsocketContext<Self>) {
-        // Handle the second round response
-        let data = response;
-        let resp = data.computed_value;
-        println!("Received second round response: {:?}", resp);
-        
-        // if this the first client, decrypt the final result and print it
-        if data.sid == 0 {
-            if let Some(dec_key) = &self.decryption_key {
-                let rct = RawCiphertext::from(resp);
-                let decrypted_result = Paillier::decrypt(dec_key, &rct);
-                let result = decrypted_result.0.into_owned();
-                println!("Final decrypted result: {}", result);
-                let result_val = serde_json::to_value(result).unwrap_or_else(|e|{
-                    println!("Failed to convert value to the json");
-                    serde_json::Value::Null
-                });
-                let relayer_msg = RelayerMessage::new(data.sid, result_val);
-                self.send_relayer_msg(relayer_msg, ctx);
-            } else {
-                eprintln!("Decryption key not found for the first client");
-            }
-            return;
+        if let (Some(recorder), Some(sid)) = (&mut self.recorder, self.party.sid()) {
+            recorder.record(Endpoint::Client(sid), Endpoint::Server, &json_str);
         }
-        let enc_key:EncryptionKey  = EncryptionKey {
-            n: data.n.clone(),
-            nn: data.n_squared.clone(),
-        };
-        let ct = Paillier::encrypt(&enc_key, SETUP.random_value);
-        println!("Random value chosen, {}", SETUP.random_value);
-
-        let ct_raw = get_bigint_from_encoded_ciphertext(&ct);
-        BigInt::mod_inv(&ct_raw, &enc_key.nn)
-            .map(|inv| {
-                let new_ct = BigInt::mod_mul(&resp, &inv, &enc_key.nn);
-                // get_bigint_from_encoded_ciphertext(new)
-                let new_response = SecondRoundResponse{
-                    computed_value: new_ct,
-                    n_squared: enc_key.nn,
-                    num_parties: data.num_parties,
-                    sid: data.sid - 1,
-                    n: enc_key.n,
-                };
-                self.send_unicast(data.sid, data.sid-1, ClientMessage::SecondRoundResponse(new_response), ctx);
-            }).unwrap_or_else(|| {
-                eprintln!("Failed to compute modular inverse");
-            });
-    }
-
-    pub fn first_round_response(&self, response: FirstRoundResponse, ctx: &mut ws::WebsocketContext<Self>) {
-        // Handle the first round response
-        println!("Received first round response: {:?}", response);
-        // get the computed value from the response and raise it to the power of 
-        let data = response;
-        let computed_value = data.computed_value;
-        let new_ct = BigInt::mod_pow(&computed_value, &BigInt::from(SETUP.private_input), &data.n_squared);
-        println!("Private input chosen, {}", SETUP.private_input);
-
-        if data.sid == data.num_parties - 1 {
-            // if this is the last client, send the SecondRoundResponse to itself so that second round can start
-            let new_msg = SecondRoundResponse{
-                computed_value: new_ct,
-                num_parties: data.num_parties,
-                sid: data.sid ,
-                n_squared: data.n_squared,
-                n: data.n
-            };
-            self.send_unicast(data.sid, data.sid, ClientMessage::SecondRoundResponse(new_msg), ctx);
+        ctx.text(json_str);
 
-        }
-        else{
-            let new_msg = FirstRoundResponse{
-                computed_value: new_ct,
-                num_parties: data.num_parties,
-                sid: data.sid + 1,
-                n_squared: data.n_squared,
-                n: data.n
-            };
-            self.send_unicast(data.sid, data.sid + 1, ClientMessage::FirstRoundResponse(new_msg), ctx);
-        }
-          
     }
 }
 
@@ -166,26 +97,16 @@ impl StreamHandler<Result<actix_http::ws::Message, ws::ProtocolError>> for Clien
         match msg {
             Ok(actix_http::ws::Message::Text(text)) => {
                 println!("Received text message: {}", text);
-                let msg = match serde_json::from_str::<ClientMessage>(&text){
-                    Ok(message) => message,
-                    Err(e) => {
-                        println!("Failed to parse message: {}", e);
-                        // this is basically not being able to parse the result sent by one of the clients. which is fatal
-                        // should be handled more gracefully.
-                        return;
-                    }
-                };
-                match msg {
-                    ClientMessage::InitializeProtocol(init) => {
-                        self.start_protocol(init, ctx);
-                    }
-                    ClientMessage::FirstRoundResponse(msg) => {
-                        self.first_round_response(msg, ctx);
-                    }
-                    ClientMessage::SecondRoundResponse(msg) => {
-                        self.second_round_response(msg, ctx);
-                    }
-
+                if !self.started {
+                    self.start(&text);
+                }
+                let replies = self.party.handle_text(&text);
+                // the sid is only known once the first message from the server is handled
+                if let (Some(recorder), Some(sid)) = (&mut self.recorder, self.party.sid()) {
+                    recorder.record(Endpoint::Server, Endpoint::Client(sid), &text);
+                }
+                for reply in replies {
+                    self.send_json(&reply, ctx);
                 }
             }
             Ok(actix_http::ws::Message::Binary(bin)) => {
@@ -194,6 +115,7 @@ impl StreamHandler<Result<actix_http::ws::Message, ws::ProtocolError>> for Clien
             }
             Ok(actix_http::ws::Message::Close(reason)) => {
                 println!("WebSocket closed: {:?}", reason);
+                self.closed = true;
                 ctx.close(reason);
             }
             Ok(_) => {
@@ -206,5 +128,3 @@ impl StreamHandler<Result<actix_http::ws::Message, ws::ProtocolError>> for Clien
         }
     }
 }
-
-
@@ -1,11 +1,34 @@
+use std::time::Duration;
+
+use shared::profile::SecurityProfile;
+
 pub struct Setup{
     pub port: u16,
-    pub private_input: u64,
-    pub random_value: u64,
+    // file with the private input vectors, read when the server connects and wiped from memory once the session
+    // is over. One vector per line, its elements separated by commas, each a decimal string so that inputs are
+    // not limited to 64 bits. Every element may be signed and have as many fractional digits as the scale
+    // announced by the server, e.g. "-12.50". The number of elements must match the vector length of the
+    // session. A Product computation names the input vector it uses by its index, i.e. its line.
+    pub inputs_file: &'static str,
+    // file to record this client's side of the session to (see shared::transcript), None to record nothing.
+    // The server's transcript can be checked against it with the verify-transcript tool.
+    pub transcript: Option<&'static str>,
+    // encrypted file keeping this client's identity key, the key pairs it generates as first client and its
+    // shares of the results across restarts (see shared::vault), None to keep nothing. Its passphrase is read
+    // from the SMPC_VAULT_PASSPHRASE environment variable; without it, the client runs without the vault.
+    pub vault: Option<&'static str>,
+    // how long a session whose connection dropped is kept for the server to resume it, after which it is wiped.
+    // The server gives up on the client after its own grace period.
+    pub resume_grace: Duration,
+    // weakest security profile this client takes part in, whatever the server announces
+    pub min_profile: SecurityProfile,
 }
 
 pub static SETUP: Setup = Setup {
     port: 8082,
-    private_input: 37,
-    random_value: 7,
-};
\ No newline at end of file
+    inputs_file: "inputs.txt",
+    transcript: None,
+    vault: Some("vault.json"),
+    resume_grace: Duration::from_secs(60),
+    min_profile: SecurityProfile::Standard2048,
+};
@@ -1,2 +1,3 @@
 pub mod client_actor;
-pub mod consts;
\ No newline at end of file
+pub mod consts;
+pub mod parked;
\ No newline at end of file
@@ -0,0 +1,45 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use shared::party::Party;
+use shared::transcript::Recorder;
+use shared::types::ResumeRequest;
+
+use crate::actor::consts::SETUP;
+
+/// A session whose connection to the server dropped, kept for the server to resume it on a new connection
+/// (see shared::resume).
+pub struct Parked {
+    pub party: Party,
+    pub recorder: Option<Recorder>,
+}
+
+// the session kept, if any, with the number it was parked under. A client takes part in one session at a time.
+static PARKED: Mutex<Option<(u64, Parked)>> = Mutex::new(None);
+static PARKINGS: AtomicU64 = AtomicU64::new(0);
+
+/// Keep a session for SETUP.resume_grace, after which its party is wiped.
+pub fn park(parked: Parked) {
+    let number = PARKINGS.fetch_add(1, Ordering::Relaxed);
+    let replaced = PARKED.lock().unwrap_or_else(|e| e.into_inner()).replace((number, parked));
+    if let Some((_, mut replaced)) = replaced {
+        replaced.party.wipe();
+    }
+    actix::spawn(async move {
+        actix::clock::sleep(SETUP.resume_grace).await;
+        let mut slot = PARKED.lock().unwrap_or_else(|e| e.into_inner());
+        if slot.as_ref().is_some_and(|(parked_as, _)| *parked_as == number) && let Some((_, mut expired)) = slot.take() {
+            println!("The server did not resume the session in time, wiping it");
+            expired.party.wipe();
+        }
+    });
+}
+
+/// The session kept, if the request is for it and carries its resume token.
+pub fn unpark(request: &ResumeRequest) -> Option<Parked> {
+    let mut slot = PARKED.lock().unwrap_or_else(|e| e.into_inner());
+    if slot.as_ref().is_some_and(|(_, parked)| parked.party.can_resume(request)) {
+        return slot.take().map(|(_, parked)| parked);
+    }
+    None
+}
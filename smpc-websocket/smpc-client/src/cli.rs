@@ -0,0 +1,34 @@
+// commands to inspect the client's vault without running the client
+use shared::vault::Vault;
+
+use crate::actor::consts::SETUP;
+
+/// Environment variable holding the passphrase of the vault.
+pub const PASSPHRASE_VAR: &str = "SMPC_VAULT_PASSPHRASE";
+
+/// The vault of SETUP, created on first use when `create` is set.
+pub fn open_vault(create: bool) -> anyhow::Result<Vault> {
+    let path = SETUP.vault.ok_or_else(|| anyhow::anyhow!("no vault is configured"))?;
+    let passphrase = std::env::var(PASSPHRASE_VAR).map_err(|_| anyhow::anyhow!("{} is not set", PASSPHRASE_VAR))?;
+    let vault = if create { Vault::open_or_create(path, &passphrase) } else { Vault::open(path, &passphrase) };
+    Ok(vault?)
+}
+
+/// `vault list`, `vault export <id>` or `vault delete <id>`.
+pub fn vault(args: &[String]) -> anyhow::Result<()> {
+    let mut vault = open_vault(false)?;
+    match args {
+        [command] if command == "list" => {
+            for entry in vault.entries() {
+                println!("{}\t{:?}\t{}", entry.id, entry.kind, entry.created);
+            }
+        }
+        [command, id] if command == "export" => {
+            let entry = vault.get(id).ok_or_else(|| anyhow::anyhow!("no vault entry '{}'", id))?;
+            println!("{}", entry.data);
+        }
+        [command, id] if command == "delete" => vault.delete(id)?,
+        _ => anyhow::bail!("usage: smpc-client vault list | export <id> | delete <id>"),
+    }
+    Ok(())
+}
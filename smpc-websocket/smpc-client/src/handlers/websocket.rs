@@ -4,5 +4,5 @@ use crate::actor::client_actor::ClientActor;
 pub async fn websocket(req:HttpRequest, stream:web::Payload)-> Result<HttpResponse, actix_web::Error> {
     // This function will handle the websocket connection
     println!("WebSocket connection established");
-    Ok(ws::start(ClientActor::new(), &req, stream)?)
+    ws::start(ClientActor::new(), &req, stream)
 }
\ No newline at end of file
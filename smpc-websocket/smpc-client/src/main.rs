@@ -1,17 +1,23 @@
 // start the web server and handle websocket connections
 mod handlers;
 mod actor;
+mod cli;
 
 
 use actix_web::{web, App, HttpServer};
 use handlers::connect_server::connect_to_server;
-use anyhow;
 use handlers::websocket::websocket;
 
 use crate::actor::consts::SETUP;
 
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
+    // process-wide: every GMP integer freed from now on is zeroed first
+    shared::secret::wipe_freed_memory();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("vault") {
+        return cli::vault(&args[1..]);
+    }
     let client_server = HttpServer::new(move || {
         App::new()
             .route("/", web::get().to(connect_to_server))
@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use crate::actor::server_message::{InitializeParameters, RegisterClient};
 use crate::errors::error_close::ErrorClose;
 
@@ -12,11 +14,77 @@ use awc::error::WsProtocolError;
 use awc::ws::{CloseCode, CloseReason, Codec, Frame, Message};
 use futures::future::try_join_all;
 use futures::StreamExt;
-use kzen_paillier::{KeyGeneration, Paillier};
 use awc::{BoxedSocket, Client};
 use crate::errors::websocket_error::WebsocketError;
 use crate::errors::server_error::ServerError;
-use shared::types::{ClientMessage, InitializeProtocol, WebsocketMessage};
+use shared::coordinator::{Coordinator, ServerAction, SessionConfig, SessionEnd};
+use shared::homomorphic::SchemeKind;
+use shared::profile::SecurityProfile;
+use shared::protocol::product;
+use shared::relay_log::SigningKey;
+use shared::transcript::{Endpoint, Recorder};
+use shared::types::{Computation, RevealMode, WebsocketMessage};
+
+// security level of every session, i.e. the size of the first client's key. Clients refuse sessions below their
+// own minimum profile, standard-2048 by default.
+const SECURITY_PROFILE: SecurityProfile = SecurityProfile::Standard2048;
+// public upper bound on the bit length of each party's private input. The first client checks that
+// num_parties * MAX_INPUT_BITS fits in its plaintext space before starting the protocol.
+const MAX_INPUT_BITS: usize = 256;
+// decimal digits kept after the point in every private input, e.g. 2 for salaries with cents.
+// The product of all the inputs then carries num_parties * INPUT_SCALE digits.
+const INPUT_SCALE: u32 = 2;
+// number of elements in every party's private input vector. Products are computed element-wise.
+const VECTOR_LEN: usize = 1;
+// additively homomorphic scheme the clients encrypt with. EC ElGamal has much smaller ciphertexts but can only
+// decrypt small values, so it is limited to sums revealed to the first client anyway.
+#[cfg(not(feature = "insecure-mock"))]
+const SCHEME: SchemeKind = SchemeKind::Paillier;
+// debug builds with the insecure-mock feature skip encryption entirely, for local simulations only
+#[cfg(feature = "insecure-mock")]
+const SCHEME: SchemeKind = SchemeKind::InsecureMock;
+// a client whose connection drops is dialed again every RECONNECT_INTERVAL, and the session is resumed with it
+// (see shared::resume). If it is not back within RESUME_GRACE, it drops out of the session if the computation in
+// progress tolerates dropouts, otherwise the session is aborted and the client blamed.
+const RESUME_GRACE: Duration = Duration::from_secs(60);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+// a computation that is not over this long after it started is aborted, blaming the clients that did not
+// report completion, e.g. because a message to or from them was lost.
+const COMPUTATION_TIMEOUT: Duration = Duration::from_secs(120);
+// every session is recorded to a transcript in this directory, one JSON line per frame (see shared::transcript),
+// which the verify-transcript tool checks. None records nothing.
+const TRANSCRIPT_DIR: Option<&str> = Some("transcripts");
+// whether transcripts keep the frames themselves, ciphertexts included, or only their hashes
+const TRANSCRIPT_PAYLOADS: bool = false;
+// computations run one after the other in the session. Every computation's result stays secret-shared
+// under its id, so later computations can consume it. Each protocol module of shared::protocol has a helper
+// to build its computation, e.g. (a*b)*c over inputs 0, 1 and 2:
+//   product::computation(0, 0, RevealMode::Private),
+//   product::computation(1, 1, RevealMode::Private),
+//   multiply::computation(2, 0, 1, RevealMode::Public),
+// A sum that still completes if some clients drop out, as long as 3 of them finish:
+//   secagg::computation(0, 0, 3),
+// The same product over Shamir shares, without public-key operations, for 3 clients or more with an honest
+// majority:
+//   bgw::computation(0, 0, RevealMode::Public),
+// a*b in one online round, with a Beaver triple made beforehand under ids 10, 11 and 12:
+//   let (mut plan, triple) = beaver::preprocessing(10);
+//   plan.push(beaver::computation(2, 0, 1, triple, RevealMode::Public));
+pub fn plan() -> Vec<Computation> {
+    vec![
+        product::computation(0, 0, RevealMode::Public),
+    ]
+}
+
+pub fn session_config() -> SessionConfig {
+    SessionConfig {
+        profile: SECURITY_PROFILE,
+        max_input_bits: MAX_INPUT_BITS,
+        scale: INPUT_SCALE,
+        vector_len: VECTOR_LEN,
+        scheme: SCHEME,
+    }
+}
 
 #[derive(PartialEq)]
 enum State{
@@ -30,10 +98,17 @@ pub struct ServerActor{
     clients: HashMap<u32, String>,
     total_clients: u32,
     state: State,
-    key_pair: Option<kzen_paillier::Keypair>,
-    sinks: Option<Vec<SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>>>
+    sinks: Option<Vec<WsSink>>,
+    // runs the plan of the session; the actor only moves its messages to and from the websockets
+    coordinator: Coordinator,
+    // epoch of the computation the timeout timer was last set for
+    timer_epoch: Option<u64>,
+    // transcript of the session in progress
+    recorder: Option<Recorder>,
 }
 
+type WsSink = SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>;
+
 // Message to handle websocket initialization result
 
 
@@ -43,47 +118,141 @@ impl Actor for ServerActor{
 
 impl ServerActor{
     pub fn new()->Self{
+        // a new key for every run of the server. Clients compare the checkpoints of the relay log against it.
+        let coordinator = Coordinator::new(session_config(), plan()).with_signing_key(SigningKey::generate());
+        println!("Relay log checkpoints are signed with key {}", coordinator.signing_key().verifying_key().to_hex());
         ServerActor{
             clients: HashMap::new(),
             total_clients: 0,
             state: State::ClientConnection,
-            key_pair: None,
-            sinks:None
+            sinks:None,
+            coordinator,
+            timer_epoch: None,
+            recorder: None,
         }
     }
 
-    pub fn handle_websocket_message(&mut self, msg: WebsocketMessage, client_index: usize, ctx: &mut <Self as Actor>::Context) {
-        
-        match msg {
-            WebsocketMessage::Unicast(response) => {
-                let wmsg = response.get_value();
-                // Handle the first round response
-                println!("Received Unicast from client {}: {:?}", client_index, response);
-                self.send_json(&wmsg, response.to, ctx);
+    /// Open the transcript of a new session in TRANSCRIPT_DIR.
+    fn start_transcript() -> Option<Recorder> {
+        let dir = TRANSCRIPT_DIR?;
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+        let path = format!("{}/session-{}.jsonl", dir, started);
+        match fs::create_dir_all(dir).and_then(|_| Recorder::create(&path, TRANSCRIPT_PAYLOADS)) {
+            Ok(recorder) => {
+                println!("Recording the session to {}", path);
+                Some(recorder)
             }
-            WebsocketMessage::Broadcast(response) => {
-                // Handle the first round response
-                println!("Received SecondRound from client {}: {:?}", client_index, response);
-                // send the response to all clients except the one broadcasting it.
+            Err(e) => {
+                eprintln!("Failed to create transcript {}: {}", path, e);
+                None
             }
-            WebsocketMessage::Relayer(response) =>{
-                println!("Got response from the first client. Not to forward but to indicate that the protocol is done");
-                println!("Final random decrypted value from the response: {}", response.data);
-                let reason = CloseReason::from(CloseCode::Normal);
-                self.close_all_websockets(&Some(reason));
-                
-                // reset the actor for potentially another round of SMPC
-                self.clients = HashMap::new();
-                self.state =State::ClientConnection;
-                self.total_clients = 0;
-                self.key_pair = None;
-                self.sinks = None;
+        }
+    }
 
+    pub fn handle_websocket_message(&mut self, msg: WebsocketMessage, client_index: usize, ctx: &mut <Self as Actor>::Context) {
+        let actions = self.coordinator.handle_message(msg, client_index);
+        self.perform(actions, ctx);
+    }
+
+    /// Write what the coordinator asks for to the websockets.
+    fn perform(&mut self, actions: Vec<ServerAction>, ctx: &mut <Self as Actor>::Context) {
+        for action in actions {
+            match action {
+                ServerAction::Send { to, message } => {
+                    if !self.send_json(&message, to, ctx) {
+                        return;
+                    }
+                }
+                ServerAction::Close(end) => {
+                    println!("Session {}", end);
+                    let reason = match end {
+                        SessionEnd::Completed => CloseReason::from(CloseCode::Normal),
+                        aborted => CloseReason { code: CloseCode::Policy, description: Some(aborted.to_string()) },
+                    };
+                    self.close_all_websockets(&Some(reason));
+                    self.reset();
+                }
             }
-            _ => {
-                eprintln!("Received unexpected message type: {:?}", msg);
-            }
         }
+        self.set_timeout(ctx);
+    }
+
+    /// Start the timer of the computation in progress, if it has not been started yet.
+    fn set_timeout(&mut self, ctx: &mut <Self as Actor>::Context) {
+        let Some(epoch) = self.coordinator.epoch() else {
+            return;
+        };
+        if self.timer_epoch == Some(epoch) {
+            return;
+        }
+        self.timer_epoch = Some(epoch);
+        ctx.run_later(COMPUTATION_TIMEOUT, move |act, ctx| {
+            let actions = act.coordinator.timeout(epoch);
+            act.perform(actions, ctx);
+        });
+    }
+
+    /// The connection to a client dropped while a session is running: hold its messages, dial it again and give
+    /// up on it after the grace period.
+    fn connection_lost(&mut self, client_index: usize, ctx: &mut <Self as Actor>::Context) {
+        let Some(session_id) = self.coordinator.session_id().map(str::to_string) else {
+            return;
+        };
+        if !self.coordinator.disconnected(client_index) {
+            return;
+        }
+        let expected = session_id.clone();
+        ctx.run_later(RESUME_GRACE, move |act, ctx| {
+            if act.coordinator.session_id() == Some(expected.as_str()) {
+                let actions = act.coordinator.lost(client_index);
+                act.perform(actions, ctx);
+            }
+        });
+        self.redial(client_index, session_id, ctx);
+    }
+
+    /// Connect to a client whose connection dropped and ask it to resume the session, retrying until the session
+    /// is over or the client dropped out of it.
+    fn redial(&mut self, client_index: usize, session_id: String, ctx: &mut <Self as Actor>::Context) {
+        let Some(url) = self.clients.get(&(client_index as u32)).cloned() else {
+            return;
+        };
+        ctx.spawn(actix::fut::wrap_future(
+            async move {
+                println!("Reconnecting to client {} at URL: {}", client_index, url);
+                WebsocketError::connect(Client::builder().finish().ws(&url)).await
+            }).map(move |connection, act: &mut ServerActor, ctx: &mut <ServerActor as Actor>::Context| {
+                if act.coordinator.session_id() != Some(session_id.as_str()) || !act.coordinator.awaiting(client_index) {
+                    return;
+                }
+                match connection {
+                    Ok(connection) => {
+                        let (sink, source) = connection.split();
+                        ctx.add_stream(source.map(move |item| (client_index as u32, item)));
+                        if let Some(slot) = act.sinks.as_mut().and_then(|sinks| sinks.get_mut(client_index)) {
+                            *slot = SinkWrite::new(sink, ctx);
+                        }
+                        if let Some(request) = act.coordinator.resume_request(client_index) {
+                            act.send_json(&request, client_index, ctx);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to reconnect to client {}: {:?}", client_index, e);
+                        ctx.run_later(RECONNECT_INTERVAL, move |act, ctx| act.redial(client_index, session_id, ctx));
+                    }
+                }
+            })
+        );
+    }
+
+    // reset the actor for potentially another round of SMPC
+    fn reset(&mut self) {
+        self.clients = HashMap::new();
+        self.state = State::ClientConnection;
+        self.total_clients = 0;
+        self.sinks = None;
+        self.recorder = None;
+        self.coordinator.reset();
     }
 
     // fn error_close(&mut self, error: impl Into<ErrorClose>, ctx: &mut <Self as Actor>::Context) {
@@ -126,8 +295,8 @@ impl ServerActor{
                 );
             }
             let sink = sink.unwrap();
-            if let Err(_) = sink.write(message) {
-                return eprintln!("Error writing message: Sink is closed or closing");
+            if sink.write(message).is_err() {
+                eprintln!("Error writing message: Sink is closed or closing");
             }
         }
         
@@ -142,7 +311,12 @@ impl ServerActor{
     {
         let serialized = serde_json::to_string(data);
         match serialized {
-            Ok(ref json) => self.text(client_index, json),
+            Ok(ref json) => {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.record(Endpoint::Server, Endpoint::Client(client_index), json);
+                }
+                self.text(client_index, json)
+            }
             Err(ref e) => self.error_close((CloseCode::Error, format!("{}", e)), ctx),
         }
 
@@ -196,6 +370,10 @@ impl ServerActor{
 
     fn close_all_websockets(&mut self, close_reason: &Option<CloseReason>) {
         for index in 0..self.total_clients as usize {
+            if let Some(recorder) = &mut self.recorder {
+                let description = close_reason.as_ref().and_then(|r| r.description.as_deref()).unwrap_or("completed");
+                recorder.record_close(index, description);
+            }
             self.close(index, close_reason.clone());
         }
     }
@@ -242,24 +420,23 @@ impl Handler<InitializeParameters> for ServerActor {
         // call the function to separate the source with id from each client and sink will be combined.
 
         if self.total_clients > 1 {
-            let kp = Paillier::keypair_with_modulus_size(2048);
-            self.key_pair = Some(kp);
-            let clients: Vec<(u32, String)> = self.clients.iter().map(|(&seq, url)| (seq, url.clone())).collect();
+            let mut clients: Vec<(u32, String)> = self.clients.iter().map(|(&seq, url)| (seq, url.clone())).collect();
+            // sinks are indexed by the client's sequence number, so connect in that order
+            clients.sort_by_key(|(seq, _)| *seq);
             // Spawn a future to connect to all clients, then send a message to self with the result
             ctx.spawn(actix::fut::wrap_future(
                 async move {
-                    let websockets = try_join_all(clients.into_iter().map(|(seq, url)| {
+                    try_join_all(clients.into_iter().map(|(seq, url)| {
                         async move {
                             println!("Connecting to client {} at URL: {}", seq, url);
                             let request = Client::builder().finish().ws(&url);
                             let connection_stream = WebsocketError::connect(request)
                                 .await
-                                .map_err(|e| ServerError::InitializationError(e))?;
+                                .map_err(ServerError::InitializationError)?;
                             Result::<_, ServerError>::Ok((seq, connection_stream))
                         }
                         
-                    })).await;
-                    websockets
+                    })).await
                 }).map(|websockets, act:&mut ServerActor, ctx: &mut <ServerActor as Actor>::Context| {
                     match websockets {
                         Ok(websockets) => {
@@ -279,19 +456,12 @@ impl Handler<InitializeParameters> for ServerActor {
                             act.state = State::FirstRound;
                             act.sinks = Some(sinks);
                             println!("Transitioned to FirstRound state.");
-
-                            let client_params: ClientMessage = ClientMessage::InitializeProtocol((InitializeProtocol{
-                                bits_security: 2048,
-                                num_parties: act.total_clients as usize,
-                                sid: 0
-
-                            }));
-
-                            act.send_json(&client_params, 0, ctx);
+                            act.recorder = Self::start_transcript();
+                            let actions = act.coordinator.start(act.total_clients as usize);
+                            act.perform(actions, ctx);
                         }
                         Err(e) => {
-                            eprintln!("Error connecting to clients");
-                            return;
+                            eprintln!("Error connecting to clients: {}", e);
                         }
                     }
                 })
@@ -312,6 +482,9 @@ impl StreamHandler<(u32, Result<Frame, WsProtocolError>)> for ServerActor{
                 match frame {
                     Frame::Text(text) => {
                         println!("Text frame from client {}: {}", id, String::from_utf8_lossy(&text));
+                        if let Some(recorder) = &mut self.recorder {
+                            recorder.record(Endpoint::Client(id as usize), Endpoint::Server, &String::from_utf8_lossy(&text));
+                        }
                         // Process text frame
                         let msg = match serde_json::from_slice::<WebsocketMessage>(&text){
                             Ok(message) => message,
@@ -328,16 +501,9 @@ impl StreamHandler<(u32, Result<Frame, WsProtocolError>)> for ServerActor{
                     }
                     Frame::Close(_) => {
                         println!("Client {} has closed the connection.", id);
-                        // if let Some(sinks) = &mut self.sinks {
-                        //     for sink in sinks.iter_mut().enumerate() {
-                        //         if sink.0 == id as usize {
-                        //             sink.1.close(); // close returns a future usually
-                        //             println!("Actually closed the connection");
-                        //         }
-                        //     }
-                        // }
-                        // if the reason for closing the connection is normal from one of the clients, it is due to the completion of the protocol
-                        // Handle client disconnection if needed
+                        // clients only close on their own when they go away in the middle of a session: the
+                        // server closes every connection first once the session is over
+                        self.connection_lost(id as usize, ctx);
                     }
                     _ => {
                     eprintln!("Unexpected frame type from client {}: {:?}", id, frame);
@@ -346,23 +512,29 @@ impl StreamHandler<(u32, Result<Frame, WsProtocolError>)> for ServerActor{
             
             }
             (id, Err(e)) => {
-                eprintln!("Error receiving frame from client {}", id, );
-                // Handle the error as needed
+                eprintln!("Error receiving frame from client {}: {}", id, e);
+                self.connection_lost(id as usize, ctx);
             }
         }
     }
+
+    // the connection to a client ended, which connection_lost already handled if it happened during a session.
+    // The actor stays up for the next session.
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
 }
 
 
 
 impl WriteHandler<WsProtocolError> for ServerActor {
-  fn error(&mut self, error: WsProtocolError, ctx: &mut Self::Context) -> Running {
+  fn error(&mut self, error: WsProtocolError, _ctx: &mut Self::Context) -> Running {
     // Send a message to close the actor due to a websocket error
     // self.error_close(
     //   (CloseCode::Error, format!("Error writing websocket message: {}", error)),
     //   ctx,
     // );
+    eprintln!("Error writing websocket message: {}", error);
 
-    Running::Stop
+    // the connection dropped, which the stream of frames from the client reports as well
+    Running::Continue
   }
 }
@@ -1,5 +1,4 @@
 use actix::prelude::*;
-use serde::{Serialize, Deserialize};
 
 
 #[derive(Message)]
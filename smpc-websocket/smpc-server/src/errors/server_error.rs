@@ -7,6 +7,10 @@ pub enum ServerError{
     InitializationError(WebsocketError),
 }
 
-impl ServerError {
-    
-}
\ No newline at end of file
+impl Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerError::InitializationError(e) => write!(f, "Failed to initialize the websocket connection: {:?}", e),
+        }
+    }
+}
@@ -3,6 +3,7 @@ use awc::ws::{WebsocketsRequest};
 use actix_codec::Framed;
 
 #[derive(Debug)]
+#[allow(dead_code)]
 pub enum WebsocketError {
   RequestError(actix_web::Error),
   ClientError(WsClientError),
@@ -20,7 +21,7 @@ impl WebsocketError{
         let (response, frame) = request
         .connect()
         .await
-        .map_err(|e| WebsocketError::ClientError(e))?;
+        .map_err(WebsocketError::from_client_error)?;
         if response.status() == StatusCode::SWITCHING_PROTOCOLS {
             Ok(frame)
         }else{
@@ -32,6 +33,7 @@ impl WebsocketError{
         WebsocketError::ClientError(error)
     }
     
+    #[allow(dead_code)]
     pub fn from_protocol_error(error: WsProtocolError) -> Self {
         WebsocketError::ProtocolError(error)
     }
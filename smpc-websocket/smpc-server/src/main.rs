@@ -5,11 +5,12 @@ mod errors;
 use actor::server_actor::ServerActor;
 use actix::prelude::*;
 use actix_web::{web, App, HttpServer};
-use anyhow;
 mod test;
 
 #[actix_web::main]
 async fn main() -> anyhow::Result<()>{
+    // the server only holds secrets when it deals triples in test mode, but wiping is cheap
+    shared::secret::wipe_freed_memory();
     // make a server actor here that will be global. Pass the address as the webdata in the server.
     let server_addr = ServerActor::new().start();
     // define an endpoint to which different clients can connect
@@ -1,9 +1,11 @@
-use curv::arithmetic::{BigInt,Converter};
-
+#[cfg(test)]
 mod tests{
-    use curv::arithmetic::Modulo;
+    use curv::arithmetic::{BigInt, Converter, Modulo};
+    use shared::coordinator::{SessionConfig, SessionEnd};
+    use shared::profile::SecurityProfile;
+    use shared::simulator::{PrivateInputs, Simulator};
+    use crate::actor::server_actor::{plan, session_config};
 
-    use super::*;
     #[test]
     fn test_outputs() {
         let a = BigInt::from_str_radix("363", 10).unwrap();
@@ -14,4 +16,18 @@ mod tests{
         let mul = BigInt::mod_mul(&BigInt::from(10),&BigInt::from(37), &c);
         assert!(result==mul);
     }
+
+    #[test]
+    fn test_session_plan() {
+        // the server's own plan, under a weaker profile than the server's
+        let config = SessionConfig { profile: SecurityProfile::Test1024, ..session_config() };
+        let inputs = vec![
+            PrivateInputs::new(&[&["37"]]),
+            PrivateInputs::new(&[&["-2.5"]]),
+            PrivateInputs::new(&[&["0.1"]]),
+        ];
+        let report = Simulator::new(config, plan(), inputs).run();
+        assert_eq!(report.end, Some(SessionEnd::Completed));
+        assert_eq!(report.result(0).unwrap(), ["-9.250000"]);
+    }
 }